@@ -0,0 +1,102 @@
+//! Read-only HTTP/JSON API over the indexed history.
+//!
+//! - `GET /caves?depositor=&mint=&status=` lists caves, all filters optional
+//! - `GET /caves/<cave_info>` returns every cave that lived at the address,
+//!   with its events
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::db::{Cave, CaveEvent, CaveFilter, Db};
+
+
+/// Serves the API on `addr` until the server fails
+pub fn serve(db: Db, addr: &str) -> Result<()> {
+
+    let server = Server::http(addr).map_err(|e| anyhow!("failed to listen on {addr}: {e}"))?;
+    let content_type: Header = "Content-Type: application/json".parse()
+        .map_err(|_| anyhow!("invalid header"))?;
+    for request in server.incoming_requests() {
+        let (status, body) = if request.method() == &Method::Get {
+            match route(&db, request.url()) {
+                Ok(Some(body)) => (200, body),
+                Ok(None) => (404, json!({ "error": "not found" })),
+                Err(e) => (500, json!({ "error": e.to_string() })),
+            }
+        } else {
+            (405, json!({ "error": "method not allowed" }))
+        };
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(content_type.clone());
+        request.respond(response)?;
+    }
+    Ok(())
+}
+
+/// Answers a GET request for `url`, or None if there's no such resource
+pub fn route(db: &Db, url: &str) -> Result<Option<Value>> {
+
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    match segments.as_slice() {
+        ["caves"] => {
+            let mut filter = CaveFilter::default();
+            for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+                match key {
+                    "depositor" => filter.depositor = Some(value.to_string()),
+                    "mint" => filter.mint = Some(value.to_string()),
+                    "status" => filter.status = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+            let caves: Vec<Value> = db.caves(&filter)?.iter().map(cave_json).collect();
+            Ok(Some(Value::Array(caves)))
+        }
+        ["caves", cave_info] => {
+            let caves = db.caves_at(cave_info)?;
+            if caves.is_empty() {
+                return Ok(None);
+            }
+            let mut history = Vec::new();
+            for cave in &caves {
+                let mut value = cave_json(cave);
+                value["events"] = db.events(cave.id)?.iter().map(event_json).collect();
+                history.push(value);
+            }
+            Ok(Some(Value::Array(history)))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn cave_json(cave: &Cave) -> Value {
+    json!({
+        "cave_info": cave.cave_info,
+        "kind": cave.kind,
+        "cave": cave.cave,
+        "mint": cave.mint,
+        "depositor": cave.depositor,
+        "backup_address": cave.backup_address,
+        "deposit_amount": cave.deposit_amount,
+        "timelock_duration": cave.timelock_duration,
+        "timelock_unit": cave.timelock_unit,
+        "withdraw_window": cave.withdraw_window,
+        "status": cave.status,
+        "created_slot": cave.created_slot,
+        "created_at": cave.created_at,
+        "closed_slot": cave.closed_slot,
+        "closed_at": cave.closed_at,
+    })
+}
+
+fn event_json(event: &CaveEvent) -> Value {
+    json!({
+        "kind": event.kind,
+        "signature": event.signature,
+        "slot": event.slot,
+        "block_time": event.block_time,
+        "amount": event.amount,
+        "account": event.account,
+    })
+}
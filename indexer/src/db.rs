@@ -0,0 +1,359 @@
+//! SQLite storage of the cave history.
+//!
+//! A cave address is reused when a depositor token account gets a new cave
+//! after the old one closed, so caves are keyed by a row id and events
+//! belong to the latest cave at their `cave_info` address.
+use anyhow::Result;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Row};
+
+use crate::decode::CaveInstruction;
+
+const SCHEMA: &str = "
+PRAGMA journal_mode = WAL;
+
+CREATE TABLE IF NOT EXISTS transactions (
+    signature TEXT PRIMARY KEY,
+    slot INTEGER NOT NULL,
+    block_time INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS caves (
+    id INTEGER PRIMARY KEY,
+    cave_info TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    cave TEXT NOT NULL,
+    mint TEXT NOT NULL,
+    depositor TEXT NOT NULL,
+    backup_address TEXT,
+    deposit_amount INTEGER NOT NULL,
+    timelock_duration INTEGER NOT NULL,
+    timelock_unit TEXT NOT NULL,
+    withdraw_window INTEGER,
+    status TEXT NOT NULL,
+    created_slot INTEGER NOT NULL,
+    created_at INTEGER,
+    closed_slot INTEGER,
+    closed_at INTEGER
+);
+CREATE INDEX IF NOT EXISTS caves_cave_info ON caves (cave_info);
+CREATE INDEX IF NOT EXISTS caves_depositor ON caves (depositor);
+CREATE INDEX IF NOT EXISTS caves_mint ON caves (mint);
+
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY,
+    cave_id INTEGER NOT NULL REFERENCES caves (id),
+    kind TEXT NOT NULL,
+    signature TEXT NOT NULL REFERENCES transactions (signature),
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    amount INTEGER,
+    account TEXT
+);
+CREATE INDEX IF NOT EXISTS events_cave_id ON events (cave_id);
+";
+
+const CAVE_COLUMNS: &str = "id, cave_info, kind, cave, mint, depositor, backup_address, \
+    deposit_amount, timelock_duration, timelock_unit, withdraw_window, status, \
+    created_slot, created_at, closed_slot, closed_at";
+
+
+/// A decoded instruction of an indexed transaction
+pub struct Event {
+
+    pub instruction: CaveInstruction,
+
+    /// Tokens that left or entered the cave, if the instruction moved any,
+    /// or the lamports of a new stake cave
+    pub amount: Option<u64>,
+}
+
+/// A cave as stored in the database
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cave {
+    pub id: i64,
+    pub cave_info: String,
+
+    /// One of `Token`, `Stake`, `ProgrammableNft` and `Basket`
+    pub kind: String,
+
+    /// The stake account of a stake cave, and the basket key of a basket
+    pub cave: String,
+
+    /// The default pubkey for stake caves and baskets
+    pub mint: String,
+    pub depositor: String,
+    pub backup_address: Option<String>,
+
+    /// Lamports for stake caves, and zero for baskets, which hold several
+    /// mints
+    pub deposit_amount: u64,
+    pub timelock_duration: u32,
+    pub timelock_unit: String,
+    pub withdraw_window: Option<u32>,
+
+    /// One of `locked`, `unlocking`, `withdrawn`, `withdrawn_to_backup`,
+    /// `aborted`, `clawed_back`
+    /// and `closed`, for caves closed by an instruction that wasn't
+    /// decoded
+    pub status: String,
+    pub created_slot: u64,
+    pub created_at: Option<i64>,
+    pub closed_slot: Option<u64>,
+    pub closed_at: Option<i64>,
+}
+
+/// An event in the history of a cave
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaveEvent {
+    pub kind: String,
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub amount: Option<u64>,
+
+    /// Account the funds went to, for instructions that close the cave and
+    /// name a single destination
+    pub account: Option<String>,
+}
+
+/// Filters for `Db::caves`. None matches anything.
+#[derive(Clone, Debug, Default)]
+pub struct CaveFilter {
+    pub depositor: Option<String>,
+    pub mint: Option<String>,
+    pub status: Option<String>,
+}
+
+pub struct Db {
+    conn: Connection,
+}
+
+impl Db {
+
+    /// Opens the database for indexing, creating the tables if needed
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Db { conn })
+    }
+
+    /// Opens an existing database for queries only
+    pub fn open_read_only(path: &str) -> Result<Self> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Ok(Db { conn })
+    }
+
+    /// Whether a transaction was indexed already
+    pub fn is_indexed(&self, signature: &str) -> Result<bool> {
+        Ok(self.conn
+            .query_row(
+                "SELECT 1 FROM transactions WHERE signature = ?1",
+                params![signature],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+
+    /// The latest indexed transaction, where a backfill can stop
+    pub fn last_signature(&self) -> Result<Option<String>> {
+        Ok(self.conn
+            .query_row(
+                "SELECT signature FROM transactions ORDER BY slot DESC, rowid DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Stores a transaction and its events. A transaction that was indexed
+    /// already is ignored, so transactions can be fed more than once.
+    pub fn record_transaction(
+        &mut self,
+        signature: &str,
+        slot: u64,
+        block_time: Option<i64>,
+        events: &[Event],
+    ) -> Result<()> {
+
+        let tx = self.conn.transaction()?;
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO transactions (signature, slot, block_time) VALUES (?1, ?2, ?3)",
+            params![signature, slot, block_time],
+        )?;
+        if inserted == 0 {
+            return Ok(());
+        }
+
+        for event in events {
+            let cave_info = event.instruction.cave_info().to_string();
+            let mut account = None;
+            let cave_id = match &event.instruction {
+                CaveInstruction::Initialize {
+                    kind,
+                    cave,
+                    mint,
+                    depositor,
+                    backup_address,
+                    timelock_duration,
+                    timelock_unit,
+                    withdraw_window,
+                    ..
+                } => {
+                    tx.execute(
+                        "INSERT INTO caves (cave_info, kind, cave, mint, depositor, backup_address, \
+                            deposit_amount, timelock_duration, timelock_unit, withdraw_window, \
+                            status, created_slot, created_at) \
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, 'locked', ?11, ?12)",
+                        params![
+                            cave_info,
+                            format!("{kind:?}"),
+                            cave.to_string(),
+                            mint.to_string(),
+                            depositor.to_string(),
+                            backup_address.map(|key| key.to_string()),
+                            event.amount.unwrap_or(0),
+                            timelock_duration,
+                            format!("{timelock_unit:?}"),
+                            withdraw_window,
+                            slot,
+                            block_time,
+                        ],
+                    )?;
+                    tx.last_insert_rowid()
+                }
+                instruction => {
+
+                    // Caves created before the indexed history can't be
+                    // tracked
+                    let cave: Option<(i64, String)> = tx
+                        .query_row(
+                            "SELECT id, status FROM caves WHERE cave_info = ?1 \
+                            ORDER BY id DESC LIMIT 1",
+                            params![cave_info],
+                            |row| Ok((row.get(0)?, row.get(1)?)),
+                        )
+                        .optional()?;
+                    let (cave_id, current_status) = match cave {
+                        Some(cave) => cave,
+                        None => continue,
+                    };
+                    let (status, closed, destination) = match instruction {
+                        CaveInstruction::Unlock { .. } => ("unlocking", false, None),
+                        CaveInstruction::Close { closing, destination, .. } => {
+                            (closing.status(), true, *destination)
+                        }
+
+                        // Only caves that are still open can close
+                        CaveInstruction::Closed { .. } => {
+                            if current_status != "locked" && current_status != "unlocking" {
+                                continue;
+                            }
+                            ("closed", true, None)
+                        }
+                        CaveInstruction::Initialize { .. } => unreachable!(),
+                    };
+                    if closed {
+                        tx.execute(
+                            "UPDATE caves SET status = ?1, closed_slot = ?2, closed_at = ?3 \
+                            WHERE id = ?4",
+                            params![status, slot, block_time, cave_id],
+                        )?;
+                    } else {
+                        tx.execute(
+                            "UPDATE caves SET status = ?1 WHERE id = ?2",
+                            params![status, cave_id],
+                        )?;
+                    }
+                    account = destination.map(|destination| destination.to_string());
+                    cave_id
+                }
+            };
+
+            tx.execute(
+                "INSERT INTO events (cave_id, kind, signature, slot, block_time, amount, account) \
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    cave_id,
+                    event.instruction.kind(),
+                    signature,
+                    slot,
+                    block_time,
+                    event.amount,
+                    account,
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Caves matching `filter`, oldest first
+    pub fn caves(&self, filter: &CaveFilter) -> Result<Vec<Cave>> {
+        let mut statement = self.conn.prepare(&format!(
+            "SELECT {CAVE_COLUMNS} FROM caves \
+            WHERE (?1 IS NULL OR depositor = ?1) \
+            AND (?2 IS NULL OR mint = ?2) \
+            AND (?3 IS NULL OR status = ?3) \
+            ORDER BY id"
+        ))?;
+        let caves = statement
+            .query_map(params![filter.depositor, filter.mint, filter.status], cave_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(caves)
+    }
+
+    /// Every cave that lived at a `cave_info` address, oldest first
+    pub fn caves_at(&self, cave_info: &str) -> Result<Vec<Cave>> {
+        let mut statement = self.conn.prepare(&format!(
+            "SELECT {CAVE_COLUMNS} FROM caves WHERE cave_info = ?1 ORDER BY id"
+        ))?;
+        let caves = statement
+            .query_map(params![cave_info], cave_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(caves)
+    }
+
+    /// Events of a cave in order
+    pub fn events(&self, cave_id: i64) -> Result<Vec<CaveEvent>> {
+        let mut statement = self.conn.prepare(
+            "SELECT kind, signature, slot, block_time, amount, account FROM events \
+            WHERE cave_id = ?1 ORDER BY id"
+        )?;
+        let events = statement
+            .query_map(params![cave_id], |row| {
+                Ok(CaveEvent {
+                    kind: row.get(0)?,
+                    signature: row.get(1)?,
+                    slot: row.get(2)?,
+                    block_time: row.get(3)?,
+                    amount: row.get(4)?,
+                    account: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(events)
+    }
+}
+
+fn cave_from_row(row: &Row) -> rusqlite::Result<Cave> {
+    Ok(Cave {
+        id: row.get(0)?,
+        cave_info: row.get(1)?,
+        kind: row.get(2)?,
+        cave: row.get(3)?,
+        mint: row.get(4)?,
+        depositor: row.get(5)?,
+        backup_address: row.get(6)?,
+        deposit_amount: row.get(7)?,
+        timelock_duration: row.get(8)?,
+        timelock_unit: row.get(9)?,
+        withdraw_window: row.get(10)?,
+        status: row.get(11)?,
+        created_slot: row.get(12)?,
+        created_at: row.get(13)?,
+        closed_slot: row.get(14)?,
+        closed_at: row.get(15)?,
+    })
+}
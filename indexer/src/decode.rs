@@ -0,0 +1,311 @@
+//! Decoding of token cave instructions from transaction data.
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::AnchorDeserialize;
+use solana_sdk::pubkey::Pubkey;
+use token_cave::instructions::batch_abort::BATCH_ABORT_GROUP_SIZE;
+use token_cave::instructions::batch_withdraw::BATCH_WITHDRAW_GROUP_SIZE;
+use token_cave::instructions::initialize::{CaveKind, TimelockUnit};
+
+/// Positions of the accounts the indexer reads, following the field order
+/// of the program's `Accounts` structs. `initialize`, `initialize_gift`
+/// and `initialize_pnft` share the first four.
+pub const INITIALIZE_CAVE: usize = 0;
+pub const INITIALIZE_CAVE_INFO: usize = 1;
+pub const INITIALIZE_MINT: usize = 2;
+pub const INITIALIZE_DEPOSITOR: usize = 3;
+pub const INITIALIZE_STAKE_STAKE_ACCOUNT: usize = 0;
+pub const INITIALIZE_STAKE_CAVE_INFO: usize = 1;
+pub const INITIALIZE_STAKE_DEPOSITOR: usize = 2;
+pub const INITIALIZE_BASKET_CAVE_INFO: usize = 0;
+pub const INITIALIZE_BASKET_DEPOSITOR: usize = 1;
+pub const UNLOCK_CAVE_INFO: usize = 0;
+
+/// First remaining account of `withdraw`, which is the backup's token
+/// account when a duress unlock routes the funds to the backup
+pub const WITHDRAW_DURESS_BACKUP: usize = 10;
+
+/// Accounts before the repeating groups of the batch instructions
+pub const BATCH_WITHDRAW_FIXED_ACCOUNTS: usize = 2;
+pub const BATCH_ABORT_FIXED_ACCOUNTS: usize = 9;
+
+/// Instructions that close a single cave, with how they close it and the
+/// positions of the cave token account, the cave info and the account the
+/// funds go to. Stake and basket caves have no single token account, and
+/// `abort_stake` and `abort_basket` don't name a single destination.
+const CLOSE_LAYOUTS: &[(&str, Closing, Option<usize>, usize, Option<usize>)] = &[
+    ("withdraw", Closing::Withdraw, Some(0), 1, Some(5)),
+    ("claim_htlc", Closing::Withdraw, Some(0), 1, Some(3)),
+    ("release_on_price", Closing::Withdraw, Some(0), 1, Some(5)),
+    ("withdraw_gift", Closing::Withdraw, Some(0), 1, Some(4)),
+    ("withdraw_pnft", Closing::Withdraw, Some(0), 1, Some(5)),
+    ("withdraw_stake", Closing::Withdraw, None, 1, Some(2)),
+    ("withdraw_basket", Closing::Withdraw, None, 0, None),
+    ("abort", Closing::Abort, Some(0), 1, Some(6)),
+    ("abort_with_signature", Closing::Abort, Some(0), 1, Some(5)),
+    ("abort_pnft", Closing::Abort, Some(0), 1, Some(6)),
+    ("abort_stake", Closing::Abort, None, 1, None),
+    ("abort_basket", Closing::Abort, None, 0, None),
+    ("clawback", Closing::Clawback, Some(0), 1, Some(4)),
+];
+
+
+/// An indexed token cave instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CaveInstruction {
+
+    /// `cave` is the stake account of a stake cave and the basket key of a
+    /// basket, whose mint is the default pubkey. `deposit_amount` is None
+    /// when it isn't an argument of the instruction.
+    Initialize {
+        kind: CaveKind,
+        cave: Pubkey,
+        cave_info: Pubkey,
+        mint: Pubkey,
+        depositor: Pubkey,
+        deposit_amount: Option<u64>,
+        backup_address: Option<Pubkey>,
+        timelock_duration: u32,
+        timelock_unit: TimelockUnit,
+        withdraw_window: Option<u32>,
+    },
+
+    Unlock {
+        cave_info: Pubkey,
+    },
+
+    /// `cave` is the token account the funds leave, and `destination` the
+    /// account they go to, when the instruction has a single one
+    Close {
+        closing: Closing,
+        cave: Option<Pubkey>,
+        cave_info: Pubkey,
+        destination: Option<Pubkey>,
+    },
+
+    /// The cave info account was closed by an instruction that isn't
+    /// decoded, e.g. one referring to it through an address lookup table
+    Closed {
+        cave_info: Pubkey,
+    },
+}
+
+/// How a cave was closed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Closing {
+
+    /// The funds went to the depositor, or to a beneficiary or hashlock
+    /// recipient
+    Withdraw,
+
+    /// The funds went to the backup
+    Abort,
+
+    /// A gift went back to its depositor
+    Clawback,
+
+    /// A duress unlock sent the funds of a withdraw to the backup
+    DuressWithdraw,
+}
+
+impl CaveInstruction {
+
+    /// The cave info account the instruction acts on
+    pub fn cave_info(&self) -> Pubkey {
+        match self {
+            CaveInstruction::Initialize { cave_info, .. }
+            | CaveInstruction::Unlock { cave_info }
+            | CaveInstruction::Close { cave_info, .. }
+            | CaveInstruction::Closed { cave_info } => *cave_info,
+        }
+    }
+
+    /// The cave token account, for instructions that move its funds
+    pub fn cave(&self) -> Option<Pubkey> {
+        match self {
+            CaveInstruction::Initialize { cave, .. } => Some(*cave),
+            CaveInstruction::Close { cave, .. } => *cave,
+            CaveInstruction::Unlock { .. } | CaveInstruction::Closed { .. } => None,
+        }
+    }
+
+    /// Event name stored in the database
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CaveInstruction::Initialize { .. } => "initialize",
+            CaveInstruction::Unlock { .. } => "unlock",
+            CaveInstruction::Close { closing: Closing::Withdraw, .. } => "withdraw",
+            CaveInstruction::Close { closing: Closing::Abort, .. } => "abort",
+            CaveInstruction::Close { closing: Closing::Clawback, .. } => "clawback",
+            CaveInstruction::Close { closing: Closing::DuressWithdraw, .. } => "duress_withdraw",
+            CaveInstruction::Closed { .. } => "closed",
+        }
+    }
+}
+
+impl Closing {
+
+    /// Status of a cave closed this way
+    pub fn status(&self) -> &'static str {
+        match self {
+            Closing::Withdraw => "withdrawn",
+            Closing::Abort => "aborted",
+            Closing::Clawback => "clawed_back",
+            Closing::DuressWithdraw => "withdrawn_to_backup",
+        }
+    }
+}
+
+/// Anchor's discriminator of the instruction `name`
+pub fn sighash(name: &str) -> [u8; 8] {
+    let mut discriminator = [0; 8];
+    discriminator.copy_from_slice(&hash(format!("global:{name}").as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+/// Decodes an instruction of the token cave program, given its data and
+/// its accounts in order. A batch yields one instruction per cave, which
+/// includes caves the batch skipped, so callers must check which caves
+/// were actually closed. Returns nothing for instructions that aren't
+/// indexed or don't decode.
+pub fn decode_instruction(data: &[u8], accounts: &[Pubkey]) -> Vec<CaveInstruction> {
+
+    if data.len() < 8 {
+        return Vec::new();
+    }
+    let (discriminator, args) = data.split_at(8);
+    if discriminator == sighash("batch_withdraw") {
+        return decode_batch(accounts, BATCH_WITHDRAW_FIXED_ACCOUNTS, BATCH_WITHDRAW_GROUP_SIZE, Closing::Withdraw);
+    }
+    if discriminator == sighash("batch_abort") {
+        return decode_batch(accounts, BATCH_ABORT_FIXED_ACCOUNTS, BATCH_ABORT_GROUP_SIZE, Closing::Abort);
+    }
+    decode_single(discriminator, args, accounts).into_iter().collect()
+}
+
+/// Decodes an instruction that acts on a single cave
+fn decode_single(discriminator: &[u8], args: &[u8], accounts: &[Pubkey]) -> Option<CaveInstruction> {
+
+    let account = |index: usize| accounts.get(index).copied();
+
+    if discriminator == sighash("initialize") {
+        let args = token_cave::instruction::Initialize::try_from_slice(args).ok()?;
+        Some(CaveInstruction::Initialize {
+            kind: CaveKind::Token,
+            cave: account(INITIALIZE_CAVE)?,
+            cave_info: account(INITIALIZE_CAVE_INFO)?,
+            mint: account(INITIALIZE_MINT)?,
+            depositor: account(INITIALIZE_DEPOSITOR)?,
+            deposit_amount: Some(args.deposit_amount),
+            backup_address: args.backup_address,
+            timelock_duration: args.timelock_duration,
+            timelock_unit: args.timelock_unit,
+            withdraw_window: args.withdraw_window,
+        })
+    } else if discriminator == sighash("initialize_gift") {
+        let args = token_cave::instruction::InitializeGift::try_from_slice(args).ok()?;
+        Some(CaveInstruction::Initialize {
+            kind: CaveKind::Token,
+            cave: account(INITIALIZE_CAVE)?,
+            cave_info: account(INITIALIZE_CAVE_INFO)?,
+            mint: account(INITIALIZE_MINT)?,
+            depositor: account(INITIALIZE_DEPOSITOR)?,
+            deposit_amount: Some(args.deposit_amount),
+            backup_address: None,
+            timelock_duration: args.timelock_duration,
+            timelock_unit: args.timelock_unit,
+            withdraw_window: args.withdraw_window,
+        })
+    } else if discriminator == sighash("initialize_pnft") {
+        let args = token_cave::instruction::InitializePnft::try_from_slice(args).ok()?;
+        Some(CaveInstruction::Initialize {
+            kind: CaveKind::ProgrammableNft,
+            cave: account(INITIALIZE_CAVE)?,
+            cave_info: account(INITIALIZE_CAVE_INFO)?,
+            mint: account(INITIALIZE_MINT)?,
+            depositor: account(INITIALIZE_DEPOSITOR)?,
+            deposit_amount: Some(1),
+            backup_address: args.backup_address,
+            timelock_duration: args.timelock_duration,
+            timelock_unit: args.timelock_unit,
+            withdraw_window: args.withdraw_window,
+        })
+    } else if discriminator == sighash("initialize_stake") {
+        let args = token_cave::instruction::InitializeStake::try_from_slice(args).ok()?;
+        Some(CaveInstruction::Initialize {
+            kind: CaveKind::Stake,
+            cave: account(INITIALIZE_STAKE_STAKE_ACCOUNT)?,
+            cave_info: account(INITIALIZE_STAKE_CAVE_INFO)?,
+            mint: Pubkey::default(),
+            depositor: account(INITIALIZE_STAKE_DEPOSITOR)?,
+            deposit_amount: None,
+            backup_address: args.backup_address,
+            timelock_duration: args.timelock_duration,
+            timelock_unit: args.timelock_unit,
+            withdraw_window: args.withdraw_window,
+        })
+    } else if discriminator == sighash("initialize_basket") {
+        let args = token_cave::instruction::InitializeBasket::try_from_slice(args).ok()?;
+        Some(CaveInstruction::Initialize {
+            kind: CaveKind::Basket,
+            cave: args.basket,
+            cave_info: account(INITIALIZE_BASKET_CAVE_INFO)?,
+            mint: Pubkey::default(),
+            depositor: account(INITIALIZE_BASKET_DEPOSITOR)?,
+            deposit_amount: None,
+            backup_address: args.backup_address,
+            timelock_duration: args.timelock_duration,
+            timelock_unit: args.timelock_unit,
+            withdraw_window: args.withdraw_window,
+        })
+    } else if discriminator == sighash("unlock") {
+        Some(CaveInstruction::Unlock {
+            cave_info: account(UNLOCK_CAVE_INFO)?,
+        })
+    } else {
+        let (_, closing, cave, cave_info, destination) = CLOSE_LAYOUTS
+            .iter()
+            .find(|(name, ..)| discriminator == sighash(name))?;
+        Some(CaveInstruction::Close {
+            closing: *closing,
+            cave: match cave {
+                Some(index) => Some(account(*index)?),
+                None => None,
+            },
+            cave_info: account(*cave_info)?,
+            destination: match destination {
+                Some(index) => Some(account(*index)?),
+                None => None,
+            },
+        })
+    }
+}
+
+/// The account a `withdraw` sends the funds to instead of the depositor
+/// token account if it turns out to be a duress withdraw. The instruction
+/// doesn't tell, so callers must check which of the two received them.
+pub fn duress_backup_account(data: &[u8], accounts: &[Pubkey]) -> Option<Pubkey> {
+    if data.get(..8)? != sighash("withdraw") {
+        return None;
+    }
+    accounts.get(WITHDRAW_DURESS_BACKUP).copied()
+}
+
+/// Decodes the `[cave, cave_info, destination, ..]` groups of a batch
+fn decode_batch(
+    accounts: &[Pubkey],
+    fixed_accounts: usize,
+    group_size: usize,
+    closing: Closing,
+) -> Vec<CaveInstruction> {
+    accounts
+        .get(fixed_accounts..)
+        .unwrap_or_default()
+        .chunks_exact(group_size)
+        .map(|group| CaveInstruction::Close {
+            closing,
+            cave: Some(group[0]),
+            cave_info: group[1],
+            destination: Some(group[2]),
+        })
+        .collect()
+}
@@ -0,0 +1,299 @@
+//! Fetching and indexing of the program's transactions.
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use solana_client::pubsub_client::PubsubClient;
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_client::rpc_config::{
+    RpcTransactionConfig,
+    RpcTransactionLogsConfig,
+    RpcTransactionLogsFilter,
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{
+    UiInstruction,
+    UiTransactionEncoding,
+    UiTransactionStatusMeta,
+    UiTransactionTokenBalance,
+};
+use token_cave::instructions::initialize::CaveKind;
+
+use crate::db::{Db, Event};
+use crate::decode::{decode_instruction, duress_backup_account, CaveInstruction, Closing};
+
+/// How often to retry fetching a transaction that was just notified, in
+/// case the RPC node hasn't stored it yet
+const FETCH_ATTEMPTS: usize = 5;
+const FETCH_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+
+pub struct Indexer {
+    rpc: RpcClient,
+    db: Db,
+}
+
+impl Indexer {
+
+    pub fn new(rpc_url: &str, db: Db) -> Self {
+        Indexer {
+            rpc: RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed()),
+            db,
+        }
+    }
+
+    /// Subscribes to the program's logs and indexes each confirmed
+    /// transaction. Transactions from before the subscription are
+    /// backfilled first. Returns when the subscription ends.
+    pub fn follow(&mut self, ws_url: &str) -> Result<()> {
+
+        let (_subscription, receiver) = PubsubClient::logs_subscribe(
+            ws_url,
+            RpcTransactionLogsFilter::Mentions(vec![token_cave::ID.to_string()]),
+            RpcTransactionLogsConfig {
+                commitment: Some(CommitmentConfig::confirmed()),
+            },
+        )?;
+        self.backfill()?;
+
+        loop {
+            let response = receiver.recv()?;
+            if response.value.err.is_some() {
+                continue;
+            }
+            let signature: Signature = response.value.signature.parse()?;
+            self.index_transaction_with_retries(&signature)?;
+        }
+    }
+
+    /// Indexes the program's transactions since the last indexed one,
+    /// oldest first
+    pub fn backfill(&mut self) -> Result<()> {
+
+        let until = self.db.last_signature()?
+            .map(|signature| signature.parse::<Signature>())
+            .transpose()?;
+        let mut signatures = Vec::new();
+        let mut before = None;
+        loop {
+            let page = self.rpc.get_signatures_for_address_with_config(
+                &token_cave::ID,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit: None,
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )?;
+            let last = match page.last() {
+                Some(last) => last.signature.parse::<Signature>()?,
+                None => break,
+            };
+            signatures.extend(
+                page.into_iter()
+                    .filter(|status| status.err.is_none())
+                    .map(|status| status.signature),
+            );
+            before = Some(last);
+        }
+
+        for signature in signatures.iter().rev() {
+            self.index_transaction(&signature.parse()?)?;
+        }
+        Ok(())
+    }
+
+    fn index_transaction_with_retries(&mut self, signature: &Signature) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            match self.index_transaction(signature) {
+                Err(_) if attempt < FETCH_ATTEMPTS => {
+                    attempt += 1;
+                    thread::sleep(FETCH_RETRY_DELAY);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Fetches a transaction and stores its token cave instructions,
+    /// including those invoked through CPI. Failed transactions are stored
+    /// without events.
+    pub fn index_transaction(&mut self, signature: &Signature) -> Result<()> {
+
+        if self.db.is_indexed(&signature.to_string())? {
+            return Ok(());
+        }
+        let confirmed = self.rpc.get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )?;
+        let meta = confirmed.transaction.meta
+            .ok_or_else(|| anyhow!("transaction {signature} has no status"))?;
+        let events = if meta.err.is_none() {
+            let transaction = confirmed.transaction.transaction
+                .decode()
+                .ok_or_else(|| anyhow!("transaction {signature} doesn't decode"))?;
+            decode_events(transaction.message, &meta)?
+        } else {
+            Vec::new()
+        };
+
+        self.db.record_transaction(
+            &signature.to_string(),
+            confirmed.slot,
+            confirmed.block_time,
+            &events,
+        )
+    }
+}
+
+/// Decodes the token cave instructions of a successful transaction in
+/// execution order. Cave infos the transaction closed without a decoded
+/// instruction closing them get a `Closed` event at the end.
+pub fn decode_events(message: VersionedMessage, meta: &UiTransactionStatusMeta) -> Result<Vec<Event>> {
+
+    // Accounts loaded from lookup tables follow the static keys, writable
+    // ones first, as in the balances of the meta
+    let (mut keys, instructions) = match message {
+        VersionedMessage::Legacy(message) => (message.account_keys, message.instructions),
+        VersionedMessage::V0(message) => (message.account_keys, message.instructions),
+    };
+    if let Some(loaded) = &meta.loaded_addresses {
+        for address in loaded.writable.iter().chain(&loaded.readonly) {
+            keys.push(address.parse()?);
+        }
+    }
+
+    let mut compiled = Vec::new();
+    for (index, instruction) in instructions.into_iter().enumerate() {
+        compiled.push((instruction.program_id_index, instruction.accounts, instruction.data));
+        let inner = meta.inner_instructions
+            .iter()
+            .flatten()
+            .filter(|inner| inner.index as usize == index)
+            .flat_map(|inner| &inner.instructions);
+        for instruction in inner {
+            if let UiInstruction::Compiled(instruction) = instruction {
+                compiled.push((
+                    instruction.program_id_index,
+                    instruction.accounts.clone(),
+                    bs58::decode(&instruction.data).into_vec()?,
+                ));
+            }
+        }
+    }
+
+    // Accounts that held lamports before the transaction and none after
+    let closed: Vec<Pubkey> = keys
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| {
+            matches!(meta.pre_balances.get(*index), Some(lamports) if *lamports > 0)
+            && meta.post_balances.get(*index) == Some(&0)
+        })
+        .map(|(_, key)| *key)
+        .collect();
+
+    let mut events = Vec::new();
+    for (program_id_index, accounts, data) in compiled {
+        if keys.get(program_id_index as usize) != Some(&token_cave::ID) {
+            continue;
+        }
+        let accounts: Option<Vec<Pubkey>> = accounts
+            .iter()
+            .map(|index| keys.get(*index as usize).copied())
+            .collect();
+        let accounts = match accounts {
+            Some(accounts) => accounts,
+            None => continue,
+        };
+        let duress_backup = duress_backup_account(&data, &accounts);
+        for mut instruction in decode_instruction(&data, &accounts) {
+            let amount = match &instruction {
+                CaveInstruction::Initialize { kind: CaveKind::Stake, cave, .. } => {
+                    lamports_after(&keys, meta, cave)
+                }
+                CaveInstruction::Initialize { deposit_amount, .. } => *deposit_amount,
+                CaveInstruction::Unlock { .. } | CaveInstruction::Closed { .. } => None,
+                CaveInstruction::Close { cave_info, cave, .. } => {
+
+                    // Batches skip caves they can't close yet
+                    if !closed.contains(cave_info) {
+                        continue;
+                    }
+                    cave.and_then(|cave| cave_balance(&keys, meta, &cave))
+                }
+            };
+
+            // A duress withdraw pays the backup rather than the depositor
+            if let (CaveInstruction::Close { closing, destination, .. }, Some(backup)) =
+                (&mut instruction, duress_backup)
+            {
+                if received_tokens(&keys, meta, &backup) {
+                    *closing = Closing::DuressWithdraw;
+                    *destination = Some(backup);
+                }
+            }
+            events.push(Event { instruction, amount });
+        }
+    }
+
+    // Catch caves closed by instructions that weren't decoded. Closed
+    // accounts that aren't cave infos are ignored by the database.
+    for account in closed {
+        let decoded = events.iter().any(|event| match event.instruction {
+            CaveInstruction::Close { cave, cave_info, .. } => {
+                cave_info == account || cave == Some(account)
+            }
+            _ => false,
+        });
+        if !decoded {
+            events.push(Event {
+                instruction: CaveInstruction::Closed { cave_info: account },
+                amount: None,
+            });
+        }
+    }
+    Ok(events)
+}
+
+/// Balance of the cave token account before the transaction, which is what
+/// a closing instruction releases
+fn cave_balance(keys: &[Pubkey], meta: &UiTransactionStatusMeta, cave: &Pubkey) -> Option<u64> {
+    let index = keys.iter().position(|key| key == cave)?;
+    meta.pre_token_balances
+        .iter()
+        .flatten()
+        .find(|balance| balance.account_index as usize == index)
+        .and_then(|balance| balance.ui_token_amount.amount.parse().ok())
+}
+
+/// Whether the token balance of an account grew in the transaction
+fn received_tokens(keys: &[Pubkey], meta: &UiTransactionStatusMeta, account: &Pubkey) -> bool {
+    let index = match keys.iter().position(|key| key == account) {
+        Some(index) => index,
+        None => return false,
+    };
+    let amount = |balances: &Option<Vec<UiTransactionTokenBalance>>| balances
+        .iter()
+        .flatten()
+        .find(|balance| balance.account_index as usize == index)
+        .and_then(|balance| balance.ui_token_amount.amount.parse::<u64>().ok())
+        .unwrap_or(0);
+    amount(&meta.post_token_balances) > amount(&meta.pre_token_balances)
+}
+
+/// Lamports of an account after the transaction, which is what a stake
+/// account holds when it's locked
+fn lamports_after(keys: &[Pubkey], meta: &UiTransactionStatusMeta, account: &Pubkey) -> Option<u64> {
+    let index = keys.iter().position(|key| key == account)?;
+    meta.post_balances.get(index).copied()
+}
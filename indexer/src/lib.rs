@@ -0,0 +1,10 @@
+//! Indexer for the token cave program.
+//!
+//! Follows the program's transactions on an RPC node, decodes the
+//! `initialize`, `unlock`, `withdraw` and `abort` instructions and stores the
+//! history of every cave in SQLite, where it outlives the closed accounts.
+//! `api` serves that history as read-only JSON.
+pub mod api;
+pub mod db;
+pub mod decode;
+pub mod ingest;
@@ -0,0 +1,69 @@
+//! Runs the indexer and its API.
+//!
+//! Usage: `token-cave-indexer [--rpc URL] [--ws URL] [--db PATH] [--listen ADDR]`
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use token_cave_indexer::api;
+use token_cave_indexer::db::Db;
+use token_cave_indexer::ingest::Indexer;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+
+struct Args {
+    rpc: String,
+    ws: String,
+    db: String,
+    listen: String,
+}
+
+impl Args {
+
+    /// Parses the command line, defaulting to a local validator
+    fn parse() -> Result<Self> {
+        let mut args = Args {
+            rpc: "http://127.0.0.1:8899".to_string(),
+            ws: "ws://127.0.0.1:8900".to_string(),
+            db: "token-cave.sqlite".to_string(),
+            listen: "127.0.0.1:8080".to_string(),
+        };
+        let mut argv = std::env::args().skip(1);
+        while let Some(flag) = argv.next() {
+            let value = argv.next().ok_or_else(|| anyhow!("missing value for {flag}"))?;
+            match flag.as_str() {
+                "--rpc" => args.rpc = value,
+                "--ws" => args.ws = value,
+                "--db" => args.db = value,
+                "--listen" => args.listen = value,
+                _ => bail!("unknown argument {flag}"),
+            }
+        }
+        Ok(args)
+    }
+}
+
+fn main() -> Result<()> {
+
+    let args = Args::parse()?;
+
+    // The indexer creates the database, so open it before the API
+    let mut indexer = Indexer::new(&args.rpc, Db::open(&args.db)?);
+    let api_db = Db::open_read_only(&args.db)?;
+    let listen = args.listen.clone();
+    thread::spawn(move || {
+        if let Err(e) = api::serve(api_db, &listen) {
+            eprintln!("api stopped: {e:#}");
+            std::process::exit(1);
+        }
+    });
+    println!("serving the api on http://{}", args.listen);
+
+    loop {
+        if let Err(e) = indexer.follow(&args.ws) {
+            eprintln!("indexer stopped: {e:#}, reconnecting");
+        }
+        thread::sleep(RECONNECT_DELAY);
+    }
+}
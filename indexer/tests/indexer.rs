@@ -0,0 +1,544 @@
+use anchor_lang::{InstructionData, ToAccountMetas};
+use serde_json::json;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::message::{Message, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::{TransactionStatusMeta, UiTransactionStatusMeta, UiTransactionTokenBalance};
+use token_cave::instructions::initialize::{CaveKind, TimelockUnit};
+use token_cave_indexer::db::{CaveFilter, Db, Event};
+use token_cave_indexer::decode::{decode_instruction, CaveInstruction, Closing};
+use token_cave_indexer::ingest::decode_events;
+
+#[test]
+fn test_decode_instructions() {
+
+    // Initialize, with the accounts in the program's order
+    let (cave, cave_info, mint, depositor) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let backup = Pubkey::new_unique();
+    let accounts = token_cave::accounts::Initialize {
+        cave,
+        cave_info,
+        mint,
+        depositor,
+        payer: Pubkey::new_unique(),
+        depositor_token_account: Pubkey::new_unique(),
+        token_program: Pubkey::new_unique(),
+        system_program: Pubkey::new_unique(),
+        rent: Pubkey::new_unique(),
+        config: Pubkey::new_unique(),
+        fee_override: Pubkey::new_unique(),
+        treasury_token_account: Pubkey::new_unique(),
+        registry: Pubkey::new_unique(),
+    };
+    let data = token_cave::instruction::Initialize {
+        deposit_amount: 42,
+        backup_address: Some(backup),
+        timelock_duration: 5,
+        timelock_unit: TimelockUnit::Slot,
+        withdraw_window: None,
+    }.data();
+    assert_eq!(
+        decode_instruction(&data, &keys(accounts.to_account_metas(None))),
+        vec![CaveInstruction::Initialize {
+            kind: CaveKind::Token,
+            cave,
+            cave_info,
+            mint,
+            depositor,
+            deposit_amount: Some(42),
+            backup_address: Some(backup),
+            timelock_duration: 5,
+            timelock_unit: TimelockUnit::Slot,
+            withdraw_window: None,
+        }],
+    );
+
+    // Unlock
+    let accounts = token_cave::accounts::Unlock {
+        cave_info,
+        depositor,
+        config: Pubkey::new_unique(),
+    };
+    assert_eq!(
+        decode_instruction(
+            &token_cave::instruction::Unlock { duress_salt: None }.data(),
+            &keys(accounts.to_account_metas(None)),
+        ),
+        vec![CaveInstruction::Unlock { cave_info }],
+    );
+
+    // Withdraw
+    let destination = Pubkey::new_unique();
+    let accounts = token_cave::accounts::Withdraw {
+        cave,
+        cave_info,
+        mint,
+        depositor,
+        rent_payer: Pubkey::new_unique(),
+        depositor_token_account: destination,
+        token_program: Pubkey::new_unique(),
+        config: Pubkey::new_unique(),
+        treasury_token_account: Pubkey::new_unique(),
+        registry: Pubkey::new_unique(),
+    };
+    assert_eq!(
+        decode_instruction(
+            &token_cave::instruction::Withdraw.data(),
+            &keys(accounts.to_account_metas(None)),
+        ),
+        vec![CaveInstruction::Close {
+            closing: Closing::Withdraw,
+            cave: Some(cave),
+            cave_info,
+            destination: Some(destination),
+        }],
+    );
+
+    // Abort
+    let accounts = token_cave::accounts::Abort {
+        cave,
+        cave_info,
+        mint,
+        depositor,
+        backup,
+        depositor_token_account: Pubkey::new_unique(),
+        backup_destination: token_cave::accounts::BackupDestination {
+            backup_spl_account: destination,
+            payer: Pubkey::new_unique(),
+            system_program: Pubkey::new_unique(),
+            associated_token_program: Pubkey::new_unique(),
+            rent: Pubkey::new_unique(),
+        },
+        token_program: Pubkey::new_unique(),
+        registry: Pubkey::new_unique(),
+    };
+    assert_eq!(
+        decode_instruction(
+            &token_cave::instruction::Abort.data(),
+            &keys(accounts.to_account_metas(None)),
+        ),
+        vec![CaveInstruction::Close {
+            closing: Closing::Abort,
+            cave: Some(cave),
+            cave_info,
+            destination: Some(destination),
+        }],
+    );
+
+    // Other instructions that close a single cave
+    let accounts = token_cave::accounts::ClaimHtlc {
+        cave,
+        cave_info,
+        mint,
+        recipient_token_account: destination,
+        rent_payer: Pubkey::new_unique(),
+        token_program: Pubkey::new_unique(),
+        config: Pubkey::new_unique(),
+        treasury_token_account: Pubkey::new_unique(),
+        registry: Pubkey::new_unique(),
+    };
+    assert_eq!(
+        decode_instruction(
+            &token_cave::instruction::ClaimHtlc { preimage: vec![0; 32] }.data(),
+            &keys(accounts.to_account_metas(None)),
+        ),
+        vec![CaveInstruction::Close {
+            closing: Closing::Withdraw,
+            cave: Some(cave),
+            cave_info,
+            destination: Some(destination),
+        }],
+    );
+    let accounts = token_cave::accounts::Clawback {
+        cave,
+        cave_info,
+        mint,
+        depositor,
+        depositor_token_account: destination,
+        rent_payer: Pubkey::new_unique(),
+        token_program: Pubkey::new_unique(),
+        registry: Pubkey::new_unique(),
+    };
+    assert_eq!(
+        decode_instruction(
+            &token_cave::instruction::Clawback.data(),
+            &keys(accounts.to_account_metas(None)),
+        ),
+        vec![CaveInstruction::Close {
+            closing: Closing::Clawback,
+            cave: Some(cave),
+            cave_info,
+            destination: Some(destination),
+        }],
+    );
+    let accounts = token_cave::accounts::AbortWithSignature {
+        cave,
+        cave_info,
+        mint,
+        depositor_token_account: Pubkey::new_unique(),
+        backup,
+        backup_destination: token_cave::accounts::BackupDestination {
+            backup_spl_account: destination,
+            payer: Pubkey::new_unique(),
+            system_program: Pubkey::new_unique(),
+            associated_token_program: Pubkey::new_unique(),
+            rent: Pubkey::new_unique(),
+        },
+        instructions_sysvar: Pubkey::new_unique(),
+        token_program: Pubkey::new_unique(),
+        registry: Pubkey::new_unique(),
+    };
+    assert_eq!(
+        decode_instruction(
+            &token_cave::instruction::AbortWithSignature { nonce: 0, expiry: 0 }.data(),
+            &keys(accounts.to_account_metas(None)),
+        ),
+        vec![CaveInstruction::Close {
+            closing: Closing::Abort,
+            cave: Some(cave),
+            cave_info,
+            destination: Some(destination),
+        }],
+    );
+
+    // A batch yields every cave in it
+    let groups: Vec<[Pubkey; 6]> = (0..2)
+        .map(|_| [(); 6].map(|_| Pubkey::new_unique()))
+        .collect();
+    let mut metas = token_cave::accounts::BatchWithdraw {
+        token_program: Pubkey::new_unique(),
+        config: Pubkey::new_unique(),
+    }.to_account_metas(None);
+    metas.extend(groups.iter().flatten().map(|key| AccountMeta::new(*key, false)));
+    assert_eq!(
+        decode_instruction(&token_cave::instruction::BatchWithdraw.data(), &keys(metas)),
+        groups
+            .iter()
+            .map(|group| CaveInstruction::Close {
+                closing: Closing::Withdraw,
+                cave: Some(group[0]),
+                cave_info: group[1],
+                destination: Some(group[2]),
+            })
+            .collect::<Vec<_>>(),
+    );
+    let mut metas = token_cave::accounts::BatchAbort {
+        depositor,
+        backup,
+        backup_destination: token_cave::accounts::BackupDestination {
+            backup_spl_account: Pubkey::new_unique(),
+            payer: Pubkey::new_unique(),
+            system_program: Pubkey::new_unique(),
+            associated_token_program: Pubkey::new_unique(),
+            rent: Pubkey::new_unique(),
+        },
+        token_program: Pubkey::new_unique(),
+        registry: Pubkey::new_unique(),
+    }.to_account_metas(None);
+    metas.extend(groups.iter().flat_map(|group| &group[..4]).map(|key| AccountMeta::new(*key, false)));
+    assert_eq!(
+        decode_instruction(&token_cave::instruction::BatchAbort.data(), &keys(metas)),
+        groups
+            .iter()
+            .map(|group| CaveInstruction::Close {
+                closing: Closing::Abort,
+                cave: Some(group[0]),
+                cave_info: group[1],
+                destination: Some(group[2]),
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    // Other instructions aren't indexed
+    assert_eq!(
+        decode_instruction(&token_cave::instruction::ClearMintFee.data(), &[]),
+        vec![],
+    );
+}
+
+#[test]
+fn test_record_history() {
+
+    let mut db = Db::open(":memory:").expect("failed to open db");
+    let (cave, cave_info, depositor, destination) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let initialize = || Event {
+        instruction: CaveInstruction::Initialize {
+            kind: CaveKind::Token,
+            cave,
+            cave_info,
+            mint: Pubkey::new_unique(),
+            depositor,
+            deposit_amount: Some(10),
+            backup_address: None,
+            timelock_duration: 5,
+            timelock_unit: TimelockUnit::UnixTimestamp,
+            withdraw_window: None,
+        },
+        amount: Some(10),
+    };
+
+    // A cave is created, unlocked and withdrawn, then a new cave is
+    // created at the same address
+    db.record_transaction("deposit", 1, Some(100), &[initialize()]).unwrap();
+    db.record_transaction("unlock", 2, Some(101), &[Event {
+        instruction: CaveInstruction::Unlock { cave_info },
+        amount: None,
+    }]).unwrap();
+    db.record_transaction("withdraw", 3, Some(107), &[Event {
+        instruction: CaveInstruction::Close {
+            closing: Closing::Withdraw,
+            cave: Some(cave),
+            cave_info,
+            destination: Some(destination),
+        },
+        amount: Some(10),
+    }]).unwrap();
+    db.record_transaction("deposit again", 4, Some(110), &[initialize()]).unwrap();
+
+    // Recording a transaction twice changes nothing
+    db.record_transaction("unlock", 2, Some(101), &[Event {
+        instruction: CaveInstruction::Unlock { cave_info },
+        amount: None,
+    }]).unwrap();
+
+    let caves = db.caves_at(&cave_info.to_string()).unwrap();
+    assert_eq!(caves.len(), 2);
+    assert_eq!(caves[0].status, "withdrawn");
+    assert_eq!(caves[0].closed_at, Some(107));
+    assert_eq!(caves[1].status, "locked");
+    let events: Vec<String> = db.events(caves[0].id).unwrap()
+        .into_iter()
+        .map(|event| event.kind)
+        .collect();
+    assert_eq!(events, vec!["initialize", "unlock", "withdraw"]);
+    assert_eq!(db.events(caves[1].id).unwrap().len(), 1);
+    assert_eq!(db.last_signature().unwrap().as_deref(), Some("deposit again"));
+
+    let filter = CaveFilter {
+        depositor: Some(depositor.to_string()),
+        status: Some("locked".to_string()),
+        ..CaveFilter::default()
+    };
+    assert_eq!(db.caves(&filter).unwrap(), vec![caves[1].clone()]);
+}
+
+#[test]
+fn test_ingest_batch_close() {
+
+    let mut db = Db::open(":memory:").expect("failed to open db");
+    let depositor = Pubkey::new_unique();
+    let caves: Vec<(Pubkey, Pubkey)> = (0..3)
+        .map(|_| (Pubkey::new_unique(), Pubkey::new_unique()))
+        .collect();
+    let initialize = |(cave, cave_info): (Pubkey, Pubkey)| Event {
+        instruction: CaveInstruction::Initialize {
+            kind: CaveKind::Token,
+            cave,
+            cave_info,
+            mint: Pubkey::new_unique(),
+            depositor,
+            deposit_amount: Some(10),
+            backup_address: None,
+            timelock_duration: 5,
+            timelock_unit: TimelockUnit::UnixTimestamp,
+            withdraw_window: None,
+        },
+        amount: Some(10),
+    };
+    let events: Vec<Event> = caves.iter().copied().map(initialize).collect();
+    db.record_transaction("deposits", 1, Some(100), &events).unwrap();
+
+    // A batch withdraw of the first two caves, which closes only the first
+    // one since the second isn't withdrawable yet
+    let mut accounts = token_cave::accounts::BatchWithdraw {
+        token_program: Pubkey::new_unique(),
+        config: Pubkey::new_unique(),
+    }.to_account_metas(None);
+    for (cave, cave_info) in &caves[..2] {
+        accounts.extend([
+            AccountMeta::new(*cave, false),
+            AccountMeta::new(*cave_info, false),
+            AccountMeta::new(Pubkey::new_unique(), false),
+            AccountMeta::new(Pubkey::new_unique(), false),
+            AccountMeta::new(Pubkey::new_unique(), false),
+            AccountMeta::new(Pubkey::new_unique(), false),
+        ]);
+    }
+    let batch = Instruction {
+        program_id: token_cave::ID,
+        accounts,
+        data: token_cave::instruction::BatchWithdraw.data(),
+    };
+    let (message, meta) = transaction(&[batch], &[caves[0].0, caves[0].1]);
+    let events = decode_events(message, &meta).expect("failed to decode");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].instruction.cave_info(), caves[0].1);
+    db.record_transaction("batch", 2, Some(107), &events).unwrap();
+
+    // The third cave's info disappears through an instruction that isn't
+    // decoded
+    let other = Instruction {
+        program_id: Pubkey::new_unique(),
+        accounts: vec![AccountMeta::new(caves[2].1, false)],
+        data: vec![],
+    };
+    let (message, meta) = transaction(&[other], &[caves[2].1]);
+    let events = decode_events(message, &meta).expect("failed to decode");
+    db.record_transaction("other", 3, Some(108), &events).unwrap();
+
+    let status = |cave_info: &Pubkey| db.caves_at(&cave_info.to_string()).unwrap()[0].status.clone();
+    assert_eq!(status(&caves[0].1), "withdrawn");
+    assert_eq!(status(&caves[1].1), "locked");
+    assert_eq!(status(&caves[2].1), "closed");
+    assert_eq!(db.caves_at(&caves[2].1.to_string()).unwrap()[0].closed_at, Some(108));
+}
+
+#[test]
+fn test_ingest_duress_withdraw() {
+
+    let mut db = Db::open(":memory:").expect("failed to open db");
+    let (cave, cave_info, depositor_token_account, backup_spl_account) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    db.record_transaction("deposit", 1, Some(100), &[Event {
+        instruction: CaveInstruction::Initialize {
+            kind: CaveKind::Token,
+            cave,
+            cave_info,
+            mint: Pubkey::new_unique(),
+            depositor: Pubkey::new_unique(),
+            deposit_amount: Some(10),
+            backup_address: Some(Pubkey::new_unique()),
+            timelock_duration: 5,
+            timelock_unit: TimelockUnit::UnixTimestamp,
+            withdraw_window: None,
+        },
+        amount: Some(10),
+    }]).unwrap();
+
+    // The first remaining account of a withdraw is the backup's token
+    // account after a duress unlock, and e.g. a strategy program otherwise
+    let mut accounts = token_cave::accounts::Withdraw {
+        cave,
+        cave_info,
+        mint: Pubkey::new_unique(),
+        depositor: Pubkey::new_unique(),
+        rent_payer: Pubkey::new_unique(),
+        depositor_token_account,
+        token_program: Pubkey::new_unique(),
+        config: Pubkey::new_unique(),
+        treasury_token_account: Pubkey::new_unique(),
+        registry: Pubkey::new_unique(),
+    }.to_account_metas(None);
+    accounts.push(AccountMeta::new(backup_spl_account, false));
+    let withdraw = Instruction {
+        program_id: token_cave::ID,
+        accounts,
+        data: token_cave::instruction::Withdraw.data(),
+    };
+    let close = |closing: Closing, destination: Pubkey| CaveInstruction::Close {
+        closing,
+        cave: Some(cave),
+        cave_info,
+        destination: Some(destination),
+    };
+
+    // A plain withdraw pays the depositor
+    let (message, meta) = transaction(&[withdraw.clone()], &[cave, cave_info]);
+    let meta = with_token_balances(
+        &message,
+        meta,
+        &[(cave, 10), (depositor_token_account, 5)],
+        &[(depositor_token_account, 15)],
+    );
+    let events = decode_events(message, &meta).expect("failed to decode");
+    assert_eq!(events[0].instruction, close(Closing::Withdraw, depositor_token_account));
+
+    // A duress withdraw pays the backup
+    let (message, meta) = transaction(&[withdraw], &[cave, cave_info]);
+    let meta = with_token_balances(
+        &message,
+        meta,
+        &[(cave, 10), (depositor_token_account, 5)],
+        &[(depositor_token_account, 5), (backup_spl_account, 10)],
+    );
+    let events = decode_events(message, &meta).expect("failed to decode");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].instruction, close(Closing::DuressWithdraw, backup_spl_account));
+    assert_eq!(events[0].amount, Some(10));
+    db.record_transaction("duress withdraw", 2, Some(107), &events).unwrap();
+
+    let caves = db.caves_at(&cave_info.to_string()).unwrap();
+    assert_eq!(caves[0].status, "withdrawn_to_backup");
+    let events = db.events(caves[0].id).unwrap();
+    assert_eq!(events[1].kind, "duress_withdraw");
+    assert_eq!(events[1].account, Some(backup_spl_account.to_string()));
+}
+
+/// A successful legacy transaction of `instructions` whose accounts all
+/// hold lamports before it, and after it except for `closed`
+fn transaction(instructions: &[Instruction], closed: &[Pubkey]) -> (VersionedMessage, UiTransactionStatusMeta) {
+    let message = Message::new(instructions, Some(&Pubkey::new_unique()));
+    let meta = TransactionStatusMeta {
+        pre_balances: vec![1_000_000; message.account_keys.len()],
+        post_balances: message.account_keys
+            .iter()
+            .map(|key| if closed.contains(key) { 0 } else { 1_000_000 })
+            .collect(),
+        ..TransactionStatusMeta::default()
+    };
+    (VersionedMessage::Legacy(message), meta.into())
+}
+
+/// `meta` with the given token balances before and after the transaction
+fn with_token_balances(
+    message: &VersionedMessage,
+    mut meta: UiTransactionStatusMeta,
+    pre: &[(Pubkey, u64)],
+    post: &[(Pubkey, u64)],
+) -> UiTransactionStatusMeta {
+    let account_keys = match message {
+        VersionedMessage::Legacy(message) => &message.account_keys,
+        VersionedMessage::V0(message) => &message.account_keys,
+    };
+    meta.pre_token_balances = Some(token_balances(account_keys, pre));
+    meta.post_token_balances = Some(token_balances(account_keys, post));
+    meta
+}
+
+/// Token balances of `accounts`, indexed by their position in `account_keys`
+fn token_balances(account_keys: &[Pubkey], accounts: &[(Pubkey, u64)]) -> Vec<UiTransactionTokenBalance> {
+    accounts
+        .iter()
+        .map(|(account, amount)| serde_json::from_value(json!({
+            "accountIndex": account_keys.iter().position(|key| key == account).unwrap(),
+            "mint": Pubkey::default().to_string(),
+            "uiTokenAmount": {
+                "uiAmount": *amount as f64,
+                "decimals": 0,
+                "amount": amount.to_string(),
+                "uiAmountString": amount.to_string(),
+            },
+            "owner": Pubkey::default().to_string(),
+            "programId": Pubkey::default().to_string(),
+        })).unwrap())
+        .collect()
+}
+
+/// Keys of account metas, in order
+fn keys(metas: Vec<anchor_lang::prelude::AccountMeta>) -> Vec<Pubkey> {
+    metas.into_iter().map(|meta| meta.pubkey).collect()
+}
@@ -0,0 +1,152 @@
+//! A mock multisig used to test PDA depositors on localnet.
+//!
+//! Like Squads, each multisig controls a system-owned vault PDA at
+//! `["vault", multisig]`. Once `threshold` members sign, `execute` invokes
+//! an arbitrary instruction with the vault as a signer. The first
+//! `threshold` remaining accounts of `execute` are the approving members,
+//! the rest are the accounts of the instruction, in order.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+
+declare_id!("269qkdY46QYXZdFwfjZygjCZkWgc7MU451FnNgaSActu");
+
+pub const MAX_MEMBERS: usize = 5;
+pub const MULTISIG_SIZE: usize = 8 + 4 + 32 * MAX_MEMBERS + 1 + 1;
+
+
+#[program]
+pub mod mock_multisig {
+
+    use super::*;
+
+    pub fn create_multisig(
+        ctx: Context<CreateMultisig>,
+        members: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        require!(
+            members.len() <= MAX_MEMBERS
+            && threshold > 0
+            && usize::from(threshold) <= members.len(),
+            MultisigError::InvalidThreshold,
+        );
+        ctx.accounts.multisig.members = members;
+        ctx.accounts.multisig.threshold = threshold;
+        ctx.accounts.multisig.vault_bump = *ctx.bumps.get("vault").unwrap();
+        Ok(())
+    }
+
+    pub fn execute<'info>(
+        ctx: Context<'_, '_, '_, 'info, Execute<'info>>,
+        data: Vec<u8>,
+    ) -> Result<()> {
+
+        // Check the approvals
+        let threshold = usize::from(ctx.accounts.multisig.threshold);
+        require_gte!(
+            ctx.remaining_accounts.len(),
+            threshold,
+            MultisigError::NotEnoughApprovals,
+        );
+        let (approvals, accounts) = ctx.remaining_accounts.split_at(threshold);
+        for (i, member) in approvals.iter().enumerate() {
+            require!(
+                member.is_signer
+                && ctx.accounts.multisig.members.contains(member.key)
+                && !approvals[..i].iter().any(|other| other.key == member.key),
+                MultisigError::NotEnoughApprovals,
+            );
+        }
+
+        // Invoke the instruction, signing for the vault
+        let vault = ctx.accounts.vault.key();
+        let metas = accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer || account.key() == vault,
+                is_writable: account.is_writable,
+            })
+            .collect();
+        let mut account_infos = accounts.to_vec();
+        account_infos.push(ctx.accounts.target_program.to_account_info());
+        let multisig = ctx.accounts.multisig.key();
+        invoke_signed(
+            &Instruction {
+                program_id: ctx.accounts.target_program.key(),
+                accounts: metas,
+                data,
+            },
+            &account_infos,
+            &[&[b"vault".as_ref(), multisig.as_ref(), &[ctx.accounts.multisig.vault_bump]]],
+        )?;
+
+        Ok(())
+    }
+}
+
+
+#[derive(Accounts)]
+pub struct CreateMultisig<'info> {
+
+    #[account(
+        init,
+        payer = payer,
+        space = MULTISIG_SIZE,
+    )]
+    pub multisig: Account<'info, Multisig>,
+
+    /// CHECK: only derived, the vault is a system account
+    #[account(
+        seeds = [b"vault".as_ref(), multisig.key().as_ref()],
+        bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Execute<'info> {
+
+    pub multisig: Account<'info, Multisig>,
+
+    /// CHECK: only signs, through `invoke_signed`
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), multisig.key().as_ref()],
+        bump = multisig.vault_bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    /// CHECK: any program
+    #[account(executable)]
+    pub target_program: UncheckedAccount<'info>,
+}
+
+#[account]
+pub struct Multisig {
+
+    /// Keys that can approve
+    pub members: Vec<Pubkey>,
+
+    /// Number of member signatures `execute` needs
+    pub threshold: u8,
+
+    /// Bump of the vault PDA
+    pub vault_bump: u8,
+}
+
+#[error_code]
+pub enum MultisigError {
+
+    #[msg("The threshold must be between one and the number of members")]
+    InvalidThreshold,
+
+    #[msg("Not enough distinct members approved")]
+    NotEnoughApprovals,
+}
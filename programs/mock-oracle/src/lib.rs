@@ -0,0 +1,84 @@
+//! A mock price oracle used to test price-conditioned caves on localnet.
+//!
+//! Each feed is an account whose authority can set its price at will. The
+//! token cave reads feeds through `token_cave::oracle`, which knows the
+//! layout of `PriceFeed`: the discriminator, then `authority`, `price`,
+//! `expo` and `publish_time`.
+use anchor_lang::prelude::*;
+
+declare_id!("3gvHRQm1723h7p8ychZk3Shk342rWA5kpXf33AEF2r4t");
+
+pub const PRICE_FEED_SIZE: usize = 8 + 32 + 8 + 4 + 8;
+
+
+#[program]
+pub mod mock_oracle {
+
+    use super::*;
+
+    pub fn initialize_feed(
+        ctx: Context<InitializeFeed>,
+        price: i64,
+        expo: i32,
+    ) -> Result<()> {
+        ctx.accounts.feed.authority = ctx.accounts.authority.key();
+        ctx.accounts.feed.price = price;
+        ctx.accounts.feed.expo = expo;
+        ctx.accounts.feed.publish_time = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    pub fn set_price(
+        ctx: Context<SetPrice>,
+        price: i64,
+    ) -> Result<()> {
+        ctx.accounts.feed.price = price;
+        ctx.accounts.feed.publish_time = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+}
+
+
+#[derive(Accounts)]
+pub struct InitializeFeed<'info> {
+
+    #[account(
+        init,
+        payer = authority,
+        space = PRICE_FEED_SIZE,
+    )]
+    pub feed: Account<'info, PriceFeed>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetPrice<'info> {
+
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub feed: Account<'info, PriceFeed>,
+
+    pub authority: Signer<'info>,
+}
+
+#[account]
+pub struct PriceFeed {
+
+    /// Can set the price
+    pub authority: Pubkey,
+
+    /// Price as a fixed-point number, `price * 10^expo`
+    pub price: i64,
+
+    /// Exponent of the price
+    pub expo: i32,
+
+    /// Unix timestamp of the latest update
+    pub publish_time: i64,
+}
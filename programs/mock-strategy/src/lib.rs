@@ -0,0 +1,241 @@
+//! A mock yield strategy used to test token cave strategies on localnet.
+//!
+//! Depositors receive shares of a per-mint pool. Yield is simulated by
+//! sending tokens straight into the pool vault, which raises the value of
+//! every share. It implements the strategy interface expected by the token
+//! cave: the first three accounts of `deposit` are the authority, the
+//! source token account and the payer, and the first three accounts of
+//! `redeem` are the authority, the destination token account and the rent
+//! receiver.
+use anchor_lang::prelude::*;
+use anchor_spl::token::{TokenAccount, Token, Mint};
+
+declare_id!("BxzjgFKm1T4jwoAHffeZvPWSG3KaiTUCQhrjqmWDzRB6");
+
+pub const POOL_SIZE: usize = 8 + 32 + 8 + 1;
+pub const POSITION_SIZE: usize = 8 + 8;
+
+
+#[program]
+pub mod mock_strategy {
+
+    use super::*;
+
+    pub fn initialize_pool(
+        ctx: Context<InitializePool>,
+    ) -> Result<()> {
+        ctx.accounts.pool.mint = ctx.accounts.mint.key();
+        ctx.accounts.pool.total_shares = 0;
+        ctx.accounts.pool.bump = *ctx.bumps.get("pool").unwrap();
+        Ok(())
+    }
+
+    pub fn deposit(
+        ctx: Context<Deposit>,
+        amount: u64,
+    ) -> Result<()> {
+
+        // Shares are minted at the current share price
+        let shares = if ctx.accounts.pool.total_shares == 0 {
+            amount
+        } else {
+            (amount as u128)
+                .checked_mul(ctx.accounts.pool.total_shares.into())
+                .unwrap()
+                .checked_div(ctx.accounts.vault.amount.into())
+                .unwrap() as u64
+        };
+
+        anchor_spl::token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.token_account.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        ctx.accounts.pool.total_shares = ctx.accounts.pool.total_shares
+            .checked_add(shares)
+            .unwrap();
+        ctx.accounts.position.shares = ctx.accounts.position.shares
+            .checked_add(shares)
+            .unwrap();
+
+        Ok(())
+    }
+
+    pub fn redeem(
+        ctx: Context<Redeem>,
+    ) -> Result<()> {
+
+        // Redeem the whole position, including accrued yield
+        let shares = ctx.accounts.position.shares;
+        let amount = (shares as u128)
+            .checked_mul(ctx.accounts.vault.amount.into())
+            .unwrap()
+            .checked_div(ctx.accounts.pool.total_shares.into())
+            .unwrap() as u64;
+
+        let mint = ctx.accounts.pool.mint;
+        anchor_spl::token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: ctx.accounts.token_account.to_account_info(),
+                    authority: ctx.accounts.pool.to_account_info(),
+                },
+                &[&[b"pool".as_ref(), mint.as_ref(), &[ctx.accounts.pool.bump]]]
+            ),
+            amount,
+        )?;
+
+        ctx.accounts.pool.total_shares = ctx.accounts.pool.total_shares
+            .checked_sub(shares)
+            .unwrap();
+
+        Ok(())
+    }
+}
+
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool".as_ref(), mint.key().as_ref()],
+        space = POOL_SIZE,
+        bump,
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"vault".as_ref(), mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = pool,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account()]
+    pub mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = pool.mint,
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool".as_ref(), pool.mint.as_ref()],
+        bump = pool.bump,
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), pool.mint.as_ref()],
+        bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [b"position".as_ref(), pool.key().as_ref(), authority.key().as_ref()],
+        space = POSITION_SIZE,
+        bump,
+    )]
+    pub position: Account<'info, Position>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Redeem<'info> {
+
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = pool.mint,
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: only receives the rent of the closed position
+    #[account(mut)]
+    pub rent_receiver: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool".as_ref(), pool.mint.as_ref()],
+        bump = pool.bump,
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), pool.mint.as_ref()],
+        bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        close = rent_receiver,
+        seeds = [b"position".as_ref(), pool.key().as_ref(), authority.key().as_ref()],
+        bump,
+    )]
+    pub position: Account<'info, Position>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[account]
+pub struct Pool {
+
+    /// Mint of the pooled tokens
+    pub mint: Pubkey,
+
+    /// Total shares outstanding
+    pub total_shares: u64,
+
+    /// Bump of this PDA
+    pub bump: u8,
+}
+
+#[account]
+pub struct Position {
+
+    /// Shares owned by the position authority
+    pub shares: u64,
+}
@@ -0,0 +1,45 @@
+//! Associated token account helpers.
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::{self, get_associated_token_address};
+use crate::error::TokenCaveError;
+
+
+/// Checks that `associated_token` is the associated token account of
+/// `authority` for `mint`, and creates it, paid by `payer`, if it does not
+/// exist yet
+#[allow(clippy::too_many_arguments)]
+pub fn create_if_missing<'info>(
+    associated_token: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    associated_token_program: &AccountInfo<'info>,
+    rent: &AccountInfo<'info>,
+) -> Result<()> {
+
+    require_keys_eq!(
+        associated_token.key(),
+        get_associated_token_address(authority.key, mint.key),
+        TokenCaveError::IncorrectDestinationTokenAccount,
+    );
+    if associated_token.data_is_empty() {
+        associated_token::create(
+            CpiContext::new(
+                associated_token_program.clone(),
+                associated_token::Create {
+                    payer: payer.clone(),
+                    associated_token: associated_token.clone(),
+                    authority: authority.clone(),
+                    mint: mint.clone(),
+                    system_program: system_program.clone(),
+                    token_program: token_program.clone(),
+                    rent: rent.clone(),
+                },
+            ),
+        )?;
+    }
+
+    Ok(())
+}
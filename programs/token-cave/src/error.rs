@@ -15,10 +15,127 @@ pub enum TokenCaveError {
     #[msg("There is already an unlock happening")]
     UnlockAlreadyActive,
 
-    #[msg("The maximum allowed lock duration is MAX_TIMELOCK_DURATION. Refer to IDL")]
+    #[msg("The lock duration exceeds the maximum for its timelock unit. Refer to IDL")]
     DurationExceedsMaximum,
 
     #[msg("You supplied a token account that does not belong to the backup address")]
     IncorrectBackupTokenAccount,
 
+    #[msg("The cave is unlocking or can release its funds early, so they are not locked")]
+    CaveIsUnlocking,
+
+    #[msg("The cave holds less than the required amount")]
+    InsufficientLockedAmount,
+
+    #[msg("The cave timelock is shorter than the required duration")]
+    InsufficientLockDuration,
+
+    #[msg("The token cave program did not return a lock proof")]
+    MissingLockProof,
+
+    #[msg("The cave does not belong to the voter or the governing token mint")]
+    InvalidVoterWeightCave,
+
+    #[msg("The same cave was supplied more than once")]
+    DuplicateCave,
+
+    #[msg("The strategy program is not whitelisted")]
+    StrategyNotWhitelisted,
+
+    #[msg("The cave already has funds in a different strategy")]
+    StrategyAlreadyActive,
+
+    #[msg("The cave has funds in a strategy, but its accounts were not supplied")]
+    MissingStrategyAccounts,
+
+    #[msg("The basket already holds the maximum number of mints")]
+    BasketIsFull,
+
+    #[msg("Every token account of the basket must be supplied")]
+    IncompleteBasket,
+
+    #[msg("The destination token account has the wrong owner or mint")]
+    IncorrectDestinationTokenAccount,
+
+    #[msg("The withdraw window of this unlock has passed. Request a new unlock")]
+    WithdrawWindowExpired,
+
+    #[msg("The cave does not hold enough funds that aren't reserved by withdraw tickets")]
+    InsufficientUnreservedFunds,
+
+    #[msg("Cancel or settle the cave's pending withdraw tickets first")]
+    OutstandingWithdrawTickets,
+
+    #[msg("The duress salt and signer don't match the duress key")]
+    InvalidDuressKey,
+
+    #[msg("The program is paused")]
+    ProgramPaused,
+
+    #[msg("The previous pause has not expired and cooled down yet")]
+    PauseCooldown,
+
+    #[msg("The fee exceeds the maximum")]
+    FeeTooHigh,
+
+    #[msg("The treasury token account has the wrong owner or mint")]
+    IncorrectTreasuryTokenAccount,
+
+    #[msg("The transaction lacks an Ed25519 signature instruction")]
+    MissingSignature,
+
+    #[msg("The signature does not cover the expected key and message")]
+    InvalidSignature,
+
+    #[msg("The signed message has expired")]
+    SignatureExpired,
+
+    #[msg("The gift can't be clawed back, or its clawback deadline has passed")]
+    ClawbackExpired,
+
+    #[msg("The beneficiary must differ from the depositor")]
+    InvalidBeneficiary,
+
+    #[msg("A hashlock holds the cave's funds until its deadline")]
+    HashlockActive,
+
+    #[msg("The cave already has a hashlock, or can't get one now")]
+    HashlockUnavailable,
+
+    #[msg("The preimage does not match the hashlock")]
+    InvalidPreimage,
+
+    #[msg("The cave has no hashlock, or its deadline has passed")]
+    HashlockExpired,
+
+    #[msg("The price feed is not owned by a supported oracle, or has an unknown layout")]
+    UnsupportedPriceFeed,
+
+    #[msg("The price feed is stale or not trading")]
+    StalePrice,
+
+    #[msg("The cave already has a price condition, or can't get one now")]
+    PriceConditionUnavailable,
+
+    #[msg("The price condition is not armed or not met")]
+    PriceConditionNotMet,
+
+    #[msg("The session key is invalid, expired, or lacks the scope")]
+    InvalidSessionKey,
+
+    #[msg("The request exceeds the session key's amount limit")]
+    SessionLimitExceeded,
+
+    #[msg("The remaining accounts don't follow the batch's account pattern")]
+    InvalidBatch,
+
+    #[msg("The hashlock can't be claimed until it has served the timelock")]
+    HashlockNotArmed,
+
+    #[msg("The strategy whitelist is full")]
+    TooManyStrategies,
+
+    #[msg("The hashlock deadline must leave time to claim after it arms")]
+    HashlockDeadlineTooSoon,
+
 }
\ No newline at end of file
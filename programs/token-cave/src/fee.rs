@@ -0,0 +1,130 @@
+//! Protocol fees.
+//!
+//! A fee is taken either from the deposit at `initialize` or from the payout
+//! at `withdraw`, following the `FeeSchedule` in the program config, or the
+//! mint's `MintFeeOverride` if it has one. The schedule is copied into the
+//! cave at `initialize`, so changing it never reprices existing deposits.
+//! Fees go to the treasury's token account for the mint. Aborts never pay a
+//! fee, so rescuing funds is never penalized.
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use crate::error::TokenCaveError;
+use crate::instructions::initialize_config::ProgramConfig;
+use crate::instructions::set_mint_fee::MintFeeOverride;
+
+/// Highest proportional fee a schedule can set, in basis points. The total
+/// fee, flat part included, never exceeds this share of the amount either.
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+/// Highest flat fee a schedule can set, in base units of the mint
+pub const MAX_FLAT_FEE: u64 = 1_000_000_000;
+
+pub const FEE_SCHEDULE_SIZE: usize = 2 + 8 + 8 + 1;
+
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeSchedule {
+
+    /// Proportional fee in basis points
+    pub bps: u16,
+
+    /// Flat fee in base units of the mint, added to the proportional fee
+    pub flat: u64,
+
+    /// Cap on the total fee, in base units of the mint
+    pub max: u64,
+
+    /// When the fee is charged
+    pub timing: FeeTiming,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeeTiming {
+
+    /// Taken out of the deposit at `initialize`
+    Deposit,
+
+    /// Taken out of the payout at `withdraw`
+    Withdraw,
+}
+
+impl FeeSchedule {
+
+    /// No fee at all
+    pub const NONE: FeeSchedule = FeeSchedule {
+        bps: 0,
+        flat: 0,
+        max: 0,
+        timing: FeeTiming::Withdraw,
+    };
+
+    pub fn validate(&self) -> Result<()> {
+        require_gte!(
+            MAX_FEE_BPS,
+            self.bps,
+            TokenCaveError::FeeTooHigh,
+        );
+        require_gte!(
+            MAX_FLAT_FEE,
+            self.flat,
+            TokenCaveError::FeeTooHigh,
+        );
+        Ok(())
+    }
+
+    /// Fee on `amount`, which never exceeds the cap or `MAX_FEE_BPS` of
+    /// the amount
+    pub fn fee_for(&self, amount: u64) -> u64 {
+        let share = |bps: u16| (amount as u128)
+            .checked_mul(bps.into())
+            .unwrap()
+            .checked_div(10_000)
+            .unwrap() as u64;
+        share(self.bps)
+            .saturating_add(self.flat)
+            .min(self.max)
+            .min(share(MAX_FEE_BPS))
+    }
+}
+
+/// Schedule that applies to new caves of a mint. A mint override replaces
+/// the default schedule entirely. The caller checks that `fee_override` is
+/// the mint's override PDA.
+pub fn schedule(
+    config: &ProgramConfig,
+    fee_override: &AccountInfo,
+) -> Result<FeeSchedule> {
+    if fee_override.owner == &crate::ID && !fee_override.data_is_empty() {
+        let fee_override: Account<MintFeeOverride> = Account::try_from(fee_override)?;
+        Ok(fee_override.fee)
+    } else {
+        Ok(config.fee)
+    }
+}
+
+/// Fee owed on `amount` at `timing` for `mint` under `schedule`. Whenever a
+/// fee is owed, checks that `treasury_token_account` is the treasury's
+/// account for the mint.
+pub fn fee_due(
+    config: &ProgramConfig,
+    schedule: &FeeSchedule,
+    treasury_token_account: &AccountInfo,
+    mint: &Pubkey,
+    timing: FeeTiming,
+    amount: u64,
+) -> Result<u64> {
+
+    if schedule.timing != timing {
+        return Ok(0);
+    }
+
+    let fee = schedule.fee_for(amount);
+    if fee > 0 {
+        let treasury: Account<TokenAccount> = Account::try_from(treasury_token_account)?;
+        require!(
+            treasury.owner == config.treasury && treasury.mint == *mint,
+            TokenCaveError::IncorrectTreasuryTokenAccount,
+        );
+    }
+    Ok(fee)
+}
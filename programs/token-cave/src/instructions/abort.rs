@@ -1,10 +1,14 @@
 use anchor_lang::prelude::*;
+use anchor_spl::associated_token::{AssociatedToken, get_associated_token_address};
 use anchor_spl::token::{TokenAccount, Token, Mint};
-use super::initialize::CaveInfo;
+use super::initialize::{CaveInfo, CaveKind};
 use crate::error::TokenCaveError;
+use crate::strategy;
+use crate::ata;
+use crate::registry;
 
-pub fn handler(
-    ctx: Context<Abort>,
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Abort<'info>>,
 ) -> Result<()> {
 
     // Check that this is the depositor and the backup account
@@ -14,47 +18,99 @@ pub fn handler(
         TokenCaveError::Unauthorized,
     );
 
-    // Check that the token account belongs to the backup
-    msg!(
-        "owners {} {}",
-        ctx.accounts.backup_spl_account.owner,
-        ctx.accounts.backup.key(),
-    );
-    require_eq!(
-        ctx.accounts.backup_spl_account.owner,
-        ctx.accounts.backup.key(),
-        TokenCaveError::IncorrectBackupTokenAccount,
-    );
+    // Check the token account the funds go to, creating it if needed
+    ctx.accounts.backup_destination.prepare(
+        &ctx.accounts.cave_info,
+        &ctx.accounts.backup,
+        &ctx.accounts.mint.to_account_info(),
+        &ctx.accounts.token_program.to_account_info(),
+    )?;
 
-    // Check that the user has requested an unlock
+    // Check that the user has requested an unlock, or that a price
+    // condition or hashlock could release the funds
+    let now = ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?);
     require!(
-        ctx.accounts.cave_info.unlocking,
+        ctx.accounts.cave_info.can_abort(now),
         TokenCaveError::DidNotRequestUnlock,
     );
 
+    // Closing the cave would strand pending withdraw tickets. Abort them
+    // first with `abort_withdraw_ticket`.
+    require_eq!(
+        ctx.accounts.cave_info.open_tickets,
+        0,
+        TokenCaveError::OutstandingWithdrawTickets,
+    );
+
+    sweep_to_backup(
+        &mut ctx.accounts.cave,
+        &ctx.accounts.cave_info,
+        &ctx.accounts.backup_destination.backup_spl_account,
+        &ctx.accounts.backup,
+        &ctx.accounts.token_program.to_account_info(),
+        ctx.remaining_accounts,
+    )?;
+
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
+
+    Ok(())
+}
+
+
+/// Redeems any strategy position and sends everything in the cave to the
+/// backup's token account, closing the cave. All rent goes to
+/// `rent_receiver`, which should be on the backup's side.
+pub fn sweep_to_backup<'info>(
+    cave: &mut Account<'info, TokenAccount>,
+    cave_info: &Account<'info, CaveInfo>,
+    backup_spl_account: &AccountInfo<'info>,
+    rent_receiver: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+
+    // Bring back funds deployed to a yield strategy, along with the yield.
+    // The strategy program and its accounts are the remaining accounts.
+    if let Some(strategy) = cave_info.strategy {
+        let (strategy_program, strategy_accounts) = strategy::split_remaining_accounts(
+            strategy,
+            remaining_accounts,
+        )?;
+        strategy::redeem(
+            strategy_program,
+            &cave_info.to_account_info(),
+            &cave.to_account_info(),
+            rent_receiver,
+            strategy_accounts,
+            &[&[cave.key().as_ref(), &[cave_info.info_bump]]],
+        )?;
+        cave.reload()?;
+    }
+
     // Withdraw spl token from the token cave to backup spl
     anchor_spl::token::transfer(
         CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
+            token_program.clone(),
             anchor_spl::token::Transfer {
-                from: ctx.accounts.cave.to_account_info(),
-                to: ctx.accounts.backup_spl_account.to_account_info(),
-                authority: ctx.accounts.cave_info.to_account_info(),
+                from: cave.to_account_info(),
+                to: backup_spl_account.clone(),
+                authority: cave_info.to_account_info(),
             },
-            &[&[ctx.accounts.cave.key().as_ref(), &[*ctx.bumps.get("cave_info").unwrap()]]]
+            &[&[cave.key().as_ref(), &[cave_info.info_bump]]]
         ),
-        ctx.accounts.cave.amount,
+        cave.amount,
     )?;
 
     anchor_spl::token::close_account(
         CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
+            token_program.clone(),
             anchor_spl::token::CloseAccount {
-                account: ctx.accounts.cave.to_account_info(),
-                destination: ctx.accounts.backup_spl_account.to_account_info(),
-                authority: ctx.accounts.cave_info.to_account_info(),
+                account: cave.to_account_info(),
+                destination: rent_receiver.clone(),
+                authority: cave_info.to_account_info(),
             },
-            &[&[&ctx.accounts.cave.key().to_bytes(), &[*ctx.bumps.get("cave_info").unwrap()]]]
+            &[&[&cave.key().to_bytes(), &[cave_info.info_bump]]]
         ),
     )?;
 
@@ -73,7 +129,7 @@ pub struct Abort<'info> {
     #[account(
         mut,
         seeds = [&depositor_token_account.key().to_bytes()],
-        bump,
+        bump = cave_info.cave_bump,
         token::mint = mint,
         token::authority = cave_info,
     )]
@@ -82,9 +138,11 @@ pub struct Abort<'info> {
     /// This PDA stores the information about the associated cave
     #[account(
         mut,
-        close = depositor,
+        close = backup,
         seeds = [&cave.key().to_bytes()],
-        bump,
+        bump = cave_info.info_bump,
+        has_one = mint,
+        constraint = cave_info.kind == CaveKind::Token,
     )]
     pub cave_info: Account<'info, CaveInfo>,
 
@@ -100,9 +158,101 @@ pub struct Abort<'info> {
     #[account()]
     pub depositor_token_account: Account<'info, TokenAccount>,
 
-    #[account(mut)]
-    pub backup_spl_account: Account<'info, TokenAccount>,
+    pub backup_destination: BackupDestination<'info>,
 
     pub token_program: Program<'info, Token>,
 
-}
\ No newline at end of file
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+
+}
+
+/// The token account an abort of `cave_info` must send the funds to
+pub fn backup_spl_account_address(cave_info: &CaveInfo, backup: &Pubkey) -> Pubkey {
+
+    // A registered destination replaces the associated token account
+    cave_info.backup_destination
+        .unwrap_or_else(|| get_associated_token_address(backup, &cave_info.mint))
+}
+
+/// Where an abort sends the funds: the cave's registered backup
+/// destination if it has one, else the backup's associated token account,
+/// which is created if the backup never held the mint
+#[derive(Accounts)]
+pub struct BackupDestination<'info> {
+
+    /// CHECK: checked in `prepare`
+    #[account(mut)]
+    pub backup_spl_account: UncheckedAccount<'info>,
+
+    /// Whoever submits the abort. Pays for the associated token account
+    /// if it has to be created.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> BackupDestination<'info> {
+
+    /// Checks that `backup_spl_account` is where the backup of `cave_info`
+    /// receives `mint`, and creates it if it's a missing associated token
+    /// account
+    pub fn prepare(
+        &self,
+        cave_info: &CaveInfo,
+        backup: &AccountInfo<'info>,
+        mint: &AccountInfo<'info>,
+        token_program: &AccountInfo<'info>,
+    ) -> Result<()> {
+        self.prepare_account(&self.backup_spl_account, cave_info, backup, mint, token_program)
+    }
+
+    /// Like `prepare`, for a token account passed outside of this struct,
+    /// such as one of the token accounts of a basket abort
+    pub fn prepare_account(
+        &self,
+        backup_spl_account: &AccountInfo<'info>,
+        cave_info: &CaveInfo,
+        backup: &AccountInfo<'info>,
+        mint: &AccountInfo<'info>,
+        token_program: &AccountInfo<'info>,
+    ) -> Result<()> {
+
+        // A registered destination replaces the associated token account.
+        // A basket holds several mints while the destination holds one,
+        // so baskets still send the others to associated token accounts.
+        if cave_info.backup_destination == Some(backup_spl_account.key()) {
+            return Ok(());
+        }
+        require!(
+            cave_info.backup_destination.is_none() || cave_info.kind == CaveKind::Basket,
+            TokenCaveError::IncorrectBackupTokenAccount,
+        );
+        require_keys_eq!(
+            backup_spl_account.key(),
+            get_associated_token_address(backup.key, mint.key),
+            TokenCaveError::IncorrectBackupTokenAccount,
+        );
+        ata::create_if_missing(
+            backup_spl_account,
+            backup,
+            mint,
+            &self.payer,
+            &self.system_program,
+            token_program,
+            &self.associated_token_program,
+            &self.rent,
+        )
+    }
+}
@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+use super::initialize::{CaveInfo, CaveKind};
+use super::abort::BackupDestination;
+use super::withdraw_basket::{check_basket_complete, load_basket_cave, sweep_basket_cave};
+use crate::error::TokenCaveError;
+use crate::registry;
+
+/// Sweeps every mint of the basket to the backup. The remaining accounts
+/// are `[basket_cave, backup_spl_account, mint]` groups, one for each mint
+/// in the basket, with the first two writable. Missing associated token
+/// accounts of the backup are created.
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, AbortBasket<'info>>,
+) -> Result<()> {
+
+    // Check that this is the depositor and the backup account
+    require!(
+        ctx.accounts.depositor.key() == ctx.accounts.cave_info.depositor
+        && ctx.accounts.cave_info.is_backup(&ctx.accounts.backup),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Check that the user has requested an unlock
+    require!(
+        ctx.accounts.cave_info.unlocking,
+        TokenCaveError::DidNotRequestUnlock,
+    );
+
+    check_basket_complete(&ctx.accounts.cave_info, ctx.remaining_accounts, 3)?;
+    for accounts in ctx.remaining_accounts.chunks(3) {
+        let basket_cave = load_basket_cave(&ctx.accounts.cave_info, &accounts[0])?;
+        let (backup_spl_account, mint) = (&accounts[1], &accounts[2]);
+
+        // Check the token account the funds go to, creating it if needed
+        require_keys_eq!(
+            mint.key(),
+            basket_cave.mint,
+            TokenCaveError::IncorrectBackupTokenAccount,
+        );
+        ctx.accounts.backup_destination.prepare_account(
+            backup_spl_account,
+            &ctx.accounts.cave_info,
+            &ctx.accounts.backup,
+            mint,
+            &ctx.accounts.token_program.to_account_info(),
+        )?;
+
+        sweep_basket_cave(
+            &ctx.accounts.cave_info,
+            &basket_cave,
+            backup_spl_account,
+            &ctx.accounts.backup,
+            &ctx.accounts.token_program.to_account_info(),
+        )?;
+    }
+
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct AbortBasket<'info> {
+
+    /// This PDA stores the information about the basket
+    #[account(
+        mut,
+        close = backup,
+        seeds = [b"basket".as_ref(), cave_info.cave.as_ref()],
+        bump = cave_info.info_bump,
+        constraint = cave_info.kind == CaveKind::Basket,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// CHECK: checked against the cave info in the handler
+    #[account(mut)]
+    pub backup: AccountInfo<'info>,
+
+    /// Creates missing associated token accounts of the backup. Its
+    /// `backup_spl_account` is unused, since every mint has its own in the
+    /// remaining accounts.
+    pub backup_destination: BackupDestination<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
@@ -0,0 +1,130 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{TokenAccount, Token, Mint};
+use super::initialize::{CaveInfo, CaveKind};
+use super::abort::BackupDestination;
+use super::initialize_pnft::{PnftAccounts, PnftTransfer};
+use crate::error::TokenCaveError;
+use crate::registry;
+
+pub fn handler(
+    ctx: Context<AbortPnft>,
+) -> Result<()> {
+
+    // Check that this is the depositor and the backup account
+    require!(
+        ctx.accounts.depositor.key() == ctx.accounts.cave_info.depositor
+        && ctx.accounts.cave_info.is_backup(&ctx.accounts.backup),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Check that the user has requested an unlock
+    require!(
+        ctx.accounts.cave_info.unlocking,
+        TokenCaveError::DidNotRequestUnlock,
+    );
+
+    // Check the token account the pNFT goes to, creating it if needed
+    ctx.accounts.backup_destination.prepare(
+        &ctx.accounts.cave_info,
+        &ctx.accounts.backup,
+        &ctx.accounts.mint.to_account_info(),
+        &ctx.accounts.token_program.to_account_info(),
+    )?;
+
+    // Send the pNFT to the backup
+    let cave_key = ctx.accounts.cave.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[cave_key.as_ref(), &[ctx.accounts.cave_info.info_bump]]];
+    ctx.accounts.pnft.transfer(
+        PnftTransfer {
+            token: ctx.accounts.cave.to_account_info(),
+            token_owner: ctx.accounts.cave_info.to_account_info(),
+            destination: ctx.accounts.backup_destination.backup_spl_account.to_account_info(),
+            destination_owner: ctx.accounts.backup_spl_account_owner.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            authority: ctx.accounts.cave_info.to_account_info(),
+            payer: ctx.accounts.backup_destination.payer.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+        signer_seeds,
+    )?;
+
+    anchor_spl::token::close_account(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::CloseAccount {
+                account: ctx.accounts.cave.to_account_info(),
+                destination: ctx.accounts.backup.to_account_info(),
+                authority: ctx.accounts.cave_info.to_account_info(),
+            },
+            signer_seeds,
+        ),
+    )?;
+
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct AbortPnft<'info> {
+
+    /// The token cave holding the pNFT
+    #[account(
+        mut,
+        seeds = [&depositor_token_account.key().to_bytes()],
+        bump = cave_info.cave_bump,
+        token::mint = mint,
+        token::authority = cave_info,
+    )]
+    pub cave: Box<Account<'info, TokenAccount>>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        close = backup,
+        seeds = [&cave.key().to_bytes()],
+        bump = cave_info.info_bump,
+        has_one = mint,
+        constraint = cave_info.kind == CaveKind::ProgrammableNft,
+    )]
+    pub cave_info: Box<Account<'info, CaveInfo>>,
+
+    #[account()]
+    pub mint: Box<Account<'info, Mint>>,
+
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// CHECK: checked against the cave info in the handler
+    #[account(mut)]
+    pub backup: AccountInfo<'info>,
+
+    #[account()]
+    pub depositor_token_account: Box<Account<'info, TokenAccount>>,
+
+    pub backup_destination: BackupDestination<'info>,
+
+    /// CHECK: the token metadata program checks that this owns
+    /// `backup_spl_account`. This is the backup, unless it registered a
+    /// destination owned by another account.
+    #[account()]
+    pub backup_spl_account_owner: UncheckedAccount<'info>,
+
+    pub pnft: PnftAccounts<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
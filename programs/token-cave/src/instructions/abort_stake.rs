@@ -0,0 +1,89 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::stake::{self, state::StakeAuthorize};
+use super::initialize::{CaveInfo, CaveKind};
+use super::initialize_stake::authorize_stake;
+use crate::error::TokenCaveError;
+use crate::registry;
+
+pub fn handler(
+    ctx: Context<AbortStake>,
+) -> Result<()> {
+
+    // Check that this is the depositor and the backup account
+    require!(
+        ctx.accounts.depositor.key() == ctx.accounts.cave_info.depositor
+        && ctx.accounts.cave_info.is_backup(&ctx.accounts.backup),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Check that the user has requested an unlock
+    require!(
+        ctx.accounts.cave_info.unlocking,
+        TokenCaveError::DidNotRequestUnlock,
+    );
+
+    // Hand the stake account over to the backup, or to the account the
+    // backup registered as its destination
+    let new_authority = ctx.accounts.cave_info.backup_destination
+        .unwrap_or_else(|| ctx.accounts.backup.key());
+    for stake_authorize in [StakeAuthorize::Staker, StakeAuthorize::Withdrawer] {
+        authorize_stake(
+            &ctx.accounts.stake_account.to_account_info(),
+            &ctx.accounts.clock.to_account_info(),
+            &ctx.accounts.cave_info.to_account_info(),
+            &new_authority,
+            stake_authorize,
+            &[&[ctx.accounts.stake_account.key().as_ref(), &[ctx.accounts.cave_info.info_bump]]],
+        )?;
+    }
+
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct AbortStake<'info> {
+
+    /// CHECK: address is checked against the cave info
+    #[account(
+        mut,
+        address = cave_info.cave,
+        owner = stake::program::ID,
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    /// This PDA stores the information about the stake cave
+    #[account(
+        mut,
+        close = backup,
+        seeds = [&stake_account.key().to_bytes()],
+        bump = cave_info.info_bump,
+        constraint = cave_info.kind == CaveKind::Stake,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// CHECK: checked against the cave info in the handler
+    #[account(mut)]
+    pub backup: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: address is checked
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
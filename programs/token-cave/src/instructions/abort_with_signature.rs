@@ -0,0 +1,137 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar;
+use anchor_spl::token::{TokenAccount, Token, Mint};
+use super::initialize::{CaveInfo, CaveKind};
+use super::abort::{sweep_to_backup, BackupDestination};
+use crate::error::TokenCaveError;
+use crate::signature;
+use crate::registry;
+
+/// Aborts an unlock on the backup's behalf. The backup signs
+/// `signature::abort_message` off-chain, and any relayer submits it behind
+/// an Ed25519 program instruction and pays the fees as the destination's
+/// `payer`, so the backup key never needs SOL.
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, AbortWithSignature<'info>>,
+    nonce: u64,
+    expiry: i64,
+) -> Result<()> {
+
+    // Check that the message is for this cave and still valid
+    require_eq!(
+        nonce,
+        ctx.accounts.cave_info.abort_nonce,
+        TokenCaveError::InvalidSignature,
+    );
+    require_gte!(
+        expiry,
+        Clock::get()?.unix_timestamp,
+        TokenCaveError::SignatureExpired,
+    );
+
+    // Check that the backup signed it
+    let backup = ctx.accounts.cave_info.backup_address
+        .ok_or(TokenCaveError::Unauthorized)?;
+    signature::verify_ed25519_signature(
+        &ctx.accounts.instructions_sysvar,
+        &backup,
+        &signature::abort_message(&ctx.accounts.cave_info.key(), nonce, expiry),
+    )?;
+
+    // Check the token account the funds go to, creating it if needed
+    require_keys_eq!(
+        ctx.accounts.backup.key(),
+        backup,
+        TokenCaveError::Unauthorized,
+    );
+    ctx.accounts.backup_destination.prepare(
+        &ctx.accounts.cave_info,
+        &ctx.accounts.backup,
+        &ctx.accounts.mint.to_account_info(),
+        &ctx.accounts.token_program.to_account_info(),
+    )?;
+
+    // Check that the user has requested an unlock, or that a price
+    // condition or hashlock could release the funds
+    let now = ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?);
+    require!(
+        ctx.accounts.cave_info.can_abort(now),
+        TokenCaveError::DidNotRequestUnlock,
+    );
+
+    // Closing the cave would strand pending withdraw tickets. Abort them
+    // first with `abort_withdraw_ticket`.
+    require_eq!(
+        ctx.accounts.cave_info.open_tickets,
+        0,
+        TokenCaveError::OutstandingWithdrawTickets,
+    );
+
+    sweep_to_backup(
+        &mut ctx.accounts.cave,
+        &ctx.accounts.cave_info,
+        &ctx.accounts.backup_destination.backup_spl_account,
+        &ctx.accounts.backup,
+        &ctx.accounts.token_program.to_account_info(),
+        ctx.remaining_accounts,
+    )?;
+
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct AbortWithSignature<'info> {
+
+    /// The token cave being aborted
+    #[account(
+        mut,
+        seeds = [&depositor_token_account.key().to_bytes()],
+        bump = cave_info.cave_bump,
+        token::mint = mint,
+        token::authority = cave_info,
+    )]
+    pub cave: Account<'info, TokenAccount>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        close = backup,
+        seeds = [&cave.key().to_bytes()],
+        bump = cave_info.info_bump,
+        has_one = mint,
+        constraint = cave_info.kind == CaveKind::Token,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account()]
+    pub mint: Account<'info, Mint>,
+
+    #[account()]
+    pub depositor_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: checked against `cave_info.backup_address`
+    #[account(mut)]
+    pub backup: AccountInfo<'info>,
+
+    /// The relayer is the payer
+    pub backup_destination: BackupDestination<'info>,
+
+    /// CHECK: address is checked
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
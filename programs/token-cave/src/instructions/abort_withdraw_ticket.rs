@@ -0,0 +1,98 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{TokenAccount, Token, Mint};
+use super::initialize::{CaveInfo, CaveKind};
+use super::request_withdraw_ticket::WithdrawTicket;
+use super::abort::BackupDestination;
+use crate::error::TokenCaveError;
+
+/// Sends the funds of a pending withdraw ticket to the backup address
+/// instead of its destination. The rest of the cave stays locked.
+pub fn handler(
+    ctx: Context<AbortWithdrawTicket>,
+) -> Result<()> {
+
+    // Check that this is the depositor and the backup account
+    require!(
+        ctx.accounts.depositor.key() == ctx.accounts.cave_info.depositor
+        && ctx.accounts.cave_info.is_backup(&ctx.accounts.backup),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Check the token account the funds go to, creating it if needed
+    ctx.accounts.backup_destination.prepare(
+        &ctx.accounts.cave_info,
+        &ctx.accounts.backup,
+        &ctx.accounts.mint.to_account_info(),
+        &ctx.accounts.token_program.to_account_info(),
+    )?;
+
+    anchor_spl::token::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::Transfer {
+                from: ctx.accounts.cave.to_account_info(),
+                to: ctx.accounts.backup_destination.backup_spl_account.to_account_info(),
+                authority: ctx.accounts.cave_info.to_account_info(),
+            },
+            &[&[ctx.accounts.cave.key().as_ref(), &[ctx.accounts.cave_info.info_bump]]]
+        ),
+        ctx.accounts.ticket.amount,
+    )?;
+
+    let amount = ctx.accounts.ticket.amount;
+    ctx.accounts.cave_info.release_ticket(amount);
+    ctx.accounts.cave_info.deposited_amount = ctx.accounts.cave_info.deposited_amount
+        .saturating_sub(amount);
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct AbortWithdrawTicket<'info> {
+
+    #[account(
+        mut,
+        close = backup,
+        seeds = [
+            b"ticket",
+            cave_info.key().as_ref(),
+            &ticket.id.to_le_bytes(),
+        ],
+        bump = ticket.bump,
+        has_one = cave_info,
+    )]
+    pub ticket: Account<'info, WithdrawTicket>,
+
+    /// The token cave the ticket withdraws from
+    #[account(
+        mut,
+        address = cave_info.cave,
+    )]
+    pub cave: Account<'info, TokenAccount>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        seeds = [&cave.key().to_bytes()],
+        bump = cave_info.info_bump,
+        constraint = cave_info.kind == CaveKind::Token,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// CHECK: checked against `cave_info.backup_address`
+    #[account(mut)]
+    pub backup: AccountInfo<'info>,
+
+    #[account(
+        address = cave_info.mint,
+    )]
+    pub mint: Account<'info, Mint>,
+
+    pub backup_destination: BackupDestination<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
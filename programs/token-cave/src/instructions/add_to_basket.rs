@@ -0,0 +1,113 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{TokenAccount, Token, Mint};
+
+use crate::error::TokenCaveError;
+use super::initialize::{CaveInfo, CaveKind};
+use super::initialize_basket::BASKET_MAX_MINTS;
+use super::initialize_config::ProgramConfig;
+
+
+/// Adds a new mint to a basket cave and deposits `deposit_amount` of it.
+/// Further deposits of a mint already in the basket are plain spl token
+/// transfers into its basket token account.
+pub fn handler(
+    ctx: Context<AddToBasket>,
+    deposit_amount: u64,
+) -> Result<()> {
+
+    // Check that the program is not paused
+    ctx.accounts.config.check_not_paused(Clock::get()?.unix_timestamp)?;
+
+    // Check that this is the depositor
+    require_keys_eq!(
+        ctx.accounts.cave_info.depositor,
+        ctx.accounts.depositor.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    // All mints share one unlock, so the basket is frozen once it starts
+    require!(
+        !ctx.accounts.cave_info.unlocking,
+        TokenCaveError::UnlockAlreadyActive,
+    );
+
+    require_gt!(
+        BASKET_MAX_MINTS,
+        ctx.accounts.cave_info.basket_size,
+        TokenCaveError::BasketIsFull,
+    );
+    ctx.accounts.cave_info.basket_size += 1;
+
+    // Store spl token in the basket
+    anchor_spl::token::transfer(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::Transfer {
+                from: ctx.accounts.depositor_token_account.to_account_info(),
+                to: ctx.accounts.basket_cave.to_account_info(),
+                authority: ctx.accounts.depositor.to_account_info(),
+            },
+        ),
+        deposit_amount,
+    )?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct AddToBasket<'info> {
+
+    /// Program-owned token account holding this mint for the basket
+    #[account(
+        init,
+        payer = payer,
+        seeds = [cave_info.key().as_ref(), mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = cave_info,
+    )]
+    pub basket_cave: Account<'info, TokenAccount>,
+
+    /// This PDA stores the information about the basket
+    #[account(
+        mut,
+        seeds = [b"basket".as_ref(), cave_info.cave.as_ref()],
+        bump = cave_info.info_bump,
+        constraint = cave_info.kind == CaveKind::Basket,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account()]
+    pub mint: Account<'info, Mint>,
+
+    #[account()]
+    pub depositor: Signer<'info>,
+
+    /// Pays the rent of the basket token account. This must be the cave's
+    /// rent payer, who gets the rent back on withdraw.
+    #[account(
+        mut,
+        constraint = payer.key() == cave_info.rent_payer @ TokenCaveError::Unauthorized,
+    )]
+    pub payer: Signer<'info>,
+
+    /// NOTE: this has no additional checks because the spl transfer
+    /// instruction requires `depositor` to have authority over funds
+    /// inside of this token account.
+    #[account(mut)]
+    pub depositor_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+
+    pub rent: Sysvar<'info, Rent>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
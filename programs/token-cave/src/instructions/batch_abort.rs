@@ -0,0 +1,105 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+use super::abort::{sweep_to_backup, BackupDestination};
+use super::batch_withdraw::load_cave;
+use crate::error::TokenCaveError;
+use crate::registry;
+
+/// Accounts per cave in the remaining accounts of `batch_abort`
+pub const BATCH_ABORT_GROUP_SIZE: usize = 4;
+
+
+/// Aborts every abortable cave of the depositor with this backup among the
+/// remaining accounts, which come in writable groups of
+/// `[cave, cave_info, backup_spl_account, mint]`. Missing associated token
+/// accounts of the backup are created. Caves that can't be aborted right
+/// now, because they are not unlocking, have pending withdraw tickets or
+/// have a strategy position, are skipped. No account in a
+/// group signs, so the groups can come from address lookup tables.
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, BatchAbort<'info>>,
+) -> Result<()> {
+
+    // Check the repeating pattern
+    require!(
+        !ctx.remaining_accounts.is_empty()
+        && ctx.remaining_accounts.len() % BATCH_ABORT_GROUP_SIZE == 0,
+        TokenCaveError::InvalidBatch,
+    );
+
+    let clock = Clock::get()?;
+    for group in ctx.remaining_accounts.chunks(BATCH_ABORT_GROUP_SIZE) {
+        let (mut cave, cave_info) = load_cave(&group[0], &group[1])?;
+        let (backup_spl_account, mint) = (&group[2], &group[3]);
+
+        // Check that this is the depositor and the backup account
+        require!(
+            ctx.accounts.depositor.key() == cave_info.depositor
+            && cave_info.is_backup(&ctx.accounts.backup),
+            TokenCaveError::Unauthorized,
+        );
+
+        // Skip caves that can't be aborted right now
+        let now = cave_info.timelock_unit.now(&clock);
+        if !cave_info.can_abort(now)
+            || cave_info.open_tickets > 0
+            || cave_info.strategy.is_some()
+        {
+            continue;
+        }
+
+        // Check the token account the funds go to, creating it if needed
+        require_keys_eq!(
+            mint.key(),
+            cave_info.mint,
+            TokenCaveError::IncorrectBackupTokenAccount,
+        );
+        ctx.accounts.backup_destination.prepare_account(
+            backup_spl_account,
+            &cave_info,
+            &ctx.accounts.backup,
+            mint,
+            &ctx.accounts.token_program.to_account_info(),
+        )?;
+
+        sweep_to_backup(
+            &mut cave,
+            &cave_info,
+            backup_spl_account,
+            &ctx.accounts.backup,
+            &ctx.accounts.token_program.to_account_info(),
+            &[],
+        )?;
+        cave_info.close(ctx.accounts.backup.clone())?;
+        registry::remove(&ctx.accounts.registry, &cave_info.key())?;
+    }
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct BatchAbort<'info> {
+
+    pub depositor: Signer<'info>,
+
+    /// CHECK: checked against `cave_info.backup_address` of every cave
+    #[account(mut)]
+    pub backup: AccountInfo<'info>,
+
+    /// Creates missing associated token accounts of the backup. Its
+    /// `backup_spl_account` is unused, since every cave has its own in the
+    /// remaining accounts.
+    pub backup_destination: BackupDestination<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), depositor.key().as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
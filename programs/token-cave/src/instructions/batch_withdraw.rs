@@ -0,0 +1,159 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{TokenAccount, Token};
+use super::initialize::{CaveInfo, CaveKind};
+use super::initialize_config::ProgramConfig;
+use crate::error::TokenCaveError;
+use crate::fee::{self, FeeTiming};
+use crate::registry;
+
+/// Accounts per cave in the remaining accounts of `batch_withdraw`
+pub const BATCH_WITHDRAW_GROUP_SIZE: usize = 6;
+
+
+/// Withdraws every matured cave among the remaining accounts, which come
+/// in writable groups of `[cave, cave_info, depositor_token_account,
+/// rent_payer, treasury_token_account, registry]`. Caves that can't be
+/// withdrawn right now are skipped: those not yet matured, with tickets, a
+/// strategy position or a duress unlock. No account in a group signs, so
+/// the groups can come from address lookup tables.
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, BatchWithdraw<'info>>,
+) -> Result<()> {
+
+    // Check that a pause does not block withdraws
+    let clock = Clock::get()?;
+    ctx.accounts.config.check_withdraw_allowed(clock.unix_timestamp)?;
+
+    // Check the repeating pattern
+    require!(
+        !ctx.remaining_accounts.is_empty()
+        && ctx.remaining_accounts.len() % BATCH_WITHDRAW_GROUP_SIZE == 0,
+        TokenCaveError::InvalidBatch,
+    );
+
+    for group in ctx.remaining_accounts.chunks(BATCH_WITHDRAW_GROUP_SIZE) {
+        let (cave, cave_info) = load_cave(&group[0], &group[1])?;
+        let depositor_token_account = &group[2];
+        let rent_payer = &group[3];
+        let treasury_token_account = &group[4];
+        let registry = &group[5];
+
+        // Skip caves that can't be withdrawn right now
+        let now = cave_info.timelock_unit.now(&clock);
+        if cave_info.check_withdrawable(now).is_err()
+            || cave_info.open_tickets > 0
+            || cave_info.strategy.is_some()
+            || cave_info.duress_routes_to_backup()
+        {
+            continue;
+        }
+
+        // Check the rest of the group, like the accounts of `withdraw`
+        require_keys_eq!(
+            Pubkey::create_program_address(
+                &[depositor_token_account.key.as_ref(), &[cave_info.cave_bump]],
+                &crate::ID,
+            ).map_err(|_| TokenCaveError::InvalidBatch)?,
+            cave.key(),
+            TokenCaveError::InvalidBatch,
+        );
+        require_keys_eq!(
+            rent_payer.key(),
+            cave_info.rent_payer,
+            TokenCaveError::InvalidBatch,
+        );
+        require_keys_eq!(
+            registry.key(),
+            Pubkey::find_program_address(&[b"registry", cave_info.depositor.as_ref()], &crate::ID).0,
+            TokenCaveError::InvalidBatch,
+        );
+
+        // Take the protocol fee out of the payout
+        let cave_key = cave.key();
+        let info_bump = [cave_info.info_bump];
+        let signer_seeds: &[&[&[u8]]] = &[&[cave_key.as_ref(), &info_bump]];
+        let fee = fee::fee_due(
+            &ctx.accounts.config,
+            &cave_info.fee,
+            treasury_token_account,
+            &cave_info.mint,
+            FeeTiming::Withdraw,
+            cave.amount,
+        )?;
+        if fee > 0 {
+            anchor_spl::token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    anchor_spl::token::Transfer {
+                        from: cave.to_account_info(),
+                        to: treasury_token_account.clone(),
+                        authority: cave_info.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                fee,
+            )?;
+        }
+
+        // Withdraw spl token from the token cave and close it
+        anchor_spl::token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: cave.to_account_info(),
+                    to: depositor_token_account.clone(),
+                    authority: cave_info.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            cave.amount.checked_sub(fee).unwrap(),
+        )?;
+        anchor_spl::token::close_account(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::CloseAccount {
+                    account: cave.to_account_info(),
+                    destination: rent_payer.clone(),
+                    authority: cave_info.to_account_info(),
+                },
+                signer_seeds,
+            ),
+        )?;
+        cave_info.close(rent_payer.clone())?;
+        registry::remove(registry, &cave_info.key())?;
+    }
+
+    Ok(())
+}
+
+
+/// Loads a token cave and its cave info from a batch group, checking that
+/// they belong together
+pub fn load_cave<'info>(
+    cave: &AccountInfo<'info>,
+    cave_info: &AccountInfo<'info>,
+) -> Result<(Account<'info, TokenAccount>, Account<'info, CaveInfo>)> {
+    let cave_info: Account<CaveInfo> = Account::try_from(cave_info)?;
+    require!(
+        cave_info.kind == CaveKind::Token
+        && cave_info.cave == cave.key()
+        && cave.is_writable
+        && cave_info.to_account_info().is_writable,
+        TokenCaveError::InvalidBatch,
+    );
+    Ok((Account::try_from(cave)?, cave_info))
+}
+
+
+#[derive(Accounts)]
+pub struct BatchWithdraw<'info> {
+
+    pub token_program: Program<'info, Token>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
@@ -0,0 +1,50 @@
+use anchor_lang::prelude::*;
+use super::initialize::CaveInfo;
+use super::request_withdraw_ticket::WithdrawTicket;
+use crate::error::TokenCaveError;
+
+/// Drops a pending withdraw ticket, returning its funds to the lock
+pub fn handler(
+    ctx: Context<CancelWithdrawTicket>,
+) -> Result<()> {
+
+    // Check that this is the depositor
+    require_keys_eq!(
+        ctx.accounts.cave_info.depositor,
+        ctx.accounts.depositor.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    ctx.accounts.cave_info.release_ticket(ctx.accounts.ticket.amount);
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct CancelWithdrawTicket<'info> {
+
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [
+            b"ticket",
+            cave_info.key().as_ref(),
+            &ticket.id.to_le_bytes(),
+        ],
+        bump = ticket.bump,
+        has_one = cave_info,
+        has_one = rent_payer,
+    )]
+    pub ticket: Account<'info, WithdrawTicket>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(mut)]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    pub depositor: Signer<'info>,
+
+    /// CHECK: gets the ticket's rent back, checked against `ticket.rent_payer`
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+}
@@ -0,0 +1,161 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use anchor_spl::token::{TokenAccount, Token, Mint};
+use super::initialize::{CaveInfo, CaveKind};
+use super::initialize_config::ProgramConfig;
+use crate::error::TokenCaveError;
+use crate::fee::{self, FeeTiming};
+use crate::registry;
+
+/// Releases a hashlocked cave to the hashlock's recipient, given the
+/// preimage of its hash before the deadline. Anyone can submit the claim,
+/// since it reveals the preimage either way.
+pub fn handler(
+    ctx: Context<ClaimHtlc>,
+    preimage: Vec<u8>,
+) -> Result<()> {
+
+    // Check that a pause does not block withdraws
+    ctx.accounts.config.check_withdraw_allowed(Clock::get()?.unix_timestamp)?;
+
+    // Check that the hashlock is still active
+    let hashlock = ctx.accounts.cave_info.hashlock
+        .ok_or(TokenCaveError::HashlockExpired)?;
+    require!(
+        ctx.accounts.cave_info.hashlock_active(Clock::get()?.unix_timestamp),
+        TokenCaveError::HashlockExpired,
+    );
+
+    // Check that the hashlock has served the timelock
+    require!(
+        !ctx.accounts.cave_info.hashlock_arming(
+            ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?),
+        ),
+        TokenCaveError::HashlockNotArmed,
+    );
+
+    // Check the preimage
+    require!(
+        hash(&preimage).to_bytes() == hashlock.hash,
+        TokenCaveError::InvalidPreimage,
+    );
+
+    // Check that the funds go to the recipient
+    require!(
+        ctx.accounts.recipient_token_account.owner == hashlock.recipient
+        && ctx.accounts.recipient_token_account.mint == ctx.accounts.mint.key(),
+        TokenCaveError::IncorrectDestinationTokenAccount,
+    );
+
+    // Take the protocol fee out of the payout
+    let fee = fee::fee_due(
+        &ctx.accounts.config,
+        &ctx.accounts.cave_info.fee,
+        &ctx.accounts.treasury_token_account,
+        &ctx.accounts.mint.key(),
+        FeeTiming::Withdraw,
+        ctx.accounts.cave.amount,
+    )?;
+    if fee > 0 {
+        anchor_spl::token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.cave.to_account_info(),
+                    to: ctx.accounts.treasury_token_account.to_account_info(),
+                    authority: ctx.accounts.cave_info.to_account_info(),
+                },
+                &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
+            ),
+            fee,
+        )?;
+    }
+
+    // Release spl token from the token cave
+    anchor_spl::token::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::Transfer {
+                from: ctx.accounts.cave.to_account_info(),
+                to: ctx.accounts.recipient_token_account.to_account_info(),
+                authority: ctx.accounts.cave_info.to_account_info(),
+            },
+            &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
+        ),
+        ctx.accounts.cave.amount.checked_sub(fee).unwrap(),
+    )?;
+
+    anchor_spl::token::close_account(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::CloseAccount {
+                account: ctx.accounts.cave.to_account_info(),
+                destination: ctx.accounts.rent_payer.to_account_info(),
+                authority: ctx.accounts.cave_info.to_account_info(),
+            },
+            &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
+        ),
+    )?;
+
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct ClaimHtlc<'info> {
+
+    /// The hashlocked token cave
+    #[account(
+        mut,
+        address = cave_info.cave,
+    )]
+    pub cave: Account<'info, TokenAccount>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [&cave.key().to_bytes()],
+        bump = cave_info.info_bump,
+        has_one = mint,
+        has_one = rent_payer,
+        constraint = cave_info.kind == CaveKind::Token,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account()]
+    pub mint: Account<'info, Mint>,
+
+    /// A token account of the hashlock's recipient
+    #[account(mut)]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: gets the rent back, checked against `cave_info.rent_payer`
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: receives the fee. Checked by `fee::fee_due` when one is owed.
+    #[account(mut)]
+    pub treasury_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
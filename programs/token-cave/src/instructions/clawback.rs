@@ -0,0 +1,109 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{TokenAccount, Token, Mint};
+use super::initialize::{CaveInfo, CaveKind};
+use crate::error::TokenCaveError;
+use crate::registry;
+
+/// Returns a gift to its depositor before the clawback deadline, even if
+/// the beneficiary has started an unlock. Like `abort`, this works while
+/// the program is paused.
+pub fn handler(
+    ctx: Context<Clawback>,
+) -> Result<()> {
+
+    // Check that this is the depositor
+    require_keys_eq!(
+        ctx.accounts.cave_info.depositor,
+        ctx.accounts.depositor.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Check that the clawback deadline has not passed
+    require!(
+        ctx.accounts.cave_info.can_claw_back(Clock::get()?.unix_timestamp),
+        TokenCaveError::ClawbackExpired,
+    );
+
+    // Return spl token from the gift cave
+    anchor_spl::token::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::Transfer {
+                from: ctx.accounts.cave.to_account_info(),
+                to: ctx.accounts.depositor_token_account.to_account_info(),
+                authority: ctx.accounts.cave_info.to_account_info(),
+            },
+            &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
+        ),
+        ctx.accounts.cave.amount,
+    )?;
+
+    anchor_spl::token::close_account(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::CloseAccount {
+                account: ctx.accounts.cave.to_account_info(),
+                destination: ctx.accounts.rent_payer.to_account_info(),
+                authority: ctx.accounts.cave_info.to_account_info(),
+            },
+            &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
+        ),
+    )?;
+
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct Clawback<'info> {
+
+    /// The gift cave
+    #[account(
+        mut,
+        address = cave_info.cave,
+    )]
+    pub cave: Account<'info, TokenAccount>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [&cave.key().to_bytes()],
+        bump = cave_info.info_bump,
+        has_one = mint,
+        has_one = rent_payer,
+        constraint = cave_info.kind == CaveKind::Gift,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account()]
+    pub mint: Account<'info, Mint>,
+
+    pub depositor: Signer<'info>,
+
+    /// Any of the depositor's token accounts for the mint
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = depositor,
+    )]
+    pub depositor_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: gets the rent back, checked against `cave_info.rent_payer`
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
@@ -0,0 +1,36 @@
+use anchor_lang::prelude::*;
+
+use crate::error::TokenCaveError;
+use super::initialize_config::ProgramConfig;
+use super::set_mint_fee::MintFeeOverride;
+
+
+/// Removes a mint's fee override, so the default schedule applies again
+pub fn handler(
+    _ctx: Context<ClearMintFee>,
+) -> Result<()> {
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct ClearMintFee<'info> {
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"fee".as_ref(), fee_override.mint.as_ref()],
+        bump = fee_override.bump,
+    )]
+    pub fee_override: Account<'info, MintFeeOverride>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+        has_one = authority @ TokenCaveError::Unauthorized,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
@@ -0,0 +1,93 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Mint;
+
+pub const VOTER_WEIGHT_RECORD_SIZE: usize = 164;
+
+
+pub fn handler(
+    ctx: Context<CreateVoterWeightRecord>,
+    realm: Pubkey,
+) -> Result<()> {
+
+    // Weight starts at zero and expired until the first update
+    ctx.accounts.voter_weight_record.realm = realm;
+    ctx.accounts.voter_weight_record.governing_token_mint = ctx.accounts.governing_token_mint.key();
+    ctx.accounts.voter_weight_record.governing_token_owner = ctx.accounts.governing_token_owner.key();
+    ctx.accounts.voter_weight_record.voter_weight = 0;
+    ctx.accounts.voter_weight_record.voter_weight_expiry = Some(0);
+    ctx.accounts.voter_weight_record.weight_action = None;
+    ctx.accounts.voter_weight_record.weight_action_target = None;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+#[instruction(realm: Pubkey)]
+pub struct CreateVoterWeightRecord<'info> {
+
+    /// SPL-Governance voter weight record for the owner's caves
+    #[account(
+        init,
+        payer = governing_token_owner,
+        seeds = [
+            b"voter-weight-record".as_ref(),
+            realm.as_ref(),
+            governing_token_mint.key().as_ref(),
+            governing_token_owner.key().as_ref(),
+        ],
+        space = VOTER_WEIGHT_RECORD_SIZE,
+        bump,
+    )]
+    pub voter_weight_record: Account<'info, VoterWeightRecord>,
+
+    /// The realm's community mint. Only caves of this mint count.
+    #[account()]
+    pub governing_token_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub governing_token_owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Voter weight record compatible with the spl-governance addin api.
+///
+/// NOTE: the name of this struct must not change, since the anchor
+/// discriminator of `VoterWeightRecord` is the one spl-governance expects.
+#[account]
+pub struct VoterWeightRecord {
+
+    /// The realm the weight is valid for
+    pub realm: Pubkey,
+
+    /// Governing token mint the weight is valid for
+    pub governing_token_mint: Pubkey,
+
+    /// Owner of the caves
+    pub governing_token_owner: Pubkey,
+
+    /// Vote-escrowed weight of the owner's caves
+    pub voter_weight: u64,
+
+    /// Slot after which the weight is stale
+    pub voter_weight_expiry: Option<u64>,
+
+    /// Action the weight is valid for. None means any action.
+    pub weight_action: Option<VoterWeightAction>,
+
+    /// Target the weight is valid for. None means any target.
+    pub weight_action_target: Option<Pubkey>,
+
+    /// Reserved by the addin api
+    pub reserved: [u8; 8],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoterWeightAction {
+    CastVote,
+    CommentProposal,
+    CreateGovernance,
+    CreateProposal,
+    SignOffProposal,
+}
@@ -0,0 +1,125 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{TokenAccount, Mint};
+use super::initialize::{CaveInfo, CaveKind};
+use super::initialize_config::ProgramConfig;
+use crate::error::TokenCaveError;
+use crate::strategy;
+
+/// Moves `amount` of the cave's tokens into a whitelisted yield strategy.
+/// The strategy specific accounts are passed in as remaining accounts.
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositToStrategy<'info>>,
+    amount: u64,
+) -> Result<()> {
+
+    // Check that the config authority whitelisted the strategy
+    strategy::check_whitelisted(
+        &ctx.accounts.config,
+        &ctx.accounts.strategy_program.key(),
+    )?;
+
+    // Check that this is the depositor
+    require_keys_eq!(
+        ctx.accounts.cave_info.depositor,
+        ctx.accounts.depositor.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Funds must be redeemable on withdraw, so don't deploy them once an
+    // unlock is underway
+    require!(
+        !ctx.accounts.cave_info.unlocking,
+        TokenCaveError::UnlockAlreadyActive,
+    );
+
+    // A claim must find the funds in the cave
+    require!(
+        !ctx.accounts.cave_info.hashlock_active(Clock::get()?.unix_timestamp),
+        TokenCaveError::HashlockActive,
+    );
+
+    // A cave can only be in one strategy at a time
+    if let Some(strategy) = ctx.accounts.cave_info.strategy {
+        require_keys_eq!(
+            strategy,
+            ctx.accounts.strategy_program.key(),
+            TokenCaveError::StrategyAlreadyActive,
+        );
+    }
+
+    // Funds reserved by withdraw tickets must stay in the cave
+    require_gte!(
+        ctx.accounts.cave.amount
+            .checked_sub(ctx.accounts.cave_info.ticketed_amount)
+            .unwrap(),
+        amount,
+        TokenCaveError::InsufficientUnreservedFunds,
+    );
+
+    strategy::deposit(
+        &ctx.accounts.strategy_program.to_account_info(),
+        &ctx.accounts.cave_info.to_account_info(),
+        &ctx.accounts.cave.to_account_info(),
+        &ctx.accounts.depositor.to_account_info(),
+        ctx.remaining_accounts,
+        amount,
+        &[&[ctx.accounts.cave.key().as_ref(), &[ctx.accounts.cave_info.info_bump]]],
+    )?;
+
+    ctx.accounts.cave_info.strategy = Some(ctx.accounts.strategy_program.key());
+    ctx.accounts.cave_info.strategy_deposited = ctx.accounts.cave_info.strategy_deposited
+        .checked_add(amount)
+        .unwrap();
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct DepositToStrategy<'info> {
+
+    /// The token cave! A program-owned spl token account
+    /// which supports deposits and time-locked withdraws.
+    /// The time-locked withdraw can be aborted, which sends
+    /// the tokens to the specified backup address
+    #[account(
+        mut,
+        seeds = [&depositor_token_account.key().to_bytes()],
+        bump = cave_info.cave_bump,
+        token::mint = mint,
+        token::authority = cave_info,
+    )]
+    pub cave: Account<'info, TokenAccount>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        seeds = [&cave.key().to_bytes()],
+        bump = cave_info.info_bump,
+        has_one = mint,
+        constraint = cave_info.kind == CaveKind::Token,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account()]
+    pub mint: Account<'info, Mint>,
+
+    /// Pays for any accounts the strategy needs to create
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// NOTE: only used to derive the cave
+    #[account()]
+    pub depositor_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: checked against the strategy whitelist before invoking
+    #[account(executable)]
+    pub strategy_program: UncheckedAccount<'info>,
+
+    /// Global settings, holding the strategy whitelist
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
@@ -1,10 +1,28 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::clock::DEFAULT_MS_PER_SLOT;
 use anchor_spl::token::{TokenAccount, Token, Mint};
 
 use crate::error::TokenCaveError;
+use crate::fee::{self, FeeSchedule, FeeTiming};
+use super::initialize_config::ProgramConfig;
+use crate::registry;
 
 pub const MAX_LOCK_DURATION: u32 = 7 * 24 * 60 * 60;
-pub const CAVE_INFO_SIZE: usize = 86;
+pub const MAX_LOCK_DURATION_SLOTS: u32 = MAX_LOCK_DURATION / 2 * 5;
+pub const MAX_LOCK_DURATION_EPOCHS: u32 = 3;
+pub const CAVE_INFO_SIZE: usize = 569;
+
+/// Offsets of fixed-size `CaveInfo` fields in the account data, for
+/// `getProgramAccounts` memcmp filters
+pub const CAVE_INFO_DEPOSITOR_OFFSET: usize = 8;
+pub const CAVE_INFO_MINT_OFFSET: usize = 40;
+pub const CAVE_INFO_CAVE_OFFSET: usize = 72;
+pub const CAVE_INFO_KIND_OFFSET: usize = 104;
+pub const CAVE_INFO_RENT_PAYER_OFFSET: usize = 105;
+
+/// Factor by which a duress unlock extends the timelock when the funds
+/// can't be routed to a backup
+pub const DURESS_TIMELOCK_MULTIPLIER: i64 = 10;
 
 
 pub fn handler(
@@ -12,21 +30,63 @@ pub fn handler(
     deposit_amount: u64,
     backup_address: Option<Pubkey>,
     timelock_duration: u32,
+    timelock_unit: TimelockUnit,
+    withdraw_window: Option<u32>,
 ) -> Result<()> {
 
+    // Check that the program is not paused
+    ctx.accounts.config.check_not_paused(Clock::get()?.unix_timestamp)?;
+
     // Check lock duration is under max lock duration
     require_gte!(
-        MAX_LOCK_DURATION,
+        timelock_unit.max_duration(),
         timelock_duration,
         TokenCaveError::DurationExceedsMaximum
     );
 
-    // Store backup address, timelock duration, and initialize util vars
-    ctx.accounts.cave_info.backup_address = backup_address;
-    ctx.accounts.cave_info.timelock_duration = timelock_duration;
-    ctx.accounts.cave_info.depositor = ctx.accounts.depositor.key();
-    ctx.accounts.cave_info.unlock_request_time = i64::MIN;
-    ctx.accounts.cave_info.unlocking = false;
+    // Take the protocol fee out of the deposit, and keep the schedule for
+    // the withdraw
+    let schedule = fee::schedule(&ctx.accounts.config, &ctx.accounts.fee_override)?;
+    let fee = fee::fee_due(
+        &ctx.accounts.config,
+        &schedule,
+        &ctx.accounts.treasury_token_account,
+        &ctx.accounts.mint.key(),
+        FeeTiming::Deposit,
+        deposit_amount,
+    )?;
+    if fee > 0 {
+        anchor_spl::token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.depositor_token_account.to_account_info(),
+                    to: ctx.accounts.treasury_token_account.to_account_info(),
+                    authority: ctx.accounts.depositor.to_account_info(),
+                },
+            ),
+            fee,
+        )?;
+    }
+    let deposit_amount = deposit_amount.checked_sub(fee).unwrap();
+
+    // Store what this info account belongs to, so that clients and
+    // other instructions don't have to re-derive it
+    ctx.accounts.cave_info.init(NewCave {
+        kind: CaveKind::Token,
+        depositor: ctx.accounts.depositor.key(),
+        mint: ctx.accounts.mint.key(),
+        cave: ctx.accounts.cave.key(),
+        rent_payer: ctx.accounts.payer.key(),
+        cave_bump: *ctx.bumps.get("cave").unwrap(),
+        info_bump: *ctx.bumps.get("cave_info").unwrap(),
+        deposited_amount: deposit_amount,
+        fee: schedule,
+        backup_address,
+        timelock_duration,
+        timelock_unit,
+        withdraw_window,
+    })?;
 
     // Store spl token in the token cave
     anchor_spl::token::transfer(
@@ -41,6 +101,16 @@ pub fn handler(
         deposit_amount,
     )?;
 
+    // List the cave in the depositor's registry
+    registry::add(
+        &ctx.accounts.registry,
+        &ctx.accounts.depositor.key(),
+        *ctx.bumps.get("registry").unwrap(),
+        &ctx.accounts.cave_info.key(),
+        &ctx.accounts.payer,
+        &ctx.accounts.system_program,
+    )?;
+
     Ok(())
 }
 
@@ -54,7 +124,7 @@ pub struct Initialize<'info> {
     /// the tokens to the specified backup address
     #[account(
         init,
-        payer = depositor,
+        payer = payer,
         seeds = [&depositor_token_account.key().to_bytes()],
         bump,
         token::mint = mint,
@@ -65,7 +135,7 @@ pub struct Initialize<'info> {
     /// This PDA stores the information about the associated cave
     #[account(
         init,
-        payer = depositor,
+        payer = payer,
         seeds = [&cave.key().to_bytes()],
         space = CAVE_INFO_SIZE,
         bump,
@@ -75,9 +145,16 @@ pub struct Initialize<'info> {
     #[account()]
     pub mint: Account<'info, Mint>,
 
-    #[account(mut)]
+    /// Owns the deposited tokens. This can be a PDA, e.g. a multisig
+    /// vault, signing through CPI.
+    #[account()]
     pub depositor: Signer<'info>,
 
+    /// Pays the rent of the cave's accounts and gets it back on withdraw.
+    /// This is the depositor, unless someone sponsors the cave.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
     /// NOTE: this has no additional checks because the spl transfer
     /// instruction requires `depositor` to have authority over funds
     /// inside of this token account.
@@ -89,17 +166,58 @@ pub struct Initialize<'info> {
     pub system_program: Program<'info, System>,
 
     pub rent: Sysvar<'info, Rent>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: the mint's fee override, if it has one. Read by `fee::schedule`.
+    #[account(
+        seeds = [b"fee".as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub fee_override: UncheckedAccount<'info>,
+
+    /// CHECK: receives the fee. Checked by `fee::fee_due` when one is owed.
+    #[account(mut)]
+    pub treasury_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: the depositor's cave registry, created with their first
+    /// cave. Written by `registry::add`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), depositor.key().as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
 }
 
 #[account]
 pub struct CaveInfo {
 
-    /// Backup address in case things go south
-    pub backup_address: Option<Pubkey>,
+    // Fixed-size fields come first, so that they have stable offsets for
+    // `getProgramAccounts` filters, see `CAVE_INFO_DEPOSITOR_OFFSET` etc.
+    // Options change size and must stay at the end.
 
     /// Depositor
     pub depositor: Pubkey,
 
+    /// Mint of the tokens held in the cave
+    pub mint: Pubkey,
+
+    /// The cave (token account) this info account belongs to
+    pub cave: Pubkey,
+
+    /// What the cave holds
+    pub kind: CaveKind,
+
+    /// Paid the rent of the cave's accounts and gets it back on withdraw.
+    /// Aborts refund all rent to the backup instead.
+    pub rent_payer: Pubkey,
+
     /// Timelock duration
     pub timelock_duration: u32,
 
@@ -108,10 +226,260 @@ pub struct CaveInfo {
 
     /// Flag whether user is unlocking: bool,
     pub unlocking: bool,
+
+    /// Bump of the cave PDA
+    pub cave_bump: u8,
+
+    /// Bump of this PDA
+    pub info_bump: u8,
+
+    /// Amount deposited at initialization
+    pub deposited_amount: u64,
+
+    /// Unix timestamp at which the cave was created
+    pub created_at: i64,
+
+    /// Principal deposited into the strategy
+    pub strategy_deposited: u64,
+
+    /// Number of mints held by a basket cave
+    pub basket_size: u8,
+
+    /// Clock the timelock duration and unlock request time are measured in
+    pub timelock_unit: TimelockUnit,
+
+    /// Id of the next withdraw ticket, used to derive its address
+    pub next_ticket_id: u64,
+
+    /// Number of withdraw tickets that are still pending
+    pub open_tickets: u16,
+
+    /// Sum of the amounts of pending withdraw tickets
+    pub ticketed_amount: u64,
+
+    /// `duress_commitment(signer, salt)` of the current unlock. It equals
+    /// `duress_commitment` if the unlock was signed with the duress key.
+    pub unlock_proof: [u8; 32],
+
+    /// Nonce a signed abort must name. This is the creation slot, so a
+    /// signature can't be replayed against a cave re-created at the same
+    /// address.
+    pub abort_nonce: u64,
+
+    /// Fee schedule at the time of the deposit, which withdraws pay
+    pub fee: FeeSchedule,
+
+    /// Backup address in case things go south
+    pub backup_address: Option<Pubkey>,
+
+    /// Yield strategy program holding some of the cave's funds, if any
+    pub strategy: Option<Pubkey>,
+
+    /// Time after the timelock is up during which the cave can be
+    /// withdrawn, in `timelock_unit`. Once it passes, the unlock lapses and
+    /// a new unlock with a full wait is required. None means no expiry.
+    pub withdraw_window: Option<u32>,
+
+    /// Salted hash of a secondary key that can unlock the cave under
+    /// duress, see `set_duress_key`
+    pub duress_commitment: Option<[u8; 32]>,
+
+    /// Token account aborts send the funds to, registered by the backup.
+    /// None means the backup's associated token account.
+    pub backup_destination: Option<Pubkey>,
+
+    /// Who unlocks and withdraws a gift cave. None means the depositor.
+    pub beneficiary: Option<Pubkey>,
+
+    /// Unix timestamp until which the depositor of a gift cave can claw
+    /// it back. None means no clawback.
+    pub clawback_deadline: Option<i64>,
+
+    /// Hash-timelock of an atomic swap, see `set_hashlock`
+    pub hashlock: Option<Hashlock>,
+
+    /// Price condition that releases the cave early, see
+    /// `set_price_condition`
+    pub price_condition: Option<PriceCondition>,
+}
+
+/// What an initializer knows about a new cave, see `CaveInfo::init`
+pub struct NewCave {
+    pub kind: CaveKind,
+    pub depositor: Pubkey,
+    pub mint: Pubkey,
+    pub cave: Pubkey,
+    pub rent_payer: Pubkey,
+    pub cave_bump: u8,
+    pub info_bump: u8,
+    pub deposited_amount: u64,
+    pub fee: FeeSchedule,
+    pub backup_address: Option<Pubkey>,
+    pub timelock_duration: u32,
+    pub timelock_unit: TimelockUnit,
+    pub withdraw_window: Option<u32>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hashlock {
+
+    /// SHA-256 hash of the secret that releases the funds
+    pub hash: [u8; 32],
+
+    /// Owner of the token account the funds are released to
+    pub recipient: Pubkey,
+
+    /// Unix timestamp until which the recipient can claim. Until then the
+    /// cave can't be unlocked.
+    pub deadline: i64,
+
+    /// Time from which the recipient can claim, in the cave's timelock unit
+    pub armed_at: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriceCondition {
+
+    /// Price feed account, read through `crate::oracle`
+    pub feed: Pubkey,
+
+    /// Which side of the threshold releases the cave
+    pub trigger: PriceTrigger,
+
+    /// Threshold as a fixed-point number, `threshold * 10^expo`
+    pub threshold: i64,
+
+    /// Exponent of the feed when the condition was set
+    pub expo: i32,
+
+    /// Time from which the condition counts, in the cave's timelock unit
+    pub armed_at: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceTrigger {
+
+    /// Release once the price is at or above the threshold
+    AtOrAbove,
+
+    /// Release once the price is at or below the threshold
+    AtOrBelow,
+}
+
+impl PriceTrigger {
+
+    /// Whether `price` satisfies the trigger for `threshold`
+    pub fn is_met(&self, price: i64, threshold: i64) -> bool {
+        match self {
+            PriceTrigger::AtOrAbove => price >= threshold,
+            PriceTrigger::AtOrBelow => price <= threshold,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaveKind {
+
+    /// `cave` is a program-owned spl token account. This includes
+    /// regular (non-programmable) NFTs.
+    Token,
+
+    /// `cave` is a native stake account whose stake and withdraw
+    /// authority is the cave info
+    Stake,
+
+    /// `cave` is a program-owned spl token account holding a Metaplex
+    /// programmable NFT, which can only be moved by the token metadata
+    /// program
+    ProgrammableNft,
+
+    /// `cave` is an arbitrary key identifying the basket. The basket holds
+    /// one program-owned token account per mint, derived from the cave info
+    /// and the mint.
+    Basket,
+
+    /// `cave` is a program-owned spl token account funded by the depositor
+    /// for `beneficiary`, who unlocks and withdraws it
+    Gift,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimelockUnit {
+
+    /// Seconds of `Clock::unix_timestamp`
+    UnixTimestamp,
+
+    /// Slots of `Clock::slot`, which are not subject to validator
+    /// timestamp drift
+    Slot,
+
+    /// Epochs of `Clock::epoch`
+    Epoch,
+}
+
+impl TimelockUnit {
+
+    /// Maximum timelock duration in this unit, roughly a week
+    pub fn max_duration(&self) -> u32 {
+        match self {
+            TimelockUnit::UnixTimestamp => MAX_LOCK_DURATION,
+            TimelockUnit::Slot => MAX_LOCK_DURATION_SLOTS,
+            TimelockUnit::Epoch => MAX_LOCK_DURATION_EPOCHS,
+        }
+    }
+
+    /// Current time in this unit
+    pub fn now(&self, clock: &Clock) -> i64 {
+        match self {
+            TimelockUnit::UnixTimestamp => clock.unix_timestamp,
+            TimelockUnit::Slot => clock.slot as i64,
+            TimelockUnit::Epoch => clock.epoch as i64,
+        }
+    }
 }
 
 impl CaveInfo {
 
+    /// Sets up a freshly created cave info. Every field is written, so
+    /// nothing carries over from a closed cave at the same address, and
+    /// the fields that aren't part of `cave` start out empty.
+    pub fn init(&mut self, cave: NewCave) -> Result<()> {
+        let clock = Clock::get()?;
+        *self = CaveInfo {
+            depositor: cave.depositor,
+            mint: cave.mint,
+            cave: cave.cave,
+            kind: cave.kind,
+            rent_payer: cave.rent_payer,
+            timelock_duration: cave.timelock_duration,
+            unlock_request_time: i64::MIN,
+            unlocking: false,
+            cave_bump: cave.cave_bump,
+            info_bump: cave.info_bump,
+            deposited_amount: cave.deposited_amount,
+            created_at: clock.unix_timestamp,
+            strategy_deposited: 0,
+            basket_size: 0,
+            timelock_unit: cave.timelock_unit,
+            next_ticket_id: 0,
+            open_tickets: 0,
+            ticketed_amount: 0,
+            unlock_proof: [0; 32],
+            abort_nonce: clock.slot,
+            fee: cave.fee,
+            backup_address: cave.backup_address,
+            strategy: None,
+            withdraw_window: cave.withdraw_window,
+            duress_commitment: None,
+            backup_destination: None,
+            beneficiary: None,
+            clawback_deadline: None,
+            hashlock: None,
+            price_condition: None,
+        };
+        Ok(())
+    }
+
     pub fn is_backup<'info>(
         &self,
         account: &AccountInfo<'info>
@@ -122,4 +490,208 @@ impl CaveInfo {
             false
         }
     }
+
+    /// Key that unlocks the cave: the beneficiary of a gift, otherwise the
+    /// depositor
+    pub fn owner(&self) -> Pubkey {
+        self.beneficiary.unwrap_or(self.depositor)
+    }
+
+    /// Whether the depositor can still claw the gift back at unix
+    /// timestamp `now`
+    pub fn can_claw_back(&self, now: i64) -> bool {
+        match self.clawback_deadline {
+            Some(deadline) => now <= deadline,
+            None => false,
+        }
+    }
+
+    /// Who the funds count for in `verify_lock` and voting at unix
+    /// timestamp `now`: the owner, or nobody while the depositor of a gift
+    /// can still claw it back
+    pub fn holder(&self, now: i64) -> Option<Pubkey> {
+        if self.can_claw_back(now) {
+            None
+        } else {
+            Some(self.owner())
+        }
+    }
+
+    /// Whether a hashlock holds the funds for its recipient at unix
+    /// timestamp `now`
+    pub fn hashlock_active(&self, now: i64) -> bool {
+        match self.hashlock {
+            Some(hashlock) => now <= hashlock.deadline,
+            None => false,
+        }
+    }
+
+    /// Whether a hashlock is set but can't be claimed yet at `now`, in the
+    /// cave's timelock unit
+    pub fn hashlock_arming(&self, now: i64) -> bool {
+        match self.hashlock {
+            Some(hashlock) => now < hashlock.armed_at,
+            None => false,
+        }
+    }
+
+    /// Whether the backup can abort at `now`, in the cave's timelock unit:
+    /// during an unlock, whenever a price condition could release the funds
+    /// without one, or while a hashlock is arming
+    pub fn can_abort(&self, now: i64) -> bool {
+        self.unlocking || self.price_condition.is_some() || self.hashlock_arming(now)
+    }
+
+    /// Time after which an unlocked cave can be withdrawn, in the cave's
+    /// timelock unit
+    pub fn earliest_withdraw_time(&self) -> i64 {
+        self.unlock_request_time
+            .checked_add(self.unlock_duration())
+            .unwrap()
+    }
+
+    /// Whether the current unlock was signed with the duress key
+    pub fn is_duress_unlock(&self) -> bool {
+        self.duress_commitment == Some(self.unlock_proof)
+    }
+
+    /// Timelock of the current unlock. A duress unlock whose funds can't
+    /// be sent to the backup has to wait a lot longer.
+    pub fn unlock_duration(&self) -> i64 {
+        let duration = i64::from(self.timelock_duration);
+        if self.is_duress_unlock() && !self.duress_routes_to_backup() {
+            duration.checked_mul(DURESS_TIMELOCK_MULTIPLIER).unwrap()
+        } else {
+            duration
+        }
+    }
+
+    /// Whether withdrawing a duress unlock sends the funds to the backup.
+    /// Only token caves support this; other kinds get the extended timelock.
+    pub fn duress_routes_to_backup(&self) -> bool {
+        self.is_duress_unlock()
+            && self.backup_address.is_some()
+            && self.kind == CaveKind::Token
+    }
+
+    /// Whether the unlock has lapsed because its withdraw window passed
+    pub fn unlock_lapsed(&self, now: i64) -> bool {
+        match self.withdraw_window {
+            Some(window) => self.unlocking && now > self.earliest_withdraw_time()
+                .checked_add(window.into())
+                .unwrap(),
+            None => false,
+        }
+    }
+
+    /// Whether an unlock is in progress. A lapsed unlock does not count,
+    /// since the funds can't leave without a new unlock and full wait.
+    pub fn is_unlocking(&self, now: i64) -> bool {
+        self.unlocking && !self.unlock_lapsed(now)
+    }
+
+    /// Checks that the cave can be withdrawn at `now`, in the cave's
+    /// timelock unit
+    pub fn check_withdrawable(&self, now: i64) -> Result<()> {
+
+        // Check that the user has requested an unlock
+        require!(
+            self.unlocking,
+            TokenCaveError::DidNotRequestUnlock,
+        );
+
+        self.check_window(self.earliest_withdraw_time(), now)
+    }
+
+    /// Checks that a withdrawal requested at `request_time` has served the
+    /// timelock and, if the cave has a withdraw window, has not lapsed
+    pub fn check_matured(&self, request_time: i64, now: i64) -> Result<()> {
+        self.check_window(
+            request_time
+                .checked_add(self.timelock_duration.into())
+                .unwrap(),
+            now,
+        )
+    }
+
+    fn check_window(&self, earliest_withdraw_time: i64, now: i64) -> Result<()> {
+
+        // Check that the timelock is up
+        require_gt!(
+            now,
+            earliest_withdraw_time,
+            TokenCaveError::LockIsActive,
+        );
+
+        // Check that the withdraw window has not passed
+        if let Some(window) = self.withdraw_window {
+            require_gte!(
+                earliest_withdraw_time.checked_add(window.into()).unwrap(),
+                now,
+                TokenCaveError::WithdrawWindowExpired,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Removes a settled or cancelled withdraw ticket from the totals
+    pub fn release_ticket(&mut self, amount: u64) {
+        self.open_tickets = self.open_tickets.checked_sub(1).unwrap();
+        self.ticketed_amount = self.ticketed_amount.checked_sub(amount).unwrap();
+    }
+
+    /// Approximate length in seconds of `duration` in the cave's timelock
+    /// unit. Slots and epochs are converted using the default slot duration
+    /// and the epoch schedule.
+    pub fn duration_seconds(&self, duration: u32) -> Result<u64> {
+        let slots = match self.timelock_unit {
+            TimelockUnit::UnixTimestamp => return Ok(duration.into()),
+            TimelockUnit::Slot => u64::from(duration),
+            TimelockUnit::Epoch => u64::from(duration)
+                .checked_mul(EpochSchedule::get()?.slots_per_epoch)
+                .unwrap(),
+        };
+        Ok(slots.checked_mul(DEFAULT_MS_PER_SLOT).unwrap() / 1000)
+    }
+
+    /// Time the funds are sure to stay in the cave, in the cave's timelock
+    /// unit, or None if they can leave at any time. Outside of an unlock
+    /// or a hashlock that is the full timelock, unless a price condition
+    /// arms sooner.
+    pub fn locked_duration(&self, clock: &Clock) -> Option<u32> {
+        let now = self.timelock_unit.now(clock);
+        if self.is_unlocking(now) || self.hashlock_active(clock.unix_timestamp) {
+            return None;
+        }
+        match self.price_condition {
+            Some(condition) if condition.armed_at <= now => None,
+            Some(condition) => Some(
+                u32::try_from(condition.armed_at - now)
+                    .unwrap_or(u32::MAX)
+                    .min(self.timelock_duration),
+            ),
+            None => Some(self.timelock_duration),
+        }
+    }
+
+    /// Vote-escrowed voting power of this cave: the deposited amount
+    /// scaled by the remaining lock time relative to the maximum lock
+    /// duration of its timelock unit.
+    ///
+    /// The remaining lock time is `locked_duration`, so the power drops to
+    /// zero during an unlock, until it lapses, while a hashlock is active
+    /// and once a price condition is armed.
+    pub fn voting_power(&self, clock: &Clock) -> u64 {
+        let locked_duration = match self.locked_duration(clock) {
+            Some(locked_duration) => locked_duration,
+            None => return 0,
+        };
+        // Funds reserved by withdraw tickets are on their way out
+        (self.deposited_amount.saturating_sub(self.ticketed_amount) as u128)
+            .checked_mul(locked_duration.into())
+            .unwrap()
+            .checked_div(self.timelock_unit.max_duration().into())
+            .unwrap() as u64
+    }
 }
\ No newline at end of file
@@ -0,0 +1,107 @@
+use anchor_lang::prelude::*;
+
+use crate::error::TokenCaveError;
+use crate::fee::FeeSchedule;
+use super::initialize::{CaveInfo, CaveKind, NewCave, CAVE_INFO_SIZE, TimelockUnit};
+use super::initialize_config::ProgramConfig;
+use crate::registry;
+
+/// Maximum number of mints a basket cave can hold
+pub const BASKET_MAX_MINTS: u8 = 10;
+
+
+/// Creates an empty basket cave. `basket` is an arbitrary key chosen by
+/// the depositor that identifies the basket; mints are added with
+/// `add_to_basket`.
+pub fn handler(
+    ctx: Context<InitializeBasket>,
+    basket: Pubkey,
+    backup_address: Option<Pubkey>,
+    timelock_duration: u32,
+    timelock_unit: TimelockUnit,
+    withdraw_window: Option<u32>,
+) -> Result<()> {
+
+    // Check that the program is not paused
+    ctx.accounts.config.check_not_paused(Clock::get()?.unix_timestamp)?;
+
+    // Check lock duration is under max lock duration
+    require_gte!(
+        timelock_unit.max_duration(),
+        timelock_duration,
+        TokenCaveError::DurationExceedsMaximum
+    );
+
+    // A basket holds several mints in PDAs derived from the cave info, so
+    // it has neither a single mint nor a cave bump
+    ctx.accounts.cave_info.init(NewCave {
+        kind: CaveKind::Basket,
+        depositor: ctx.accounts.depositor.key(),
+        mint: Pubkey::default(),
+        cave: basket,
+        rent_payer: ctx.accounts.payer.key(),
+        cave_bump: 0,
+        info_bump: *ctx.bumps.get("cave_info").unwrap(),
+        deposited_amount: 0,
+        fee: FeeSchedule::NONE,
+        backup_address,
+        timelock_duration,
+        timelock_unit,
+        withdraw_window,
+    })?;
+
+    // List the cave in the depositor's registry
+    registry::add(
+        &ctx.accounts.registry,
+        &ctx.accounts.depositor.key(),
+        *ctx.bumps.get("registry").unwrap(),
+        &ctx.accounts.cave_info.key(),
+        &ctx.accounts.payer,
+        &ctx.accounts.system_program,
+    )?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+#[instruction(basket: Pubkey)]
+pub struct InitializeBasket<'info> {
+
+    /// This PDA stores the information about the basket and is the
+    /// authority of every token account in it
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"basket".as_ref(), basket.as_ref()],
+        space = CAVE_INFO_SIZE,
+        bump,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account()]
+    pub depositor: Signer<'info>,
+
+    /// Pays the rent of the cave's accounts and gets it back on withdraw.
+    /// This is the depositor, unless someone sponsors the cave.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: the depositor's cave registry, created with their first
+    /// cave. Written by `registry::add`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), depositor.key().as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
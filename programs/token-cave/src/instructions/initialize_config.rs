@@ -0,0 +1,128 @@
+use anchor_lang::prelude::*;
+
+use crate::error::TokenCaveError;
+use crate::fee::{FeeSchedule, FEE_SCHEDULE_SIZE};
+use crate::program::TokenCave;
+
+/// Longest a pause can last before it expires on its own
+pub const MAX_PAUSE_DURATION: i64 = 14 * 24 * 60 * 60;
+
+/// Time after a pause expires before the next one can start, so that
+/// unlocks can mature and be withdrawn in between
+pub const PAUSE_COOLDOWN: i64 = 14 * 24 * 60 * 60;
+
+/// Most strategy programs the whitelist can hold
+pub const MAX_STRATEGIES: usize = 8;
+
+pub const PROGRAM_CONFIG_SIZE: usize = 8 + 32 + 32 + 1 + 8 + 1 + 1 + 32 + FEE_SCHEDULE_SIZE
+    + 4 + 32 * MAX_STRATEGIES;
+
+
+/// Creates the global program config. Only the program's upgrade authority
+/// can do this.
+pub fn handler(
+    ctx: Context<InitializeConfig>,
+    pause_authority: Pubkey,
+    withdraw_allowed_while_paused: bool,
+) -> Result<()> {
+
+    ctx.accounts.config.authority = ctx.accounts.authority.key();
+    ctx.accounts.config.pause_authority = pause_authority;
+    ctx.accounts.config.paused = false;
+    ctx.accounts.config.paused_at = i64::MIN / 2;
+    ctx.accounts.config.withdraw_allowed_while_paused = withdraw_allowed_while_paused;
+    ctx.accounts.config.bump = *ctx.bumps.get("config").unwrap();
+    ctx.accounts.config.treasury = ctx.accounts.authority.key();
+    ctx.accounts.config.fee = FeeSchedule::NONE;
+    ctx.accounts.config.strategies = Vec::new();
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+
+    /// Global settings of the program
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"config".as_ref()],
+        space = PROGRAM_CONFIG_SIZE,
+        bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key()),
+    )]
+    pub program: Program<'info, TokenCave>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ TokenCaveError::Unauthorized,
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+pub struct ProgramConfig {
+
+    /// Can change the config
+    pub authority: Pubkey,
+
+    /// Can pause and unpause the program
+    pub pause_authority: Pubkey,
+
+    /// Whether the pause authority has paused the program
+    pub paused: bool,
+
+    /// Unix timestamp of the latest pause
+    pub paused_at: i64,
+
+    /// Whether `withdraw` keeps working while paused. `abort` always does.
+    pub withdraw_allowed_while_paused: bool,
+
+    /// Bump of this PDA
+    pub bump: u8,
+
+    /// Owner of the token accounts that receive fees
+    pub treasury: Pubkey,
+
+    /// Default fee schedule, see `crate::fee`
+    pub fee: FeeSchedule,
+
+    /// Strategy programs caves can deposit into, see `crate::strategy`
+    pub strategies: Vec<Pubkey>,
+}
+
+impl ProgramConfig {
+
+    /// Whether the program is paused at unix timestamp `now`. A pause
+    /// expires after `MAX_PAUSE_DURATION` even if nobody lifts it.
+    pub fn is_paused(&self, now: i64) -> bool {
+        self.paused && now <= self.paused_at.saturating_add(MAX_PAUSE_DURATION)
+    }
+
+    /// Fails if the program is paused
+    pub fn check_not_paused(&self, now: i64) -> Result<()> {
+        require!(
+            !self.is_paused(now),
+            TokenCaveError::ProgramPaused,
+        );
+        Ok(())
+    }
+
+    /// Fails if the program is paused and the policy blocks withdraws
+    pub fn check_withdraw_allowed(&self, now: i64) -> Result<()> {
+        if self.withdraw_allowed_while_paused {
+            return Ok(());
+        }
+        self.check_not_paused(now)
+    }
+}
@@ -0,0 +1,194 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{TokenAccount, Token, Mint};
+
+use crate::error::TokenCaveError;
+use crate::fee::{self, FeeTiming};
+use super::initialize::{CaveInfo, CaveKind, NewCave, TimelockUnit, CAVE_INFO_SIZE};
+use super::initialize_config::ProgramConfig;
+use crate::registry;
+
+
+/// Locks tokens for a beneficiary, who can unlock and withdraw them after
+/// the timelock. Until `clawback_deadline` the depositor can take them
+/// back with `clawback`.
+pub fn handler(
+    ctx: Context<InitializeGift>,
+    deposit_amount: u64,
+    beneficiary: Pubkey,
+    timelock_duration: u32,
+    timelock_unit: TimelockUnit,
+    withdraw_window: Option<u32>,
+    clawback_deadline: Option<i64>,
+) -> Result<()> {
+
+    // Check that the program is not paused
+    ctx.accounts.config.check_not_paused(Clock::get()?.unix_timestamp)?;
+
+    // Check lock duration is under max lock duration
+    require_gte!(
+        timelock_unit.max_duration(),
+        timelock_duration,
+        TokenCaveError::DurationExceedsMaximum
+    );
+
+    // Check that this is a gift to someone else
+    require_keys_neq!(
+        beneficiary,
+        ctx.accounts.depositor.key(),
+        TokenCaveError::InvalidBeneficiary,
+    );
+
+    // Take the protocol fee out of the deposit, and keep the schedule for
+    // the withdraw
+    let schedule = fee::schedule(&ctx.accounts.config, &ctx.accounts.fee_override)?;
+    let fee = fee::fee_due(
+        &ctx.accounts.config,
+        &schedule,
+        &ctx.accounts.treasury_token_account,
+        &ctx.accounts.mint.key(),
+        FeeTiming::Deposit,
+        deposit_amount,
+    )?;
+    if fee > 0 {
+        anchor_spl::token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.depositor_token_account.to_account_info(),
+                    to: ctx.accounts.treasury_token_account.to_account_info(),
+                    authority: ctx.accounts.depositor.to_account_info(),
+                },
+            ),
+            fee,
+        )?;
+    }
+    let deposit_amount = deposit_amount.checked_sub(fee).unwrap();
+
+    // Store what this info account belongs to. A gift has no backup, the
+    // clawback takes its place.
+    ctx.accounts.cave_info.init(NewCave {
+        kind: CaveKind::Gift,
+        depositor: ctx.accounts.depositor.key(),
+        mint: ctx.accounts.mint.key(),
+        cave: ctx.accounts.cave.key(),
+        rent_payer: ctx.accounts.payer.key(),
+        cave_bump: *ctx.bumps.get("cave").unwrap(),
+        info_bump: *ctx.bumps.get("cave_info").unwrap(),
+        deposited_amount: deposit_amount,
+        fee: schedule,
+        backup_address: None,
+        timelock_duration,
+        timelock_unit,
+        withdraw_window,
+    })?;
+    ctx.accounts.cave_info.beneficiary = Some(beneficiary);
+    ctx.accounts.cave_info.clawback_deadline = clawback_deadline;
+
+    // Store spl token in the token cave
+    anchor_spl::token::transfer(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::Transfer {
+                from: ctx.accounts.depositor_token_account.to_account_info(),
+                to: ctx.accounts.cave.to_account_info(),
+                authority: ctx.accounts.depositor.to_account_info(),
+            },
+        ),
+        deposit_amount,
+    )?;
+
+    // List the cave in the depositor's registry
+    registry::add(
+        &ctx.accounts.registry,
+        &ctx.accounts.depositor.key(),
+        *ctx.bumps.get("registry").unwrap(),
+        &ctx.accounts.cave_info.key(),
+        &ctx.accounts.payer,
+        &ctx.accounts.system_program,
+    )?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+#[instruction(deposit_amount: u64, beneficiary: Pubkey)]
+pub struct InitializeGift<'info> {
+
+    /// The gift cave, a program-owned spl token account. One depositor
+    /// token account can fund one gift per beneficiary.
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"gift".as_ref(),
+            depositor_token_account.key().as_ref(),
+            beneficiary.as_ref(),
+        ],
+        bump,
+        token::mint = mint,
+        token::authority = cave_info,
+    )]
+    pub cave: Account<'info, TokenAccount>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        init,
+        payer = payer,
+        seeds = [&cave.key().to_bytes()],
+        space = CAVE_INFO_SIZE,
+        bump,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account()]
+    pub mint: Account<'info, Mint>,
+
+    /// The funder
+    #[account()]
+    pub depositor: Signer<'info>,
+
+    /// Pays the rent of the cave's accounts and gets it back when the
+    /// gift is withdrawn or clawed back
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// NOTE: this has no additional checks because the spl transfer
+    /// instruction requires `depositor` to have authority over funds
+    /// inside of this token account.
+    #[account(mut)]
+    pub depositor_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+
+    pub rent: Sysvar<'info, Rent>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: the mint's fee override, if it has one. Read by `fee::schedule`.
+    #[account(
+        seeds = [b"fee".as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub fee_override: UncheckedAccount<'info>,
+
+    /// CHECK: receives the fee. Checked by `fee::fee_due` when one is owed.
+    #[account(mut)]
+    pub treasury_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: the depositor's cave registry, created with their first
+    /// cave. Written by `registry::add`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), depositor.key().as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
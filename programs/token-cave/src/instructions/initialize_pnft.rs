@@ -0,0 +1,327 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::solana_program::sysvar;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{TokenAccount, Token, Mint};
+
+use crate::error::TokenCaveError;
+use crate::fee::FeeSchedule;
+use super::initialize::{CaveInfo, CaveKind, NewCave, CAVE_INFO_SIZE, TimelockUnit};
+use super::initialize_config::ProgramConfig;
+use crate::registry;
+
+/// Metaplex token metadata program
+pub const TOKEN_METADATA_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
+    11, 112, 101, 177, 227, 209, 124, 69,
+    56, 157, 82, 127, 107, 4, 195, 205,
+    88, 184, 108, 115, 26, 160, 253, 181,
+    73, 182, 209, 188, 3, 248, 41, 70,
+]);
+
+/// Index of `Transfer` in the token metadata instruction enum
+const TOKEN_METADATA_TRANSFER_IX: u8 = 49;
+
+
+/// Locks a Metaplex programmable NFT. Regular NFTs can be locked with
+/// `initialize`, since they are plain spl tokens with a supply of one.
+pub fn handler(
+    ctx: Context<InitializePnft>,
+    backup_address: Option<Pubkey>,
+    timelock_duration: u32,
+    timelock_unit: TimelockUnit,
+    withdraw_window: Option<u32>,
+) -> Result<()> {
+
+    // Check that the program is not paused
+    ctx.accounts.config.check_not_paused(Clock::get()?.unix_timestamp)?;
+
+    // Check lock duration is under max lock duration
+    require_gte!(
+        timelock_unit.max_duration(),
+        timelock_duration,
+        TokenCaveError::DurationExceedsMaximum
+    );
+
+    // Store what this info account belongs to
+    ctx.accounts.cave_info.init(NewCave {
+        kind: CaveKind::ProgrammableNft,
+        depositor: ctx.accounts.depositor.key(),
+        mint: ctx.accounts.mint.key(),
+        cave: ctx.accounts.cave.key(),
+        rent_payer: ctx.accounts.payer.key(),
+        cave_bump: *ctx.bumps.get("cave").unwrap(),
+        info_bump: *ctx.bumps.get("cave_info").unwrap(),
+        deposited_amount: 1,
+        fee: FeeSchedule::NONE,
+        backup_address,
+        timelock_duration,
+        timelock_unit,
+        withdraw_window,
+    })?;
+
+    // Move the pNFT into the cave through the token metadata program,
+    // which enforces the asset's rule set and token records
+    ctx.accounts.pnft.transfer(
+        PnftTransfer {
+            token: ctx.accounts.depositor_token_account.to_account_info(),
+            token_owner: ctx.accounts.depositor.to_account_info(),
+            destination: ctx.accounts.cave.to_account_info(),
+            destination_owner: ctx.accounts.cave_info.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            authority: ctx.accounts.depositor.to_account_info(),
+            payer: ctx.accounts.payer.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+        &[],
+    )?;
+
+    // List the cave in the depositor's registry
+    registry::add(
+        &ctx.accounts.registry,
+        &ctx.accounts.depositor.key(),
+        *ctx.bumps.get("registry").unwrap(),
+        &ctx.accounts.cave_info.key(),
+        &ctx.accounts.payer,
+        &ctx.accounts.system_program,
+    )?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct InitializePnft<'info> {
+
+    /// The token cave! A program-owned spl token account
+    /// which supports deposits and time-locked withdraws.
+    /// The time-locked withdraw can be aborted, which sends
+    /// the tokens to the specified backup address
+    #[account(
+        init,
+        payer = payer,
+        seeds = [&depositor_token_account.key().to_bytes()],
+        bump,
+        token::mint = mint,
+        token::authority = cave_info,
+    )]
+    pub cave: Box<Account<'info, TokenAccount>>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        init,
+        payer = payer,
+        seeds = [&cave.key().to_bytes()],
+        space = CAVE_INFO_SIZE,
+        bump,
+    )]
+    pub cave_info: Box<Account<'info, CaveInfo>>,
+
+    #[account(
+        constraint = mint.decimals == 0 && mint.supply == 1,
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    #[account()]
+    pub depositor: Signer<'info>,
+
+    /// Pays the rent of the cave's accounts and gets it back on withdraw.
+    /// This is the depositor, unless someone sponsors the cave.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// NOTE: the token metadata program checks that `depositor` owns
+    /// this token account
+    #[account(mut)]
+    pub depositor_token_account: Box<Account<'info, TokenAccount>>,
+
+    pub pnft: PnftAccounts<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+
+    pub rent: Sysvar<'info, Rent>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: the depositor's cave registry, created with their first
+    /// cave. Written by `registry::add`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), depositor.key().as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
+
+/// Accounts the token metadata program needs to transfer a pNFT.
+/// Optional accounts that don't apply to the asset are set to the token
+/// metadata program id.
+#[derive(Accounts)]
+pub struct PnftAccounts<'info> {
+
+    /// CHECK: checked by the token metadata program
+    #[account(mut)]
+    pub metadata: UncheckedAccount<'info>,
+
+    /// CHECK: checked by the token metadata program
+    #[account()]
+    pub edition: UncheckedAccount<'info>,
+
+    /// CHECK: checked by the token metadata program
+    #[account(mut)]
+    pub owner_token_record: UncheckedAccount<'info>,
+
+    /// CHECK: checked by the token metadata program
+    #[account(mut)]
+    pub destination_token_record: UncheckedAccount<'info>,
+
+    /// CHECK: checked by the token metadata program
+    #[account()]
+    pub authorization_rules_program: UncheckedAccount<'info>,
+
+    /// CHECK: checked by the token metadata program
+    #[account()]
+    pub authorization_rules: UncheckedAccount<'info>,
+
+    /// CHECK: address is checked
+    #[account(address = TOKEN_METADATA_PROGRAM_ID)]
+    pub token_metadata_program: UncheckedAccount<'info>,
+
+    /// CHECK: address is checked
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: UncheckedAccount<'info>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+/// Accounts of a pNFT transfer that differ between instructions
+pub struct PnftTransfer<'info> {
+    pub token: AccountInfo<'info>,
+    pub token_owner: AccountInfo<'info>,
+    pub destination: AccountInfo<'info>,
+    pub destination_owner: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+    pub payer: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+}
+
+/// Keys of a token metadata `TransferV1` instruction
+pub struct TransferV1Keys {
+    pub token: Pubkey,
+    pub token_owner: Pubkey,
+    pub destination: Pubkey,
+    pub destination_owner: Pubkey,
+    pub mint: Pubkey,
+    pub metadata: Pubkey,
+    pub edition: Pubkey,
+    pub owner_token_record: Pubkey,
+    pub destination_token_record: Pubkey,
+    pub authority: Pubkey,
+    pub payer: Pubkey,
+    pub system_program: Pubkey,
+    pub sysvar_instructions: Pubkey,
+    pub token_program: Pubkey,
+    pub associated_token_program: Pubkey,
+    pub authorization_rules_program: Pubkey,
+    pub authorization_rules: Pubkey,
+}
+
+/// Builds a token metadata `TransferV1` instruction moving one token,
+/// with the accounts in the order the token metadata program expects
+pub fn transfer_v1_instruction(keys: &TransferV1Keys) -> Instruction {
+
+    // TransferArgs::V1 { amount: 1, authorization_data: None }
+    let mut data = vec![TOKEN_METADATA_TRANSFER_IX, 0];
+    data.extend_from_slice(&1_u64.to_le_bytes());
+    data.push(0);
+
+    Instruction {
+        program_id: TOKEN_METADATA_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(keys.token, false),
+            AccountMeta::new_readonly(keys.token_owner, false),
+            AccountMeta::new(keys.destination, false),
+            AccountMeta::new_readonly(keys.destination_owner, false),
+            AccountMeta::new_readonly(keys.mint, false),
+            AccountMeta::new(keys.metadata, false),
+            AccountMeta::new_readonly(keys.edition, false),
+            AccountMeta::new(keys.owner_token_record, false),
+            AccountMeta::new(keys.destination_token_record, false),
+            AccountMeta::new_readonly(keys.authority, true),
+            AccountMeta::new(keys.payer, true),
+            AccountMeta::new_readonly(keys.system_program, false),
+            AccountMeta::new_readonly(keys.sysvar_instructions, false),
+            AccountMeta::new_readonly(keys.token_program, false),
+            AccountMeta::new_readonly(keys.associated_token_program, false),
+            AccountMeta::new_readonly(keys.authorization_rules_program, false),
+            AccountMeta::new_readonly(keys.authorization_rules, false),
+        ],
+        data,
+    }
+}
+
+impl<'info> PnftAccounts<'info> {
+
+    /// Transfers the pNFT with the token metadata `TransferV1` instruction
+    pub fn transfer(
+        &self,
+        transfer: PnftTransfer<'info>,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+
+        let instruction = transfer_v1_instruction(&TransferV1Keys {
+            token: transfer.token.key(),
+            token_owner: transfer.token_owner.key(),
+            destination: transfer.destination.key(),
+            destination_owner: transfer.destination_owner.key(),
+            mint: transfer.mint.key(),
+            metadata: self.metadata.key(),
+            edition: self.edition.key(),
+            owner_token_record: self.owner_token_record.key(),
+            destination_token_record: self.destination_token_record.key(),
+            authority: transfer.authority.key(),
+            payer: transfer.payer.key(),
+            system_program: transfer.system_program.key(),
+            sysvar_instructions: self.sysvar_instructions.key(),
+            token_program: transfer.token_program.key(),
+            associated_token_program: self.associated_token_program.key(),
+            authorization_rules_program: self.authorization_rules_program.key(),
+            authorization_rules: self.authorization_rules.key(),
+        });
+
+        invoke_signed(
+            &instruction,
+            &[
+                transfer.token,
+                transfer.token_owner,
+                transfer.destination,
+                transfer.destination_owner,
+                transfer.mint,
+                self.metadata.to_account_info(),
+                self.edition.to_account_info(),
+                self.owner_token_record.to_account_info(),
+                self.destination_token_record.to_account_info(),
+                transfer.authority,
+                transfer.payer,
+                transfer.system_program,
+                self.sysvar_instructions.to_account_info(),
+                transfer.token_program,
+                self.associated_token_program.to_account_info(),
+                self.authorization_rules_program.to_account_info(),
+                self.authorization_rules.to_account_info(),
+                self.token_metadata_program.to_account_info(),
+            ],
+            signer_seeds,
+        ).map_err(Into::into)
+    }
+}
@@ -0,0 +1,153 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::solana_program::stake::{self, state::StakeAuthorize};
+
+use crate::error::TokenCaveError;
+use crate::fee::FeeSchedule;
+use super::initialize::{CaveInfo, CaveKind, NewCave, CAVE_INFO_SIZE, TimelockUnit};
+use super::initialize_config::ProgramConfig;
+use crate::registry;
+
+
+pub fn handler(
+    ctx: Context<InitializeStake>,
+    backup_address: Option<Pubkey>,
+    timelock_duration: u32,
+    timelock_unit: TimelockUnit,
+    withdraw_window: Option<u32>,
+) -> Result<()> {
+
+    // Check that the program is not paused
+    ctx.accounts.config.check_not_paused(Clock::get()?.unix_timestamp)?;
+
+    // Check lock duration is under max lock duration
+    require_gte!(
+        timelock_unit.max_duration(),
+        timelock_duration,
+        TokenCaveError::DurationExceedsMaximum
+    );
+
+    // The stake account plays the role of the cave. It is not a PDA, so
+    // there is no cave bump, and native SOL has no mint.
+    ctx.accounts.cave_info.init(NewCave {
+        kind: CaveKind::Stake,
+        depositor: ctx.accounts.depositor.key(),
+        mint: Pubkey::default(),
+        cave: ctx.accounts.stake_account.key(),
+        rent_payer: ctx.accounts.payer.key(),
+        cave_bump: 0,
+        info_bump: *ctx.bumps.get("cave_info").unwrap(),
+        deposited_amount: ctx.accounts.stake_account.lamports(),
+        fee: FeeSchedule::NONE,
+        backup_address,
+        timelock_duration,
+        timelock_unit,
+        withdraw_window,
+    })?;
+
+    // Make the cave info the stake and withdraw authority
+    for stake_authorize in [StakeAuthorize::Staker, StakeAuthorize::Withdrawer] {
+        authorize_stake(
+            &ctx.accounts.stake_account.to_account_info(),
+            &ctx.accounts.clock.to_account_info(),
+            &ctx.accounts.depositor.to_account_info(),
+            &ctx.accounts.cave_info.key(),
+            stake_authorize,
+            &[],
+        )?;
+    }
+
+    // List the cave in the depositor's registry
+    registry::add(
+        &ctx.accounts.registry,
+        &ctx.accounts.depositor.key(),
+        *ctx.bumps.get("registry").unwrap(),
+        &ctx.accounts.cave_info.key(),
+        &ctx.accounts.payer,
+        &ctx.accounts.system_program,
+    )?;
+
+    Ok(())
+}
+
+/// Hands the given stake authority over to `new_authority`
+pub fn authorize_stake<'info>(
+    stake_account: &AccountInfo<'info>,
+    clock: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    new_authority: &Pubkey,
+    stake_authorize: StakeAuthorize,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    invoke_signed(
+        &stake::instruction::authorize(
+            stake_account.key,
+            authority.key,
+            new_authority,
+            stake_authorize,
+            None,
+        ),
+        &[
+            stake_account.clone(),
+            clock.clone(),
+            authority.clone(),
+        ],
+        signer_seeds,
+    ).map_err(Into::into)
+}
+
+
+#[derive(Accounts)]
+pub struct InitializeStake<'info> {
+
+    /// CHECK: the stake program checks that `depositor` is both the
+    /// stake and withdraw authority when handing them over.
+    #[account(
+        mut,
+        owner = stake::program::ID,
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    /// This PDA stores the information about the stake cave and is the
+    /// stake and withdraw authority of the stake account
+    #[account(
+        init,
+        payer = payer,
+        seeds = [&stake_account.key().to_bytes()],
+        space = CAVE_INFO_SIZE,
+        bump,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account()]
+    pub depositor: Signer<'info>,
+
+    /// Pays the rent of the cave's accounts and gets it back on withdraw.
+    /// This is the depositor, unless someone sponsors the cave.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: address is checked
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: the depositor's cave registry, created with their first
+    /// cave. Written by `registry::add`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), depositor.key().as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
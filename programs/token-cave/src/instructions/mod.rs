@@ -2,3 +2,42 @@ pub mod initialize;
 pub mod unlock;
 pub mod withdraw;
 pub mod abort;
+pub mod verify_lock;
+pub mod create_voter_weight_record;
+pub mod update_voter_weight_record;
+pub mod deposit_to_strategy;
+pub mod initialize_stake;
+pub mod withdraw_stake;
+pub mod abort_stake;
+pub mod initialize_pnft;
+pub mod withdraw_pnft;
+pub mod abort_pnft;
+pub mod initialize_basket;
+pub mod add_to_basket;
+pub mod withdraw_basket;
+pub mod abort_basket;
+pub mod request_withdraw_ticket;
+pub mod cancel_withdraw_ticket;
+pub mod withdraw_ticket;
+pub mod abort_withdraw_ticket;
+pub mod set_duress_key;
+pub mod initialize_config;
+pub mod set_pause;
+pub mod update_config;
+pub mod set_fee_config;
+pub mod set_mint_fee;
+pub mod clear_mint_fee;
+pub mod abort_with_signature;
+pub mod set_backup_destination;
+pub mod initialize_gift;
+pub mod withdraw_gift;
+pub mod clawback;
+pub mod set_hashlock;
+pub mod claim_htlc;
+pub mod set_price_condition;
+pub mod release_on_price;
+pub mod register_session_key;
+pub mod revoke_session_key;
+pub mod batch_withdraw;
+pub mod batch_abort;
+pub mod set_strategies;
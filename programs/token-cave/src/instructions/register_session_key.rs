@@ -0,0 +1,143 @@
+use anchor_lang::prelude::*;
+use super::initialize::CaveInfo;
+use crate::error::TokenCaveError;
+
+pub const SESSION_KEY_SIZE: usize = 8 + 32 + 32 + 32 + 1 + 8 + 8 + 8 + 1;
+
+/// Session key scope: sign `unlock`
+pub const SCOPE_UNLOCK: u8 = 1 << 0;
+
+/// Session key scope: request withdraw tickets to the depositor's own
+/// token accounts, up to the session's `max_amount` in total
+pub const SCOPE_WITHDRAW: u8 = 1 << 1;
+
+pub const ALL_SCOPES: u8 = SCOPE_UNLOCK | SCOPE_WITHDRAW;
+
+
+/// Delegates the `scopes` of the cave's owner to `key` until `expires_at`
+/// (unix timestamp), so the main key can stay offline
+pub fn handler(
+    ctx: Context<RegisterSessionKey>,
+    key: Pubkey,
+    scopes: u8,
+    max_amount: u64,
+    expires_at: i64,
+) -> Result<()> {
+
+    // Check that this is the owner
+    require_keys_eq!(
+        ctx.accounts.cave_info.owner(),
+        ctx.accounts.owner.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Check the session
+    require!(
+        key != ctx.accounts.owner.key()
+        && scopes != 0
+        && scopes & !ALL_SCOPES == 0
+        && expires_at > Clock::get()?.unix_timestamp,
+        TokenCaveError::InvalidSessionKey,
+    );
+
+    ctx.accounts.session.cave_info = ctx.accounts.cave_info.key();
+    ctx.accounts.session.owner = ctx.accounts.owner.key();
+    ctx.accounts.session.key = key;
+    ctx.accounts.session.scopes = scopes;
+    ctx.accounts.session.max_amount = max_amount;
+    ctx.accounts.session.used_amount = 0;
+    ctx.accounts.session.expires_at = expires_at;
+    ctx.accounts.session.bump = *ctx.bumps.get("session").unwrap();
+
+    Ok(())
+}
+
+
+/// Checks that the first remaining account is a live session of `key` for
+/// `cave_info` with `scope`, and counts `amount` against its limit
+pub fn authorize_session<'info>(
+    remaining_accounts: &[AccountInfo<'info>],
+    cave_info: &Pubkey,
+    key: &Pubkey,
+    scope: u8,
+    amount: u64,
+) -> Result<()> {
+
+    let session_account = remaining_accounts
+        .first()
+        .ok_or(TokenCaveError::Unauthorized)?;
+    let mut session: Account<SessionKey> = Account::try_from(session_account)?;
+    require!(
+        session.cave_info == *cave_info
+        && session.key == *key
+        && session.scopes & scope == scope
+        && Clock::get()?.unix_timestamp <= session.expires_at,
+        TokenCaveError::InvalidSessionKey,
+    );
+
+    if amount > 0 {
+        session.used_amount = session.used_amount.checked_add(amount).unwrap();
+        require_gte!(
+            session.max_amount,
+            session.used_amount,
+            TokenCaveError::SessionLimitExceeded,
+        );
+        session.exit(&crate::ID)?;
+    }
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+#[instruction(key: Pubkey)]
+pub struct RegisterSessionKey<'info> {
+
+    #[account(
+        init,
+        payer = owner,
+        space = SESSION_KEY_SIZE,
+        seeds = [b"session".as_ref(), cave_info.key().as_ref(), key.as_ref()],
+        bump,
+    )]
+    pub session: Account<'info, SessionKey>,
+
+    /// This PDA stores the information about the associated cave
+    #[account()]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    /// The depositor, or a gift's beneficiary
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Delegated, expiring authority over some of a cave's operations
+#[account]
+pub struct SessionKey {
+
+    /// Cave info of the cave this session is for
+    pub cave_info: Pubkey,
+
+    /// Registered the session and gets its rent back
+    pub owner: Pubkey,
+
+    /// The delegated key, e.g. held by a mobile app
+    pub key: Pubkey,
+
+    /// Bitmask of `SCOPE_*`
+    pub scopes: u8,
+
+    /// Total amount the session can request with `SCOPE_WITHDRAW`
+    pub max_amount: u64,
+
+    /// Amount requested so far
+    pub used_amount: u64,
+
+    /// Unix timestamp after which the session is void
+    pub expires_at: i64,
+
+    /// Bump of this PDA
+    pub bump: u8,
+}
@@ -0,0 +1,200 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{TokenAccount, Token, Mint};
+use super::initialize::{CaveInfo, CaveKind};
+use super::initialize_config::ProgramConfig;
+use crate::error::TokenCaveError;
+use crate::strategy;
+use crate::fee::{self, FeeTiming};
+use crate::oracle;
+use crate::registry;
+
+/// Withdraws the cave without an unlock, because its price condition is
+/// armed and met
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, ReleaseOnPrice<'info>>,
+) -> Result<()> {
+
+    // Check that a pause does not block withdraws
+    let clock = Clock::get()?;
+    ctx.accounts.config.check_withdraw_allowed(clock.unix_timestamp)?;
+
+    // Check that this is the depositor
+    require_keys_eq!(
+        ctx.accounts.cave_info.depositor,
+        ctx.accounts.depositor.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Check that the condition is armed and met
+    let condition = ctx.accounts.cave_info.price_condition
+        .ok_or(TokenCaveError::PriceConditionNotMet)?;
+    require_keys_eq!(
+        ctx.accounts.feed.key(),
+        condition.feed,
+        TokenCaveError::UnsupportedPriceFeed,
+    );
+    let price = oracle::read_price(&ctx.accounts.feed, clock.unix_timestamp)?;
+    require_eq!(
+        price.expo,
+        condition.expo,
+        TokenCaveError::UnsupportedPriceFeed,
+    );
+    require!(
+        ctx.accounts.cave_info.timelock_unit.now(&clock) >= condition.armed_at
+        && condition.trigger.is_met(price.price, condition.threshold),
+        TokenCaveError::PriceConditionNotMet,
+    );
+
+    // Check that no hashlock holds the funds for a swap
+    require!(
+        !ctx.accounts.cave_info.hashlock_active(clock.unix_timestamp),
+        TokenCaveError::HashlockActive,
+    );
+
+    // Closing the cave would strand pending withdraw tickets
+    require_eq!(
+        ctx.accounts.cave_info.open_tickets,
+        0,
+        TokenCaveError::OutstandingWithdrawTickets,
+    );
+
+    // Bring back funds deployed to a yield strategy, along with the yield.
+    // The strategy program and its accounts are the remaining accounts.
+    if let Some(strategy) = ctx.accounts.cave_info.strategy {
+        let (strategy_program, strategy_accounts) = strategy::split_remaining_accounts(
+            strategy,
+            ctx.remaining_accounts,
+        )?;
+        strategy::redeem(
+            strategy_program,
+            &ctx.accounts.cave_info.to_account_info(),
+            &ctx.accounts.cave.to_account_info(),
+            &ctx.accounts.depositor.to_account_info(),
+            strategy_accounts,
+            &[&[ctx.accounts.cave.key().as_ref(), &[ctx.accounts.cave_info.info_bump]]],
+        )?;
+        ctx.accounts.cave.reload()?;
+    }
+
+    // Take the protocol fee out of the payout
+    let fee = fee::fee_due(
+        &ctx.accounts.config,
+        &ctx.accounts.cave_info.fee,
+        &ctx.accounts.treasury_token_account,
+        &ctx.accounts.mint.key(),
+        FeeTiming::Withdraw,
+        ctx.accounts.cave.amount,
+    )?;
+    if fee > 0 {
+        anchor_spl::token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.cave.to_account_info(),
+                    to: ctx.accounts.treasury_token_account.to_account_info(),
+                    authority: ctx.accounts.cave_info.to_account_info(),
+                },
+                &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
+            ),
+            fee,
+        )?;
+    }
+
+    // Withdraw spl token from the token cave
+    anchor_spl::token::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::Transfer {
+                from: ctx.accounts.cave.to_account_info(),
+                to: ctx.accounts.depositor_token_account.to_account_info(),
+                authority: ctx.accounts.cave_info.to_account_info(),
+            },
+            &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
+        ),
+        ctx.accounts.cave.amount.checked_sub(fee).unwrap(),
+    )?;
+
+    anchor_spl::token::close_account(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::CloseAccount {
+                account: ctx.accounts.cave.to_account_info(),
+                destination: ctx.accounts.rent_payer.to_account_info(),
+                authority: ctx.accounts.cave_info.to_account_info(),
+            },
+            &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
+        ),
+    )?;
+
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct ReleaseOnPrice<'info> {
+
+    /// The token cave being released
+    #[account(
+        mut,
+        seeds = [&depositor_token_account.key().to_bytes()],
+        bump = cave_info.cave_bump,
+        token::mint = mint,
+        token::authority = cave_info,
+    )]
+    pub cave: Account<'info, TokenAccount>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [&cave.key().to_bytes()],
+        bump = cave_info.info_bump,
+        has_one = mint,
+        has_one = rent_payer,
+        constraint = cave_info.kind == CaveKind::Token,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account()]
+    pub mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// CHECK: gets the rent back, checked against `cave_info.rent_payer`
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+
+    /// NOTE: this has no additional checks because the cave is derived
+    /// from it
+    #[account(mut)]
+    pub depositor_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: checked against the cave's price condition
+    pub feed: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: receives the fee. Checked by `fee::fee_due` when one is owed.
+    #[account(mut)]
+    pub treasury_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
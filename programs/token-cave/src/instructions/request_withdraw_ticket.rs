@@ -0,0 +1,158 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use super::initialize::{CaveInfo, CaveKind};
+use super::initialize_config::ProgramConfig;
+use super::register_session_key::{authorize_session, SCOPE_WITHDRAW};
+use crate::error::TokenCaveError;
+
+pub const WITHDRAW_TICKET_SIZE: usize = 8 + 32 + 8 + 8 + 8 + 32 + 1 + 32;
+
+/// Schedules a partial withdrawal of `amount` to `destination`. The ticket
+/// matures after the cave's timelock, independently of any other ticket or
+/// of a full unlock.
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, RequestWithdrawTicket<'info>>,
+    amount: u64,
+) -> Result<()> {
+
+    // Check that the program is not paused
+    ctx.accounts.config.check_not_paused(Clock::get()?.unix_timestamp)?;
+
+    // Check that this is the depositor, or a session key of the depositor
+    // passed as the first remaining account. A session can only pay out to
+    // the depositor's own token accounts.
+    let signer = ctx.accounts.depositor.key();
+    if ctx.accounts.cave_info.depositor != signer {
+        require_keys_eq!(
+            ctx.accounts.destination.owner,
+            ctx.accounts.cave_info.depositor,
+            TokenCaveError::IncorrectDestinationTokenAccount,
+        );
+        authorize_session(
+            ctx.remaining_accounts,
+            &ctx.accounts.cave_info.key(),
+            &signer,
+            SCOPE_WITHDRAW,
+            amount,
+        )?;
+    }
+
+    // Check that no hashlock holds the funds for a swap
+    require!(
+        !ctx.accounts.cave_info.hashlock_active(Clock::get()?.unix_timestamp),
+        TokenCaveError::HashlockActive,
+    );
+
+    // Check that the cave holds enough funds not already promised to other
+    // tickets. Funds deployed to a strategy don't count, since tickets
+    // don't redeem them.
+    let available = ctx.accounts.cave.amount
+        .checked_sub(ctx.accounts.cave_info.ticketed_amount)
+        .unwrap();
+    require_gte!(
+        available,
+        amount,
+        TokenCaveError::InsufficientUnreservedFunds,
+    );
+
+    let now = ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?);
+
+    ctx.accounts.ticket.cave_info = ctx.accounts.cave_info.key();
+    ctx.accounts.ticket.id = ctx.accounts.cave_info.next_ticket_id;
+    ctx.accounts.ticket.amount = amount;
+    ctx.accounts.ticket.request_time = now;
+    ctx.accounts.ticket.destination = ctx.accounts.destination.key();
+    ctx.accounts.ticket.bump = *ctx.bumps.get("ticket").unwrap();
+    ctx.accounts.ticket.rent_payer = signer;
+
+    ctx.accounts.cave_info.next_ticket_id = ctx.accounts.cave_info.next_ticket_id
+        .checked_add(1)
+        .unwrap();
+    ctx.accounts.cave_info.open_tickets = ctx.accounts.cave_info.open_tickets
+        .checked_add(1)
+        .unwrap();
+    ctx.accounts.cave_info.ticketed_amount = ctx.accounts.cave_info.ticketed_amount
+        .checked_add(amount)
+        .unwrap();
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct RequestWithdrawTicket<'info> {
+
+    /// The new pending withdrawal
+    #[account(
+        init,
+        payer = depositor,
+        space = WITHDRAW_TICKET_SIZE,
+        seeds = [
+            b"ticket",
+            cave_info.key().as_ref(),
+            &cave_info.next_ticket_id.to_le_bytes(),
+        ],
+        bump,
+    )]
+    pub ticket: Account<'info, WithdrawTicket>,
+
+    /// The token cave the ticket withdraws from
+    #[account(
+        address = cave_info.cave,
+    )]
+    pub cave: Account<'info, TokenAccount>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        constraint = cave_info.kind == CaveKind::Token,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    /// The depositor or a session key. Pays the ticket's rent and gets it
+    /// back when the ticket is paid out or cancelled.
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// Where the funds go once the ticket matures, e.g. a payee
+    #[account(
+        token::mint = cave_info.mint,
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+/// A pending partial withdrawal from a token cave
+#[account]
+pub struct WithdrawTicket {
+
+    /// Cave info of the cave this ticket withdraws from
+    pub cave_info: Pubkey,
+
+    /// Sequence number within the cave, part of the PDA seeds
+    pub id: u64,
+
+    /// Amount to withdraw
+    pub amount: u64,
+
+    /// Time of the request, in the cave's timelock unit
+    pub request_time: i64,
+
+    /// Token account receiving the funds
+    pub destination: Pubkey,
+
+    /// Bump of this PDA
+    pub bump: u8,
+
+    /// Paid the ticket's rent and gets it back when the ticket is paid out
+    /// or cancelled. Aborts refund it to the backup instead.
+    pub rent_payer: Pubkey,
+}
@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+use super::register_session_key::SessionKey;
+use crate::error::TokenCaveError;
+
+
+/// Revokes a session key before it expires, or cleans up an expired one,
+/// refunding its rent. This works even after the cave is closed.
+pub fn handler(
+    _ctx: Context<RevokeSessionKey>,
+) -> Result<()> {
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct RevokeSessionKey<'info> {
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"session".as_ref(), session.cave_info.as_ref(), session.key.as_ref()],
+        bump = session.bump,
+        has_one = owner @ TokenCaveError::Unauthorized,
+    )]
+    pub session: Account<'info, SessionKey>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
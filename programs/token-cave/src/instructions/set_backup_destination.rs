@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+use crate::error::TokenCaveError;
+
+use super::initialize::CaveInfo;
+
+/// Registers the token account aborts send funds to, instead of the
+/// backup's associated token account. For stake caves this is the new
+/// stake authority instead. Only the backup can do this, so a stolen
+/// depositor key can't redirect a rescue.
+pub fn handler(
+    ctx: Context<SetBackupDestination>,
+    destination: Option<Pubkey>,
+) -> Result<()> {
+
+    // Check that this is the backup
+    require!(
+        ctx.accounts.cave_info.is_backup(&ctx.accounts.backup),
+        TokenCaveError::Unauthorized,
+    );
+
+    ctx.accounts.cave_info.backup_destination = destination;
+
+    Ok(())
+}
+
+
+
+#[derive(Accounts)]
+pub struct SetBackupDestination<'info> {
+
+    /// This PDA stores the information about the associated cave
+    #[account(mut)]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    pub backup: Signer<'info>,
+
+}
@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use crate::error::TokenCaveError;
+
+use super::initialize::CaveInfo;
+
+/// Sets or clears the cave's duress key, given as
+/// `duress_commitment(duress_key, salt)` for a secret 32 byte salt, so that
+/// the account doesn't reveal which key it is. An unlock signed by the
+/// duress key, passing the salt, looks like a regular one, but on withdraw
+/// the funds go to the backup, or if there is none, only after
+/// `DURESS_TIMELOCK_MULTIPLIER` times the timelock.
+pub fn handler(
+    ctx: Context<SetDuressKey>,
+    duress_commitment: Option<[u8; 32]>,
+) -> Result<()> {
+
+    // Check that this is the depositor
+    require_keys_eq!(
+        ctx.accounts.cave_info.depositor,
+        ctx.accounts.depositor.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Don't let an ongoing unlock change its duress status
+    let now = ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?);
+    require!(
+        !ctx.accounts.cave_info.is_unlocking(now),
+        TokenCaveError::CaveIsUnlocking,
+    );
+
+    ctx.accounts.cave_info.duress_commitment = duress_commitment;
+
+    Ok(())
+}
+
+/// Salted hash identifying a duress key
+pub fn duress_commitment(key: &Pubkey, salt: &[u8; 32]) -> [u8; 32] {
+    hashv(&[key.as_ref(), salt]).to_bytes()
+}
+
+
+
+#[derive(Accounts)]
+pub struct SetDuressKey<'info> {
+
+    /// This PDA stores the information about the associated cave
+    #[account(mut)]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+}
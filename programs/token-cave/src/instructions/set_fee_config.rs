@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+
+use crate::error::TokenCaveError;
+use crate::fee::FeeSchedule;
+use super::initialize_config::ProgramConfig;
+
+
+/// Sets the treasury and the default fee schedule
+pub fn handler(
+    ctx: Context<SetFeeConfig>,
+    treasury: Pubkey,
+    fee: FeeSchedule,
+) -> Result<()> {
+
+    // Check the fee is within the protocol maximum
+    fee.validate()?;
+
+    ctx.accounts.config.treasury = treasury;
+    ctx.accounts.config.fee = fee;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct SetFeeConfig<'info> {
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+        has_one = authority @ TokenCaveError::Unauthorized,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    pub authority: Signer<'info>,
+}
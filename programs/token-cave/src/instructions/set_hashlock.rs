@@ -0,0 +1,88 @@
+use anchor_lang::prelude::*;
+use super::initialize::{CaveInfo, CaveKind, Hashlock};
+use crate::error::TokenCaveError;
+
+/// Time the recipient has at least to claim between the hashlock arming and
+/// its deadline. It also absorbs the error of converting slot and epoch
+/// timelocks to seconds.
+pub const HASHLOCK_CLAIM_MARGIN: i64 = 5 * 60;
+
+
+/// Puts the cave in HTLC mode for an atomic swap. Until `deadline` (unix
+/// timestamp) `recipient` can claim all funds with the preimage of `hash`,
+/// and the cave can't be unlocked. Afterwards the depositor unlocks and
+/// withdraws as usual. Claims only open one full timelock after the
+/// hashlock is set, during which the backup can abort, so a stolen
+/// depositor key can't use a hashlock to skip the timelock.
+pub fn handler(
+    ctx: Context<SetHashlock>,
+    hash: [u8; 32],
+    recipient: Pubkey,
+    deadline: i64,
+) -> Result<()> {
+
+    // Check that this is the depositor
+    require_keys_eq!(
+        ctx.accounts.cave_info.depositor,
+        ctx.accounts.depositor.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    // The counterparty relies on the hashlock, so it can't be replaced
+    // before its deadline
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        !ctx.accounts.cave_info.hashlock_active(now),
+        TokenCaveError::HashlockUnavailable,
+    );
+
+    // All funds must be claimable: no unlock, tickets or strategy
+    let unlock_now = ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?);
+    require!(
+        !ctx.accounts.cave_info.is_unlocking(unlock_now)
+        && ctx.accounts.cave_info.open_tickets == 0
+        && ctx.accounts.cave_info.strategy.is_none(),
+        TokenCaveError::HashlockUnavailable,
+    );
+
+    // Check that the recipient can claim before the deadline. Claims open
+    // one timelock from now, which the deadline, in unix seconds, must
+    // outlast by the margin.
+    let arming_seconds = ctx.accounts.cave_info.duration_seconds(
+        ctx.accounts.cave_info.timelock_duration,
+    )?;
+    require_gte!(
+        deadline,
+        now
+            .checked_add(i64::try_from(arming_seconds).unwrap())
+            .unwrap()
+            .checked_add(HASHLOCK_CLAIM_MARGIN)
+            .unwrap(),
+        TokenCaveError::HashlockDeadlineTooSoon,
+    );
+
+    ctx.accounts.cave_info.hashlock = Some(Hashlock {
+        hash,
+        recipient,
+        deadline,
+        armed_at: unlock_now
+            .checked_add(ctx.accounts.cave_info.timelock_duration.into())
+            .unwrap(),
+    });
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct SetHashlock<'info> {
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        constraint = cave_info.kind == CaveKind::Token,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    pub depositor: Signer<'info>,
+}
@@ -0,0 +1,69 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Mint;
+
+use crate::error::TokenCaveError;
+use crate::fee::{FeeSchedule, FEE_SCHEDULE_SIZE};
+use super::initialize_config::ProgramConfig;
+
+pub const MINT_FEE_OVERRIDE_SIZE: usize = 8 + 32 + FEE_SCHEDULE_SIZE + 1;
+
+
+/// Creates or updates the fee schedule for a single mint, which replaces
+/// the default schedule for caves of that mint
+pub fn handler(
+    ctx: Context<SetMintFee>,
+    fee: FeeSchedule,
+) -> Result<()> {
+
+    // Check the fee is within the protocol maximum
+    fee.validate()?;
+
+    ctx.accounts.fee_override.mint = ctx.accounts.mint.key();
+    ctx.accounts.fee_override.fee = fee;
+    ctx.accounts.fee_override.bump = *ctx.bumps.get("fee_override").unwrap();
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct SetMintFee<'info> {
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"fee".as_ref(), mint.key().as_ref()],
+        space = MINT_FEE_OVERRIDE_SIZE,
+        bump,
+    )]
+    pub fee_override: Account<'info, MintFeeOverride>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+        has_one = authority @ TokenCaveError::Unauthorized,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account()]
+    pub mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Fee schedule of a single mint
+#[account]
+pub struct MintFeeOverride {
+
+    /// Mint this override applies to
+    pub mint: Pubkey,
+
+    /// Replaces the config's default schedule
+    pub fee: FeeSchedule,
+
+    /// Bump of this PDA
+    pub bump: u8,
+}
@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+
+use crate::error::TokenCaveError;
+use super::initialize_config::{ProgramConfig, MAX_PAUSE_DURATION, PAUSE_COOLDOWN};
+
+
+/// Pauses or unpauses the program. A new pause can only start once the
+/// previous one has expired and the cooldown has passed, so repeated
+/// pauses can't lock funds forever.
+pub fn handler(
+    ctx: Context<SetPause>,
+    paused: bool,
+) -> Result<()> {
+
+    if paused {
+        let now = Clock::get()?.unix_timestamp;
+        require_gt!(
+            now,
+            ctx.accounts.config.paused_at
+                .saturating_add(MAX_PAUSE_DURATION)
+                .saturating_add(PAUSE_COOLDOWN),
+            TokenCaveError::PauseCooldown,
+        );
+        ctx.accounts.config.paused_at = now;
+    }
+    ctx.accounts.config.paused = paused;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct SetPause<'info> {
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+        has_one = pause_authority @ TokenCaveError::Unauthorized,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    pub pause_authority: Signer<'info>,
+}
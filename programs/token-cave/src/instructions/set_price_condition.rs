@@ -0,0 +1,63 @@
+use anchor_lang::prelude::*;
+use super::initialize::{CaveInfo, CaveKind, PriceCondition, PriceTrigger};
+use crate::error::TokenCaveError;
+use crate::oracle;
+
+/// Lets the cave release early once `feed` crosses `threshold`, in the
+/// feed's fixed-point units. The condition only counts after a full
+/// timelock, during which the backup can abort, so a stolen depositor key
+/// can't use it to skip the timelock. It can't be changed once set.
+pub fn handler(
+    ctx: Context<SetPriceCondition>,
+    trigger: PriceTrigger,
+    threshold: i64,
+) -> Result<()> {
+
+    // Check that this is the depositor
+    require_keys_eq!(
+        ctx.accounts.cave_info.depositor,
+        ctx.accounts.depositor.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Check that the cave is locked and has no condition yet
+    let clock = Clock::get()?;
+    let now = ctx.accounts.cave_info.timelock_unit.now(&clock);
+    require!(
+        ctx.accounts.cave_info.price_condition.is_none()
+        && !ctx.accounts.cave_info.is_unlocking(now),
+        TokenCaveError::PriceConditionUnavailable,
+    );
+
+    // Check that the feed can be read
+    let price = oracle::read_price(&ctx.accounts.feed, clock.unix_timestamp)?;
+
+    ctx.accounts.cave_info.price_condition = Some(PriceCondition {
+        feed: ctx.accounts.feed.key(),
+        trigger,
+        threshold,
+        expo: price.expo,
+        armed_at: now
+            .checked_add(ctx.accounts.cave_info.timelock_duration.into())
+            .unwrap(),
+    });
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct SetPriceCondition<'info> {
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        constraint = cave_info.kind == CaveKind::Token,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    pub depositor: Signer<'info>,
+
+    /// CHECK: read by `oracle::read_price`, which checks the owner
+    pub feed: UncheckedAccount<'info>,
+}
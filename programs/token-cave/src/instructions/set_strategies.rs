@@ -0,0 +1,39 @@
+use anchor_lang::prelude::*;
+
+use crate::error::TokenCaveError;
+use super::initialize_config::{ProgramConfig, MAX_STRATEGIES};
+
+
+/// Replaces the whitelist of strategy programs caves can deposit into.
+/// Caves already in a strategy that is removed can still redeem from it.
+pub fn handler(
+    ctx: Context<SetStrategies>,
+    strategies: Vec<Pubkey>,
+) -> Result<()> {
+
+    // Check that the whitelist fits in the config
+    require_gte!(
+        MAX_STRATEGIES,
+        strategies.len(),
+        TokenCaveError::TooManyStrategies,
+    );
+
+    ctx.accounts.config.strategies = strategies;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct SetStrategies<'info> {
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+        has_one = authority @ TokenCaveError::Unauthorized,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    pub authority: Signer<'info>,
+}
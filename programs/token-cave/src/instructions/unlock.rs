@@ -1,30 +1,58 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{TokenAccount, Mint};
 use crate::error::TokenCaveError;
 
 use super::initialize::CaveInfo;
+use super::initialize_config::ProgramConfig;
+use super::register_session_key::{authorize_session, SCOPE_UNLOCK};
+use super::set_duress_key::duress_commitment;
 
-pub fn handler(
-    ctx: Context<Unlock>,
+/// Starts the timelock. The duress key passes its salt as `duress_salt`;
+/// other signers pass None.
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Unlock<'info>>,
+    duress_salt: Option<[u8; 32]>,
 ) -> Result<()> {
 
-    // Check that this is the depositor
-    require_keys_eq!(
-        ctx.accounts.cave_info.depositor,
-        ctx.accounts.depositor.key(),
-        TokenCaveError::Unauthorized,
+    // Check that the program is not paused
+    ctx.accounts.config.check_not_paused(Clock::get()?.unix_timestamp)?;
+
+    // Check that this is the owner (the depositor, or a gift's
+    // beneficiary), the owner using their duress key, or a session key of
+    // the owner passed as the first remaining account
+    let signer = ctx.accounts.depositor.key();
+    let unlock_proof = duress_commitment(&signer, &duress_salt.unwrap_or_default());
+    let duress = ctx.accounts.cave_info.duress_commitment == Some(unlock_proof);
+    if duress_salt.is_some() {
+        require!(duress, TokenCaveError::InvalidDuressKey);
+    }
+    if ctx.accounts.cave_info.owner() != signer && !duress {
+        authorize_session(
+            ctx.remaining_accounts,
+            &ctx.accounts.cave_info.key(),
+            &signer,
+            SCOPE_UNLOCK,
+            0,
+        )?;
+    }
+
+    // Check that no hashlock holds the funds for a swap
+    require!(
+        !ctx.accounts.cave_info.hashlock_active(Clock::get()?.unix_timestamp),
+        TokenCaveError::HashlockActive,
     );
 
-    // Check unlock is not already active
-    require_keys_eq!(
-        ctx.accounts.cave_info.depositor,
-        ctx.accounts.depositor.key(),
+    // Check unlock is not already active. An unlock whose withdraw window
+    // has lapsed can be restarted, which requires the full wait again.
+    let now = ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?);
+    require!(
+        !ctx.accounts.cave_info.is_unlocking(now),
         TokenCaveError::UnlockAlreadyActive,
     );
 
     // Initialize unlock
-    ctx.accounts.cave_info.unlock_request_time = Clock::get()?.unix_timestamp;
+    ctx.accounts.cave_info.unlock_request_time = now;
     ctx.accounts.cave_info.unlocking = true;
+    ctx.accounts.cave_info.unlock_proof = unlock_proof;
 
     Ok(())
 }
@@ -34,36 +62,22 @@ pub fn handler(
 #[derive(Accounts)]
 pub struct Unlock<'info> {
 
-    /// The token cave! A program-owned spl token account
-    /// which supports deposits and time-locked withdraws.
-    /// The time-locked withdraw can be aborted, which sends
-    /// the tokens to the specified backup address
-    #[account(
-        seeds = [&depositor_token_account.key().to_bytes()],
-        bump,
-        token::mint = mint,
-        token::authority = cave_info,
-    )]
-    pub cave: Account<'info, TokenAccount>,
-
-    /// This PDA stores the information about the associated cave
-    #[account(
-        mut,
-        seeds = [&cave.key().to_bytes()],
-        bump,
-    )]
+    /// This PDA stores the information about the associated cave.
+    /// Unlocking only touches the cave info, so this works for every
+    /// kind of cave.
+    #[account(mut)]
     pub cave_info: Account<'info, CaveInfo>,
 
-    #[account()]
-    pub mint: Account<'info, Mint>,
-
+    /// The depositor, a gift's beneficiary, the cave's duress key, or a
+    /// session key
     #[account(mut)]
     pub depositor: Signer<'info>,
 
-    /// NOTE: this has no additional checks because the spl transfer
-    /// instruction requires `depositor` to have authority over funds
-    /// inside of this token account.
-    #[account(mut)]
-    pub depositor_token_account: Account<'info, TokenAccount>,
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
 
 }
\ No newline at end of file
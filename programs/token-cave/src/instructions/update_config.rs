@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+use crate::error::TokenCaveError;
+use super::initialize_config::ProgramConfig;
+
+
+/// Changes the config authority, pause authority and pause policy
+pub fn handler(
+    ctx: Context<UpdateConfig>,
+    new_authority: Pubkey,
+    pause_authority: Pubkey,
+    withdraw_allowed_while_paused: bool,
+) -> Result<()> {
+
+    ctx.accounts.config.authority = new_authority;
+    ctx.accounts.config.pause_authority = pause_authority;
+    ctx.accounts.config.withdraw_allowed_while_paused = withdraw_allowed_while_paused;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+        has_one = authority @ TokenCaveError::Unauthorized,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    pub authority: Signer<'info>,
+}
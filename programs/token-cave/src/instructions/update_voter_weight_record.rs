@@ -0,0 +1,68 @@
+use anchor_lang::prelude::*;
+use super::initialize::CaveInfo;
+use super::create_voter_weight_record::VoterWeightRecord;
+use crate::error::TokenCaveError;
+
+/// Sets the voter weight to the summed voting power of the cave infos
+/// passed in as remaining accounts. The weight is only valid in the
+/// current slot, so this should be called in the same transaction as the
+/// governance instruction that consumes it.
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdateVoterWeightRecord<'info>>,
+) -> Result<()> {
+
+    let record = &ctx.accounts.voter_weight_record;
+    let clock = Clock::get()?;
+
+    let mut voter_weight: u64 = 0;
+    for (i, cave_info_account) in ctx.remaining_accounts.iter().enumerate() {
+
+        // Each cave may only be counted once
+        require!(
+            ctx.remaining_accounts[..i]
+                .iter()
+                .all(|other| other.key() != cave_info_account.key()),
+            TokenCaveError::DuplicateCave,
+        );
+
+        // Checks owner and discriminator. Gifts count for the beneficiary
+        // once they can't be clawed back.
+        let cave_info: Account<CaveInfo> = Account::try_from(cave_info_account)?;
+        require!(
+            cave_info.holder(clock.unix_timestamp) == Some(record.governing_token_owner),
+            TokenCaveError::InvalidVoterWeightCave,
+        );
+        require_keys_eq!(
+            cave_info.mint,
+            record.governing_token_mint,
+            TokenCaveError::InvalidVoterWeightCave,
+        );
+
+        voter_weight = voter_weight
+            .checked_add(cave_info.voting_power(&clock))
+            .unwrap();
+    }
+
+    ctx.accounts.voter_weight_record.voter_weight = voter_weight;
+    ctx.accounts.voter_weight_record.voter_weight_expiry = Some(clock.slot);
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct UpdateVoterWeightRecord<'info> {
+
+    /// SPL-Governance voter weight record for the owner's caves
+    #[account(
+        mut,
+        seeds = [
+            b"voter-weight-record".as_ref(),
+            voter_weight_record.realm.as_ref(),
+            voter_weight_record.governing_token_mint.as_ref(),
+            voter_weight_record.governing_token_owner.as_ref(),
+        ],
+        bump,
+    )]
+    pub voter_weight_record: Account<'info, VoterWeightRecord>,
+}
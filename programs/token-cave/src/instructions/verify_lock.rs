@@ -0,0 +1,167 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::set_return_data;
+use anchor_spl::token::{TokenAccount, Mint};
+use super::initialize::{CaveInfo, TimelockUnit};
+use crate::error::TokenCaveError;
+
+/// Read-only check that `depositor` has at least `min_amount` tokens of
+/// `mint` locked behind a timelock of at least `min_lock_duration` seconds.
+/// For gift caves `depositor` is the beneficiary, once the gift can no
+/// longer be clawed back.
+///
+/// On success a borsh-serialized [`LockProof`] is set as return data.
+pub fn handler(
+    ctx: Context<VerifyLock>,
+    min_amount: u64,
+    min_lock_duration: u32,
+) -> Result<()> {
+
+    // Check that the funds count for this depositor
+    let clock = Clock::get()?;
+    require!(
+        ctx.accounts.cave_info.holder(clock.unix_timestamp) == Some(ctx.accounts.depositor.key()),
+        TokenCaveError::Unauthorized,
+    );
+
+    // A cave that is unlocking can be emptied once its timelock is up, and
+    // one with an active hashlock or an armed price condition at any time,
+    // so none of them counts as locked
+    let locked_duration = ctx.accounts.cave_info.locked_duration(&clock)
+        .ok_or(TokenCaveError::CaveIsUnlocking)?;
+
+    // Check locked amount, counting principal deployed to a strategy but
+    // not funds reserved by pending withdraw tickets
+    let locked_amount = ctx.accounts.cave.amount
+        .checked_add(ctx.accounts.cave_info.strategy_deposited)
+        .unwrap()
+        .saturating_sub(ctx.accounts.cave_info.ticketed_amount);
+    require_gte!(
+        locked_amount,
+        min_amount,
+        TokenCaveError::InsufficientLockedAmount,
+    );
+
+    // The funds cannot leave for at least the locked duration
+    let timelock_duration_seconds = ctx.accounts.cave_info.duration_seconds(locked_duration)?;
+    require_gte!(
+        timelock_duration_seconds,
+        u64::from(min_lock_duration),
+        TokenCaveError::InsufficientLockDuration,
+    );
+
+    let proof = LockProof {
+        depositor: ctx.accounts.depositor.key(),
+        mint: ctx.accounts.cave_info.mint,
+        cave: ctx.accounts.cave_info.cave,
+        amount: locked_amount,
+        timelock_duration: locked_duration,
+        timelock_unit: ctx.accounts.cave_info.timelock_unit,
+        timelock_duration_seconds,
+        locked_since: ctx.accounts.cave_info.created_at,
+    };
+    set_return_data(&proof.try_to_vec()?);
+
+    Ok(())
+}
+
+
+/// Verifies a lock through CPI and returns the resulting [`LockProof`].
+///
+/// This is the intended entrypoint for other programs, e.g.
+///
+/// ```ignore
+/// let proof = token_cave::instructions::verify_lock::verify_lock_cpi(
+///     CpiContext::new(
+///         ctx.accounts.token_cave_program.to_account_info(),
+///         token_cave::cpi::accounts::VerifyLock {
+///             cave: ctx.accounts.cave.to_account_info(),
+///             cave_info: ctx.accounts.cave_info.to_account_info(),
+///             mint: ctx.accounts.mint.to_account_info(),
+///             depositor: ctx.accounts.voter.to_account_info(),
+///         },
+///     ),
+///     1_000 * ONE_TOKEN,
+///     3 * 24 * 60 * 60,
+/// )?;
+/// ```
+///
+/// The CPI fails if the lock does not satisfy the requirements, so callers
+/// only need to inspect the proof if they want more than a yes/no answer.
+#[cfg(feature = "cpi")]
+pub fn verify_lock_cpi<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, crate::cpi::accounts::VerifyLock<'info>>,
+    min_amount: u64,
+    min_lock_duration: u32,
+) -> Result<LockProof> {
+
+    crate::cpi::verify_lock(ctx, min_amount, min_lock_duration)?;
+
+    // Return data could in principle have been overwritten by another
+    // program, so make sure it came from us
+    let (program_id, data) = anchor_lang::solana_program::program::get_return_data()
+        .ok_or(TokenCaveError::MissingLockProof)?;
+    require_keys_eq!(
+        program_id,
+        crate::ID,
+        TokenCaveError::MissingLockProof,
+    );
+
+    LockProof::try_from_slice(&data)
+        .map_err(|_| error!(TokenCaveError::MissingLockProof))
+}
+
+
+#[derive(Accounts)]
+pub struct VerifyLock<'info> {
+
+    /// The token cave whose balance is being verified
+    #[account(
+        address = cave_info.cave,
+    )]
+    pub cave: Account<'info, TokenAccount>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        seeds = [&cave.key().to_bytes()],
+        bump = cave_info.info_bump,
+        has_one = mint,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account()]
+    pub mint: Account<'info, Mint>,
+
+    /// CHECK: only compared against `CaveInfo::holder`; it does not need
+    /// to sign since this instruction is read-only.
+    #[account()]
+    pub depositor: UncheckedAccount<'info>,
+}
+
+/// Return data of `verify_lock`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LockProof {
+
+    /// Owner of the locked funds, the beneficiary for gift caves
+    pub depositor: Pubkey,
+
+    /// Mint of the locked funds
+    pub mint: Pubkey,
+
+    /// The cave holding the funds
+    pub cave: Pubkey,
+
+    /// Amount currently locked
+    pub amount: u64,
+
+    /// Minimum time the funds stay locked, in `timelock_unit`
+    pub timelock_duration: u32,
+
+    /// Unit of `timelock_duration`
+    pub timelock_unit: TimelockUnit,
+
+    /// `timelock_duration` in (approximate, for slots and epochs) seconds
+    pub timelock_duration_seconds: u64,
+
+    /// Unix timestamp at which the cave was created
+    pub locked_since: i64,
+}
@@ -1,12 +1,20 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{TokenAccount, Token, Mint};
-use super::initialize::CaveInfo;
+use super::initialize::{CaveInfo, CaveKind};
+use super::abort::backup_spl_account_address;
+use super::initialize_config::ProgramConfig;
 use crate::error::TokenCaveError;
+use crate::strategy;
+use crate::fee::{self, FeeTiming};
+use crate::registry;
 
-pub fn handler(
-    ctx: Context<Withdraw>,
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>,
 ) -> Result<()> {
 
+    // Check that a pause does not block withdraws
+    ctx.accounts.config.check_withdraw_allowed(Clock::get()?.unix_timestamp)?;
+
     // Check that this is the depositor
     require_keys_eq!(
         ctx.accounts.cave_info.depositor,
@@ -14,22 +22,87 @@ pub fn handler(
         TokenCaveError::Unauthorized,
     );
     
-    // Check that the user has requested an unlock
-    require!(
-        ctx.accounts.cave_info.unlocking,
-        TokenCaveError::DidNotRequestUnlock,
-    );
-
-    // Check that the timelock is up
-    let earliest_withdraw_time = ctx.accounts.cave_info.unlock_request_time
-        .checked_add(ctx.accounts.cave_info.timelock_duration.into())
-        .unwrap();
-    require_gt!(
-        Clock::get()?.unix_timestamp,
-        earliest_withdraw_time,
-        TokenCaveError::LockIsActive,
+    // Check that the unlock has matured and not lapsed
+    let now = ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?);
+    ctx.accounts.cave_info.check_withdrawable(now)?;
+
+    // Closing the cave would strand pending withdraw tickets
+    require_eq!(
+        ctx.accounts.cave_info.open_tickets,
+        0,
+        TokenCaveError::OutstandingWithdrawTickets,
     );
 
+    // A duress unlock sends the funds to the backup, like an abort. The
+    // backup's token account is then the first remaining account, and
+    // must be the one an abort would send the funds to. It must exist
+    // already, since withdraws need no signer to pay for creating it.
+    // NOTE: the transfer to the backup is public, so anyone watching the
+    // chain, including whoever forced the unlock, can tell that the
+    // withdraw was a duress withdraw once it happens.
+    let (recipient, remaining_accounts) = if ctx.accounts.cave_info.duress_routes_to_backup() {
+        let (backup_spl_account, remaining_accounts) = ctx.remaining_accounts
+            .split_first()
+            .ok_or(TokenCaveError::IncorrectBackupTokenAccount)?;
+        require_keys_eq!(
+            backup_spl_account.key(),
+            backup_spl_account_address(
+                &ctx.accounts.cave_info,
+                &ctx.accounts.cave_info.backup_address.unwrap(),
+            ),
+            TokenCaveError::IncorrectBackupTokenAccount,
+        );
+        (backup_spl_account.clone(), remaining_accounts)
+    } else {
+        (ctx.accounts.depositor_token_account.to_account_info(), ctx.remaining_accounts)
+    };
+
+    // Bring back funds deployed to a yield strategy, along with the yield.
+    // The strategy program and its accounts are the remaining accounts.
+    if let Some(strategy) = ctx.accounts.cave_info.strategy {
+        let (strategy_program, strategy_accounts) = strategy::split_remaining_accounts(
+            strategy,
+            remaining_accounts,
+        )?;
+        strategy::redeem(
+            strategy_program,
+            &ctx.accounts.cave_info.to_account_info(),
+            &ctx.accounts.cave.to_account_info(),
+            &ctx.accounts.depositor.to_account_info(),
+            strategy_accounts,
+            &[&[ctx.accounts.cave.key().as_ref(), &[ctx.accounts.cave_info.info_bump]]],
+        )?;
+        ctx.accounts.cave.reload()?;
+    }
+
+    // Take the protocol fee out of the payout. Funds going to the backup
+    // are a rescue, which is always free.
+    let fee = if ctx.accounts.cave_info.duress_routes_to_backup() {
+        0
+    } else {
+        fee::fee_due(
+            &ctx.accounts.config,
+            &ctx.accounts.cave_info.fee,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.mint.key(),
+            FeeTiming::Withdraw,
+            ctx.accounts.cave.amount,
+        )?
+    };
+    if fee > 0 {
+        anchor_spl::token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.cave.to_account_info(),
+                    to: ctx.accounts.treasury_token_account.to_account_info(),
+                    authority: ctx.accounts.cave_info.to_account_info(),
+                },
+                &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
+            ),
+            fee,
+        )?;
+    }
 
     // Withdraw spl token from the token cave
     anchor_spl::token::transfer(
@@ -37,12 +110,12 @@ pub fn handler(
             ctx.accounts.token_program.to_account_info(),
             anchor_spl::token::Transfer {
                 from: ctx.accounts.cave.to_account_info(),
-                to: ctx.accounts.depositor_token_account.to_account_info(),
+                to: recipient,
                 authority: ctx.accounts.cave_info.to_account_info(),
             },
-            &[&[&ctx.accounts.cave.key().to_bytes(), &[*ctx.bumps.get("cave_info").unwrap()]]]
+            &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
         ),
-        ctx.accounts.cave.amount,
+        ctx.accounts.cave.amount.checked_sub(fee).unwrap(),
     )?;
 
     anchor_spl::token::close_account(
@@ -50,13 +123,15 @@ pub fn handler(
             ctx.accounts.token_program.to_account_info(),
             anchor_spl::token::CloseAccount {
                 account: ctx.accounts.cave.to_account_info(),
-                destination: ctx.accounts.depositor_token_account.to_account_info(),
+                destination: ctx.accounts.rent_payer.to_account_info(),
                 authority: ctx.accounts.cave_info.to_account_info(),
             },
-            &[&[&ctx.accounts.cave.key().to_bytes(), &[*ctx.bumps.get("cave_info").unwrap()]]]
+            &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
         ),
     )?;
 
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
 
     Ok(())
 }
@@ -72,7 +147,7 @@ pub struct Withdraw<'info> {
     #[account(
         mut,
         seeds = [&depositor_token_account.key().to_bytes()],
-        bump,
+        bump = cave_info.cave_bump,
         token::mint = mint,
         token::authority = cave_info,
     )]
@@ -81,9 +156,12 @@ pub struct Withdraw<'info> {
     /// This PDA stores the information about the associated cave
     #[account(
         mut,
-        close = depositor,
+        close = rent_payer,
         seeds = [&cave.key().to_bytes()],
-        bump,
+        bump = cave_info.info_bump,
+        has_one = mint,
+        has_one = rent_payer,
+        constraint = cave_info.kind == CaveKind::Token,
     )]
     pub cave_info: Account<'info, CaveInfo>,
 
@@ -93,6 +171,10 @@ pub struct Withdraw<'info> {
     #[account(mut)]
     pub depositor: AccountInfo<'info>,
 
+    /// CHECK: gets the rent back, checked against `cave_info.rent_payer`
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+
     /// NOTE: this has no additional checks because the spl transfer
     /// instruction requires `depositor` to have authority over funds
     /// inside of this token account.
@@ -101,4 +183,24 @@ pub struct Withdraw<'info> {
 
     pub token_program: Program<'info, Token>,
 
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: receives the fee. Checked by `fee::fee_due` when one is owed.
+    #[account(mut)]
+    pub treasury_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+
 }
\ No newline at end of file
@@ -0,0 +1,175 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{TokenAccount, Token};
+use super::initialize::{CaveInfo, CaveKind};
+use super::initialize_config::ProgramConfig;
+use crate::error::TokenCaveError;
+use crate::registry;
+
+/// Sweeps every mint of the basket to the depositor. The remaining
+/// accounts are `[basket_cave, depositor_token_account]` pairs, one for
+/// each mint in the basket.
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, WithdrawBasket<'info>>,
+) -> Result<()> {
+
+    // Check that a pause does not block withdraws
+    ctx.accounts.config.check_withdraw_allowed(Clock::get()?.unix_timestamp)?;
+
+    // Check that this is the depositor
+    require_keys_eq!(
+        ctx.accounts.cave_info.depositor,
+        ctx.accounts.depositor.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Check that the unlock has matured and not lapsed
+    let now = ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?);
+    ctx.accounts.cave_info.check_withdrawable(now)?;
+
+    check_basket_complete(&ctx.accounts.cave_info, ctx.remaining_accounts, 2)?;
+    for accounts in ctx.remaining_accounts.chunks(2) {
+        let basket_cave = load_basket_cave(&ctx.accounts.cave_info, &accounts[0])?;
+        let destination: Account<TokenAccount> = Account::try_from(&accounts[1])?;
+
+        // Check that the destination belongs to the depositor
+        require_keys_eq!(
+            destination.owner,
+            ctx.accounts.depositor.key(),
+            TokenCaveError::IncorrectDestinationTokenAccount,
+        );
+        require_keys_eq!(
+            destination.mint,
+            basket_cave.mint,
+            TokenCaveError::IncorrectDestinationTokenAccount,
+        );
+
+        sweep_basket_cave(
+            &ctx.accounts.cave_info,
+            &basket_cave,
+            &accounts[1],
+            &ctx.accounts.rent_payer,
+            &ctx.accounts.token_program.to_account_info(),
+        )?;
+    }
+
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
+
+    Ok(())
+}
+
+/// Checks that the remaining accounts hold a group of `group_size`
+/// accounts for every mint of the basket. Every mint must be swept,
+/// otherwise it would be stranded once the cave info is closed.
+pub fn check_basket_complete(
+    cave_info: &CaveInfo,
+    remaining_accounts: &[AccountInfo],
+    group_size: usize,
+) -> Result<()> {
+    require!(
+        remaining_accounts.len() % group_size == 0
+        && remaining_accounts.len() / group_size == cave_info.basket_size as usize,
+        TokenCaveError::IncompleteBasket,
+    );
+    Ok(())
+}
+
+/// Loads a token account of the basket, checking that it is the basket's
+/// token account for its mint. Being a PDA of the cave info also rules out
+/// supplying one twice, since it is closed after the first sweep.
+pub fn load_basket_cave<'info>(
+    cave_info: &Account<'info, CaveInfo>,
+    account: &AccountInfo<'info>,
+) -> Result<Account<'info, TokenAccount>> {
+
+    let basket_cave: Account<TokenAccount> = Account::try_from(account)?;
+    let (expected_basket_cave, _) = Pubkey::find_program_address(
+        &[cave_info.key().as_ref(), basket_cave.mint.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(
+        basket_cave.key(),
+        expected_basket_cave,
+        TokenCaveError::IncompleteBasket,
+    );
+    Ok(basket_cave)
+}
+
+/// Moves the full balance of a basket token account to `destination` and
+/// closes the basket token account
+pub fn sweep_basket_cave<'info>(
+    cave_info: &Account<'info, CaveInfo>,
+    basket_cave: &Account<'info, TokenAccount>,
+    destination: &AccountInfo<'info>,
+    rent_receiver: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+) -> Result<()> {
+
+    let signer_seeds: &[&[&[u8]]] = &[&[b"basket".as_ref(), cave_info.cave.as_ref(), &[cave_info.info_bump]]];
+    anchor_spl::token::transfer(
+        CpiContext::new_with_signer(
+            token_program.clone(),
+            anchor_spl::token::Transfer {
+                from: basket_cave.to_account_info(),
+                to: destination.clone(),
+                authority: cave_info.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        basket_cave.amount,
+    )?;
+
+    anchor_spl::token::close_account(
+        CpiContext::new_with_signer(
+            token_program.clone(),
+            anchor_spl::token::CloseAccount {
+                account: basket_cave.to_account_info(),
+                destination: rent_receiver.clone(),
+                authority: cave_info.to_account_info(),
+            },
+            signer_seeds,
+        ),
+    )
+}
+
+
+#[derive(Accounts)]
+pub struct WithdrawBasket<'info> {
+
+    /// This PDA stores the information about the basket
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [b"basket".as_ref(), cave_info.cave.as_ref()],
+        bump = cave_info.info_bump,
+        has_one = rent_payer,
+        constraint = cave_info.kind == CaveKind::Basket,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    /// CHECK: checked against the cave info in the handler
+    #[account(mut)]
+    pub depositor: AccountInfo<'info>,
+
+    /// CHECK: gets the rent back, checked against `cave_info.rent_payer`
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
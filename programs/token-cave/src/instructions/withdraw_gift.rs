@@ -0,0 +1,165 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{TokenAccount, Token, Mint};
+use super::initialize::{CaveInfo, CaveKind};
+use super::initialize_config::ProgramConfig;
+use crate::error::TokenCaveError;
+use crate::fee::{self, FeeTiming};
+use crate::ata;
+use crate::registry;
+
+/// Sends an unlocked gift to the beneficiary's associated token account,
+/// creating it if needed
+pub fn handler(
+    ctx: Context<WithdrawGift>,
+) -> Result<()> {
+
+    // Check that a pause does not block withdraws
+    ctx.accounts.config.check_withdraw_allowed(Clock::get()?.unix_timestamp)?;
+
+    // Check that this is the beneficiary
+    require_keys_eq!(
+        ctx.accounts.cave_info.owner(),
+        ctx.accounts.beneficiary.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Check that the unlock has matured and not lapsed
+    let now = ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?);
+    ctx.accounts.cave_info.check_withdrawable(now)?;
+
+    // Check the token account the gift goes to, creating it if needed
+    ata::create_if_missing(
+        &ctx.accounts.beneficiary_token_account,
+        &ctx.accounts.beneficiary,
+        &ctx.accounts.mint.to_account_info(),
+        &ctx.accounts.beneficiary,
+        &ctx.accounts.system_program,
+        &ctx.accounts.token_program,
+        &ctx.accounts.associated_token_program,
+        &ctx.accounts.rent.to_account_info(),
+    )?;
+
+    // Take the protocol fee out of the payout
+    let fee = fee::fee_due(
+        &ctx.accounts.config,
+        &ctx.accounts.cave_info.fee,
+        &ctx.accounts.treasury_token_account,
+        &ctx.accounts.mint.key(),
+        FeeTiming::Withdraw,
+        ctx.accounts.cave.amount,
+    )?;
+    if fee > 0 {
+        anchor_spl::token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.cave.to_account_info(),
+                    to: ctx.accounts.treasury_token_account.to_account_info(),
+                    authority: ctx.accounts.cave_info.to_account_info(),
+                },
+                &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
+            ),
+            fee,
+        )?;
+    }
+
+    // Withdraw spl token from the gift cave
+    anchor_spl::token::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::Transfer {
+                from: ctx.accounts.cave.to_account_info(),
+                to: ctx.accounts.beneficiary_token_account.to_account_info(),
+                authority: ctx.accounts.cave_info.to_account_info(),
+            },
+            &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
+        ),
+        ctx.accounts.cave.amount.checked_sub(fee).unwrap(),
+    )?;
+
+    anchor_spl::token::close_account(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::CloseAccount {
+                account: ctx.accounts.cave.to_account_info(),
+                destination: ctx.accounts.rent_payer.to_account_info(),
+                authority: ctx.accounts.cave_info.to_account_info(),
+            },
+            &[&[&ctx.accounts.cave.key().to_bytes(), &[ctx.accounts.cave_info.info_bump]]]
+        ),
+    )?;
+
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct WithdrawGift<'info> {
+
+    /// The gift cave
+    #[account(
+        mut,
+        address = cave_info.cave,
+    )]
+    pub cave: Account<'info, TokenAccount>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [&cave.key().to_bytes()],
+        bump = cave_info.info_bump,
+        has_one = mint,
+        has_one = rent_payer,
+        constraint = cave_info.kind == CaveKind::Gift,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    #[account()]
+    pub mint: Account<'info, Mint>,
+
+    /// Signs, and pays for their token account if it doesn't exist yet
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+
+    /// CHECK: must be the beneficiary's associated token account, checked
+    /// by `ata::create_if_missing`
+    #[account(mut)]
+    pub beneficiary_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: gets the rent back, checked against `cave_info.rent_payer`
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    pub system_program: Program<'info, System>,
+
+    pub rent: Sysvar<'info, Rent>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: receives the fee. Checked by `fee::fee_due` when one is owed.
+    #[account(mut)]
+    pub treasury_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
@@ -0,0 +1,132 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{TokenAccount, Token, Mint};
+use super::initialize::{CaveInfo, CaveKind};
+use super::initialize_pnft::{PnftAccounts, PnftTransfer};
+use super::initialize_config::ProgramConfig;
+use crate::error::TokenCaveError;
+use crate::registry;
+
+pub fn handler(
+    ctx: Context<WithdrawPnft>,
+) -> Result<()> {
+
+    // Check that a pause does not block withdraws
+    ctx.accounts.config.check_withdraw_allowed(Clock::get()?.unix_timestamp)?;
+
+    // Check that this is the depositor
+    require_keys_eq!(
+        ctx.accounts.cave_info.depositor,
+        ctx.accounts.depositor.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Check that the unlock has matured and not lapsed
+    let now = ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?);
+    ctx.accounts.cave_info.check_withdrawable(now)?;
+
+    // Withdraw the pNFT from the token cave
+    let cave_key = ctx.accounts.cave.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[cave_key.as_ref(), &[ctx.accounts.cave_info.info_bump]]];
+    ctx.accounts.pnft.transfer(
+        PnftTransfer {
+            token: ctx.accounts.cave.to_account_info(),
+            token_owner: ctx.accounts.cave_info.to_account_info(),
+            destination: ctx.accounts.depositor_token_account.to_account_info(),
+            destination_owner: ctx.accounts.depositor.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            authority: ctx.accounts.cave_info.to_account_info(),
+            payer: ctx.accounts.payer.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+        signer_seeds,
+    )?;
+
+    // The token metadata program thaws the cave when transferring out of
+    // it, so it can be closed like a regular cave
+    anchor_spl::token::close_account(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::CloseAccount {
+                account: ctx.accounts.cave.to_account_info(),
+                destination: ctx.accounts.rent_payer.to_account_info(),
+                authority: ctx.accounts.cave_info.to_account_info(),
+            },
+            signer_seeds,
+        ),
+    )?;
+
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct WithdrawPnft<'info> {
+
+    /// The token cave holding the pNFT
+    #[account(
+        mut,
+        seeds = [&depositor_token_account.key().to_bytes()],
+        bump = cave_info.cave_bump,
+        token::mint = mint,
+        token::authority = cave_info,
+    )]
+    pub cave: Box<Account<'info, TokenAccount>>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [&cave.key().to_bytes()],
+        bump = cave_info.info_bump,
+        has_one = mint,
+        has_one = rent_payer,
+        constraint = cave_info.kind == CaveKind::ProgrammableNft,
+    )]
+    pub cave_info: Box<Account<'info, CaveInfo>>,
+
+    #[account()]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: checked against the cave info in the handler
+    #[account(mut)]
+    pub depositor: AccountInfo<'info>,
+
+    /// CHECK: gets the rent back, checked against `cave_info.rent_payer`
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+
+    /// NOTE: the token metadata program checks that this token account
+    /// belongs to `depositor`
+    #[account(mut)]
+    pub depositor_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// Pays for the depositor's token record
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub pnft: PnftAccounts<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
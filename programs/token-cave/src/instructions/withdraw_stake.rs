@@ -0,0 +1,97 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::stake::{self, state::StakeAuthorize};
+use super::initialize::{CaveInfo, CaveKind};
+use super::initialize_stake::authorize_stake;
+use super::initialize_config::ProgramConfig;
+use crate::error::TokenCaveError;
+use crate::registry;
+
+pub fn handler(
+    ctx: Context<WithdrawStake>,
+) -> Result<()> {
+
+    // Check that a pause does not block withdraws
+    ctx.accounts.config.check_withdraw_allowed(Clock::get()?.unix_timestamp)?;
+
+    // Check that this is the depositor
+    require_keys_eq!(
+        ctx.accounts.cave_info.depositor,
+        ctx.accounts.depositor.key(),
+        TokenCaveError::Unauthorized,
+    );
+
+    // Check that the unlock has matured and not lapsed
+    let now = ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?);
+    ctx.accounts.cave_info.check_withdrawable(now)?;
+
+    // Hand the stake account back to the depositor
+    for stake_authorize in [StakeAuthorize::Staker, StakeAuthorize::Withdrawer] {
+        authorize_stake(
+            &ctx.accounts.stake_account.to_account_info(),
+            &ctx.accounts.clock.to_account_info(),
+            &ctx.accounts.cave_info.to_account_info(),
+            &ctx.accounts.depositor.key(),
+            stake_authorize,
+            &[&[ctx.accounts.stake_account.key().as_ref(), &[ctx.accounts.cave_info.info_bump]]],
+        )?;
+    }
+
+    // Remove the cave from the depositor's registry
+    registry::remove(&ctx.accounts.registry, &ctx.accounts.cave_info.key())?;
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct WithdrawStake<'info> {
+
+    /// CHECK: address is checked against the cave info
+    #[account(
+        mut,
+        address = cave_info.cave,
+        owner = stake::program::ID,
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    /// This PDA stores the information about the stake cave
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [&stake_account.key().to_bytes()],
+        bump = cave_info.info_bump,
+        has_one = rent_payer,
+        constraint = cave_info.kind == CaveKind::Stake,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    /// CHECK: checked against the cave info in the handler
+    #[account(mut)]
+    pub depositor: AccountInfo<'info>,
+
+    /// CHECK: gets the rent back, checked against `cave_info.rent_payer`
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: address is checked
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: the depositor's cave registry, if they have one. Written by
+    /// `registry::remove`.
+    #[account(
+        mut,
+        seeds = [b"registry".as_ref(), cave_info.depositor.as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+}
@@ -0,0 +1,121 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{TokenAccount, Token};
+use super::initialize::{CaveInfo, CaveKind};
+use super::request_withdraw_ticket::WithdrawTicket;
+use super::initialize_config::ProgramConfig;
+use crate::fee::{self, FeeTiming};
+
+/// Pays out a matured withdraw ticket to its destination. The destination
+/// was fixed by the depositor when requesting the ticket, so anyone can
+/// crank this.
+pub fn handler(
+    ctx: Context<WithdrawTicketFunds>,
+) -> Result<()> {
+
+    // Check that a pause does not block withdraws
+    ctx.accounts.config.check_withdraw_allowed(Clock::get()?.unix_timestamp)?;
+
+    // Check that the ticket has matured and not lapsed
+    let now = ctx.accounts.cave_info.timelock_unit.now(&Clock::get()?);
+    ctx.accounts.cave_info.check_matured(ctx.accounts.ticket.request_time, now)?;
+
+    // Take the protocol fee out of the payout
+    let fee = fee::fee_due(
+        &ctx.accounts.config,
+        &ctx.accounts.cave_info.fee,
+        &ctx.accounts.treasury_token_account,
+        &ctx.accounts.cave_info.mint,
+        FeeTiming::Withdraw,
+        ctx.accounts.ticket.amount,
+    )?;
+    if fee > 0 {
+        anchor_spl::token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.cave.to_account_info(),
+                    to: ctx.accounts.treasury_token_account.to_account_info(),
+                    authority: ctx.accounts.cave_info.to_account_info(),
+                },
+                &[&[ctx.accounts.cave.key().as_ref(), &[ctx.accounts.cave_info.info_bump]]]
+            ),
+            fee,
+        )?;
+    }
+
+    anchor_spl::token::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::Transfer {
+                from: ctx.accounts.cave.to_account_info(),
+                to: ctx.accounts.destination.to_account_info(),
+                authority: ctx.accounts.cave_info.to_account_info(),
+            },
+            &[&[ctx.accounts.cave.key().as_ref(), &[ctx.accounts.cave_info.info_bump]]]
+        ),
+        ctx.accounts.ticket.amount.checked_sub(fee).unwrap(),
+    )?;
+
+    let amount = ctx.accounts.ticket.amount;
+    ctx.accounts.cave_info.release_ticket(amount);
+    ctx.accounts.cave_info.deposited_amount = ctx.accounts.cave_info.deposited_amount
+        .saturating_sub(amount);
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct WithdrawTicketFunds<'info> {
+
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [
+            b"ticket",
+            cave_info.key().as_ref(),
+            &ticket.id.to_le_bytes(),
+        ],
+        bump = ticket.bump,
+        has_one = cave_info,
+        has_one = destination,
+        has_one = rent_payer,
+    )]
+    pub ticket: Account<'info, WithdrawTicket>,
+
+    /// The token cave the ticket withdraws from
+    #[account(
+        mut,
+        address = cave_info.cave,
+    )]
+    pub cave: Account<'info, TokenAccount>,
+
+    /// This PDA stores the information about the associated cave
+    #[account(
+        mut,
+        seeds = [&cave.key().to_bytes()],
+        bump = cave_info.info_bump,
+        constraint = cave_info.kind == CaveKind::Token,
+    )]
+    pub cave_info: Account<'info, CaveInfo>,
+
+    /// CHECK: gets the ticket's rent back, checked against `ticket.rent_payer`
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// Global settings, checked for an emergency pause
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// CHECK: receives the fee. Checked by `fee::fee_due` when one is owed.
+    #[account(mut)]
+    pub treasury_token_account: UncheckedAccount<'info>,
+}
@@ -4,13 +4,59 @@ declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
 pub mod instructions;
 pub mod error;
+pub mod strategy;
+pub mod fee;
+pub mod signature;
+pub mod ata;
+pub mod oracle;
+pub mod registry;
 
 use instructions::{
     initialize::*,
     unlock::*,
     withdraw::*,
     abort::*,
+    verify_lock::*,
+    create_voter_weight_record::*,
+    update_voter_weight_record::*,
+    deposit_to_strategy::*,
+    initialize_stake::*,
+    withdraw_stake::*,
+    abort_stake::*,
+    initialize_pnft::*,
+    withdraw_pnft::*,
+    abort_pnft::*,
+    initialize_basket::*,
+    add_to_basket::*,
+    withdraw_basket::*,
+    abort_basket::*,
+    request_withdraw_ticket::*,
+    cancel_withdraw_ticket::*,
+    withdraw_ticket::*,
+    abort_withdraw_ticket::*,
+    set_duress_key::*,
+    initialize_config::*,
+    set_pause::*,
+    update_config::*,
+    set_fee_config::*,
+    set_mint_fee::*,
+    clear_mint_fee::*,
+    abort_with_signature::*,
+    set_backup_destination::*,
+    initialize_gift::*,
+    withdraw_gift::*,
+    clawback::*,
+    set_hashlock::*,
+    claim_htlc::*,
+    set_price_condition::*,
+    release_on_price::*,
+    register_session_key::*,
+    revoke_session_key::*,
+    batch_withdraw::*,
+    batch_abort::*,
+    set_strategies::*,
 };
+use fee::FeeSchedule;
 
 
 #[program]
@@ -23,31 +69,365 @@ pub mod token_cave {
         deposit_amount: u64,
         backup_address: Option<Pubkey>,
         timelock_duration: u32,
+        timelock_unit: TimelockUnit,
+        withdraw_window: Option<u32>,
     ) -> Result<()> {
         instructions::initialize::handler(
             ctx,
             deposit_amount,
             backup_address,
             timelock_duration,
+            timelock_unit,
+            withdraw_window,
         )
     }
 
-    pub fn unlock(
-        ctx: Context<Unlock>,
+    pub fn unlock<'info>(
+        ctx: Context<'_, '_, '_, 'info, Unlock<'info>>,
+        duress_salt: Option<[u8; 32]>,
     ) -> Result<()> {
-        instructions::unlock::handler(ctx)
+        instructions::unlock::handler(ctx, duress_salt)
     }
 
-    pub fn withdraw(
-        ctx: Context<Withdraw>,
+    pub fn withdraw<'info>(
+        ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>,
     ) -> Result<()> {
         instructions::withdraw::handler(ctx)
     }
 
-    pub fn abort(
-        ctx: Context<Abort>,
+    pub fn abort<'info>(
+        ctx: Context<'_, '_, '_, 'info, Abort<'info>>,
     ) -> Result<()> {
         instructions::abort::handler(ctx)
     }
+
+    pub fn verify_lock(
+        ctx: Context<VerifyLock>,
+        min_amount: u64,
+        min_lock_duration: u32,
+    ) -> Result<()> {
+        instructions::verify_lock::handler(
+            ctx,
+            min_amount,
+            min_lock_duration,
+        )
+    }
+
+    pub fn create_voter_weight_record(
+        ctx: Context<CreateVoterWeightRecord>,
+        realm: Pubkey,
+    ) -> Result<()> {
+        instructions::create_voter_weight_record::handler(ctx, realm)
+    }
+
+    pub fn update_voter_weight_record<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdateVoterWeightRecord<'info>>,
+    ) -> Result<()> {
+        instructions::update_voter_weight_record::handler(ctx)
+    }
+
+    pub fn deposit_to_strategy<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositToStrategy<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::deposit_to_strategy::handler(ctx, amount)
+    }
+
+    pub fn initialize_stake(
+        ctx: Context<InitializeStake>,
+        backup_address: Option<Pubkey>,
+        timelock_duration: u32,
+        timelock_unit: TimelockUnit,
+        withdraw_window: Option<u32>,
+    ) -> Result<()> {
+        instructions::initialize_stake::handler(
+            ctx,
+            backup_address,
+            timelock_duration,
+            timelock_unit,
+            withdraw_window,
+        )
+    }
+
+    pub fn withdraw_stake(
+        ctx: Context<WithdrawStake>,
+    ) -> Result<()> {
+        instructions::withdraw_stake::handler(ctx)
+    }
+
+    pub fn abort_stake(
+        ctx: Context<AbortStake>,
+    ) -> Result<()> {
+        instructions::abort_stake::handler(ctx)
+    }
+
+    pub fn initialize_pnft(
+        ctx: Context<InitializePnft>,
+        backup_address: Option<Pubkey>,
+        timelock_duration: u32,
+        timelock_unit: TimelockUnit,
+        withdraw_window: Option<u32>,
+    ) -> Result<()> {
+        instructions::initialize_pnft::handler(
+            ctx,
+            backup_address,
+            timelock_duration,
+            timelock_unit,
+            withdraw_window,
+        )
+    }
+
+    pub fn withdraw_pnft(
+        ctx: Context<WithdrawPnft>,
+    ) -> Result<()> {
+        instructions::withdraw_pnft::handler(ctx)
+    }
+
+    pub fn abort_pnft(
+        ctx: Context<AbortPnft>,
+    ) -> Result<()> {
+        instructions::abort_pnft::handler(ctx)
+    }
+
+    pub fn initialize_basket(
+        ctx: Context<InitializeBasket>,
+        basket: Pubkey,
+        backup_address: Option<Pubkey>,
+        timelock_duration: u32,
+        timelock_unit: TimelockUnit,
+        withdraw_window: Option<u32>,
+    ) -> Result<()> {
+        instructions::initialize_basket::handler(
+            ctx,
+            basket,
+            backup_address,
+            timelock_duration,
+            timelock_unit,
+            withdraw_window,
+        )
+    }
+
+    pub fn add_to_basket(
+        ctx: Context<AddToBasket>,
+        deposit_amount: u64,
+    ) -> Result<()> {
+        instructions::add_to_basket::handler(ctx, deposit_amount)
+    }
+
+    pub fn withdraw_basket<'info>(
+        ctx: Context<'_, '_, '_, 'info, WithdrawBasket<'info>>,
+    ) -> Result<()> {
+        instructions::withdraw_basket::handler(ctx)
+    }
+
+    pub fn abort_basket<'info>(
+        ctx: Context<'_, '_, '_, 'info, AbortBasket<'info>>,
+    ) -> Result<()> {
+        instructions::abort_basket::handler(ctx)
+    }
+
+    pub fn request_withdraw_ticket<'info>(
+        ctx: Context<'_, '_, '_, 'info, RequestWithdrawTicket<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::request_withdraw_ticket::handler(ctx, amount)
+    }
+
+    pub fn cancel_withdraw_ticket(
+        ctx: Context<CancelWithdrawTicket>,
+    ) -> Result<()> {
+        instructions::cancel_withdraw_ticket::handler(ctx)
+    }
+
+    pub fn withdraw_ticket(
+        ctx: Context<WithdrawTicketFunds>,
+    ) -> Result<()> {
+        instructions::withdraw_ticket::handler(ctx)
+    }
+
+    pub fn abort_withdraw_ticket(
+        ctx: Context<AbortWithdrawTicket>,
+    ) -> Result<()> {
+        instructions::abort_withdraw_ticket::handler(ctx)
+    }
+
+    pub fn set_duress_key(
+        ctx: Context<SetDuressKey>,
+        duress_commitment: Option<[u8; 32]>,
+    ) -> Result<()> {
+        instructions::set_duress_key::handler(ctx, duress_commitment)
+    }
+
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        pause_authority: Pubkey,
+        withdraw_allowed_while_paused: bool,
+    ) -> Result<()> {
+        instructions::initialize_config::handler(
+            ctx,
+            pause_authority,
+            withdraw_allowed_while_paused,
+        )
+    }
+
+    pub fn set_pause(
+        ctx: Context<SetPause>,
+        paused: bool,
+    ) -> Result<()> {
+        instructions::set_pause::handler(ctx, paused)
+    }
+
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        new_authority: Pubkey,
+        pause_authority: Pubkey,
+        withdraw_allowed_while_paused: bool,
+    ) -> Result<()> {
+        instructions::update_config::handler(
+            ctx,
+            new_authority,
+            pause_authority,
+            withdraw_allowed_while_paused,
+        )
+    }
+
+    pub fn set_fee_config(
+        ctx: Context<SetFeeConfig>,
+        treasury: Pubkey,
+        fee: FeeSchedule,
+    ) -> Result<()> {
+        instructions::set_fee_config::handler(ctx, treasury, fee)
+    }
+
+    pub fn set_mint_fee(
+        ctx: Context<SetMintFee>,
+        fee: FeeSchedule,
+    ) -> Result<()> {
+        instructions::set_mint_fee::handler(ctx, fee)
+    }
+
+    pub fn clear_mint_fee(
+        ctx: Context<ClearMintFee>,
+    ) -> Result<()> {
+        instructions::clear_mint_fee::handler(ctx)
+    }
+
+    pub fn abort_with_signature<'info>(
+        ctx: Context<'_, '_, '_, 'info, AbortWithSignature<'info>>,
+        nonce: u64,
+        expiry: i64,
+    ) -> Result<()> {
+        instructions::abort_with_signature::handler(ctx, nonce, expiry)
+    }
+
+    pub fn set_backup_destination(
+        ctx: Context<SetBackupDestination>,
+        destination: Option<Pubkey>,
+    ) -> Result<()> {
+        instructions::set_backup_destination::handler(ctx, destination)
+    }
+
+    pub fn initialize_gift(
+        ctx: Context<InitializeGift>,
+        deposit_amount: u64,
+        beneficiary: Pubkey,
+        timelock_duration: u32,
+        timelock_unit: TimelockUnit,
+        withdraw_window: Option<u32>,
+        clawback_deadline: Option<i64>,
+    ) -> Result<()> {
+        instructions::initialize_gift::handler(
+            ctx,
+            deposit_amount,
+            beneficiary,
+            timelock_duration,
+            timelock_unit,
+            withdraw_window,
+            clawback_deadline,
+        )
+    }
+
+    pub fn withdraw_gift(
+        ctx: Context<WithdrawGift>,
+    ) -> Result<()> {
+        instructions::withdraw_gift::handler(ctx)
+    }
+
+    pub fn clawback(
+        ctx: Context<Clawback>,
+    ) -> Result<()> {
+        instructions::clawback::handler(ctx)
+    }
+
+    pub fn set_hashlock(
+        ctx: Context<SetHashlock>,
+        hash: [u8; 32],
+        recipient: Pubkey,
+        deadline: i64,
+    ) -> Result<()> {
+        instructions::set_hashlock::handler(ctx, hash, recipient, deadline)
+    }
+
+    pub fn claim_htlc(
+        ctx: Context<ClaimHtlc>,
+        preimage: Vec<u8>,
+    ) -> Result<()> {
+        instructions::claim_htlc::handler(ctx, preimage)
+    }
+
+    pub fn set_price_condition(
+        ctx: Context<SetPriceCondition>,
+        trigger: PriceTrigger,
+        threshold: i64,
+    ) -> Result<()> {
+        instructions::set_price_condition::handler(ctx, trigger, threshold)
+    }
+
+    pub fn release_on_price<'info>(
+        ctx: Context<'_, '_, '_, 'info, ReleaseOnPrice<'info>>,
+    ) -> Result<()> {
+        instructions::release_on_price::handler(ctx)
+    }
+
+    pub fn register_session_key(
+        ctx: Context<RegisterSessionKey>,
+        key: Pubkey,
+        scopes: u8,
+        max_amount: u64,
+        expires_at: i64,
+    ) -> Result<()> {
+        instructions::register_session_key::handler(
+            ctx,
+            key,
+            scopes,
+            max_amount,
+            expires_at,
+        )
+    }
+
+    pub fn revoke_session_key(
+        ctx: Context<RevokeSessionKey>,
+    ) -> Result<()> {
+        instructions::revoke_session_key::handler(ctx)
+    }
+
+    pub fn batch_withdraw<'info>(
+        ctx: Context<'_, '_, '_, 'info, BatchWithdraw<'info>>,
+    ) -> Result<()> {
+        instructions::batch_withdraw::handler(ctx)
+    }
+
+    pub fn batch_abort<'info>(
+        ctx: Context<'_, '_, '_, 'info, BatchAbort<'info>>,
+    ) -> Result<()> {
+        instructions::batch_abort::handler(ctx)
+    }
+
+    pub fn set_strategies(
+        ctx: Context<SetStrategies>,
+        strategies: Vec<Pubkey>,
+    ) -> Result<()> {
+        instructions::set_strategies::handler(ctx, strategies)
+    }
     
 }
\ No newline at end of file
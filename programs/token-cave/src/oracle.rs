@@ -0,0 +1,118 @@
+//! Reader for on-chain price feeds.
+//!
+//! Price-conditioned caves only store the feed's address, and read it
+//! through `read_price`, which picks the account layout by the feed's owner
+//! program. Supported are Pyth price accounts and, in localnet builds, the
+//! feeds of `programs/mock-oracle`. Another oracle only needs its owner
+//! program and a parser added here.
+use anchor_lang::prelude::*;
+use crate::error::TokenCaveError;
+
+/// Pyth oracle program on mainnet-beta
+pub const PYTH_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
+    220, 229, 235, 225, 228, 156, 59, 159,
+    17, 76, 181, 84, 76, 80, 169, 158,
+    192, 214, 146, 214, 63, 86, 121, 90,
+    224, 41, 172, 131, 217, 234, 139, 226,
+]);
+
+/// Mock oracle program, only accepted in localnet builds
+#[cfg(feature = "mock-oracle")]
+pub const MOCK_ORACLE_ID: Pubkey = Pubkey::new_from_array([
+    39, 241, 69, 215, 143, 177, 64, 116,
+    30, 89, 42, 170, 31, 36, 103, 228,
+    102, 224, 206, 194, 88, 243, 104, 158,
+    54, 223, 135, 152, 249, 72, 58, 45,
+]);
+
+/// Oldest price, in seconds, a price condition accepts
+pub const MAX_PRICE_AGE: i64 = 60;
+
+/// Pyth price account layout (version 2)
+const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+const PYTH_PRICE_ACCOUNT_TYPE: u32 = 3;
+const PYTH_TRADING_STATUS: u32 = 1;
+const PYTH_EXPO_OFFSET: usize = 20;
+const PYTH_TIMESTAMP_OFFSET: usize = 96;
+const PYTH_AGG_PRICE_OFFSET: usize = 208;
+const PYTH_AGG_STATUS_OFFSET: usize = 224;
+
+/// Mock oracle feed layout, after the 8 byte discriminator and authority
+#[cfg(feature = "mock-oracle")]
+const MOCK_PRICE_OFFSET: usize = 40;
+
+
+/// A price read from a feed: `price * 10^expo`, published at unix
+/// timestamp `publish_time`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Price {
+    pub price: i64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+/// Reads the current price of `feed`, failing if it is unsupported,
+/// not trading, or older than `MAX_PRICE_AGE` at unix timestamp `now`
+pub fn read_price(feed: &AccountInfo, now: i64) -> Result<Price> {
+
+    let data = feed.try_borrow_data()?;
+    let price = if *feed.owner == PYTH_PROGRAM_ID {
+        read_pyth(&data)?
+    } else {
+        read_other(feed.owner, &data)?
+    };
+
+    // Check that the price is recent
+    require_gte!(
+        price.publish_time.saturating_add(MAX_PRICE_AGE),
+        now,
+        TokenCaveError::StalePrice,
+    );
+
+    Ok(price)
+}
+
+fn read_pyth(data: &[u8]) -> Result<Price> {
+    require!(
+        data.len() >= PYTH_AGG_STATUS_OFFSET + 4
+        && read_u32(data, 0) == PYTH_MAGIC
+        && read_u32(data, 8) == PYTH_PRICE_ACCOUNT_TYPE,
+        TokenCaveError::UnsupportedPriceFeed,
+    );
+    require_eq!(
+        read_u32(data, PYTH_AGG_STATUS_OFFSET),
+        PYTH_TRADING_STATUS,
+        TokenCaveError::StalePrice,
+    );
+    Ok(Price {
+        price: read_i64(data, PYTH_AGG_PRICE_OFFSET),
+        expo: read_u32(data, PYTH_EXPO_OFFSET) as i32,
+        publish_time: read_i64(data, PYTH_TIMESTAMP_OFFSET),
+    })
+}
+
+#[cfg(feature = "mock-oracle")]
+fn read_other(owner: &Pubkey, data: &[u8]) -> Result<Price> {
+    require!(
+        *owner == MOCK_ORACLE_ID && data.len() >= MOCK_PRICE_OFFSET + 20,
+        TokenCaveError::UnsupportedPriceFeed,
+    );
+    Ok(Price {
+        price: read_i64(data, MOCK_PRICE_OFFSET),
+        expo: read_u32(data, MOCK_PRICE_OFFSET + 8) as i32,
+        publish_time: read_i64(data, MOCK_PRICE_OFFSET + 12),
+    })
+}
+
+#[cfg(not(feature = "mock-oracle"))]
+fn read_other(_owner: &Pubkey, _data: &[u8]) -> Result<Price> {
+    err!(TokenCaveError::UnsupportedPriceFeed)
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+fn read_i64(data: &[u8], at: usize) -> i64 {
+    i64::from_le_bytes(data[at..at + 8].try_into().unwrap())
+}
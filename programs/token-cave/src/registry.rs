@@ -0,0 +1,121 @@
+//! Per-depositor index of caves.
+//!
+//! Every depositor has a `CaveRegistry` PDA at `["registry", depositor]`
+//! listing the cave infos of their open caves, so clients can find them
+//! without scanning all program accounts. The initializers add to it,
+//! creating it with the depositor's first cave, and every instruction that
+//! closes a cave removes it. The account keeps its size when caves are
+//! removed, and later caves reuse the space.
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+
+/// Size of a registry listing no caves. Each cave adds 32 bytes.
+pub const CAVE_REGISTRY_BASE_SIZE: usize = 8 + 32 + 1 + 4;
+
+
+#[account]
+pub struct CaveRegistry {
+
+    /// Depositor whose caves are listed
+    pub depositor: Pubkey,
+
+    /// Bump of this PDA
+    pub bump: u8,
+
+    /// Cave infos of the depositor's open caves
+    pub caves: Vec<Pubkey>,
+}
+
+/// Adds `cave_info` to the registry of `depositor`, creating or growing
+/// the account at the expense of `payer`
+pub fn add<'info>(
+    registry: &AccountInfo<'info>,
+    depositor: &Pubkey,
+    bump: u8,
+    cave_info: &Pubkey,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+
+    let created = registry.owner == &crate::ID;
+    let mut caves = if created {
+        let registry: Account<CaveRegistry> = Account::try_from(registry)?;
+        registry.into_inner().caves
+    } else {
+        Vec::new()
+    };
+    caves.push(*cave_info);
+
+    // Fund the new size. The address may hold lamports already, so the
+    // registry is created by allocating and assigning it rather than with
+    // `create_account`.
+    let space = CAVE_REGISTRY_BASE_SIZE + 32 * caves.len();
+    if space > registry.data_len() {
+        let lamports = Rent::get()?
+            .minimum_balance(space)
+            .saturating_sub(registry.lamports());
+        if lamports > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    system_program::Transfer {
+                        from: payer.clone(),
+                        to: registry.clone(),
+                    },
+                ),
+                lamports,
+            )?;
+        }
+        if created {
+            registry.realloc(space, false)?;
+        } else {
+            let signer_seeds: &[&[&[u8]]] = &[&[b"registry".as_ref(), depositor.as_ref(), &[bump]]];
+            system_program::allocate(
+                CpiContext::new_with_signer(
+                    system_program.clone(),
+                    system_program::Allocate {
+                        account_to_allocate: registry.clone(),
+                    },
+                    signer_seeds,
+                ),
+                space as u64,
+            )?;
+            system_program::assign(
+                CpiContext::new_with_signer(
+                    system_program.clone(),
+                    system_program::Assign {
+                        account_to_assign: registry.clone(),
+                    },
+                    signer_seeds,
+                ),
+                &crate::ID,
+            )?;
+        }
+    }
+
+    let mut data = registry.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data[..];
+    CaveRegistry {
+        depositor: *depositor,
+        bump,
+        caves,
+    }.try_serialize(&mut writer)
+}
+
+/// Removes `cave_info` from a registry. Caves created before registries
+/// existed aren't listed, so a missing registry or cave is fine.
+pub fn remove(
+    registry: &AccountInfo,
+    cave_info: &Pubkey,
+) -> Result<()> {
+
+    if registry.owner != &crate::ID {
+        return Ok(());
+    }
+    let mut registry: Account<CaveRegistry> = Account::try_from(registry)?;
+    if let Some(index) = registry.caves.iter().position(|cave| cave == cave_info) {
+        registry.caves.swap_remove(index);
+        registry.exit(&crate::ID)?;
+    }
+    Ok(())
+}
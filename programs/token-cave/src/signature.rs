@@ -0,0 +1,89 @@
+//! Verification of off-chain signatures through the Ed25519 program.
+//!
+//! The runtime verifies Ed25519 program instructions before the transaction
+//! executes, so it's enough to check, through the instructions sysvar, that
+//! such an instruction is present and covers the expected key and message.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked,
+    load_instruction_at_checked,
+};
+use crate::error::TokenCaveError;
+
+/// Prefix of signed abort messages, so that they can't be confused with
+/// other messages signed by the backup
+pub const ABORT_MESSAGE_PREFIX: &[u8] = b"token-cave:abort";
+
+/// Size of the Ed25519 instruction header and of one signature's offsets
+const HEADER_SIZE: usize = 2;
+const OFFSETS_SIZE: usize = 14;
+
+/// Offsets index meaning "this instruction"
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+
+/// The message the backup signs to abort a cave:
+/// `prefix || cave_info || nonce (u64 le) || expiry (i64 le)`
+pub fn abort_message(cave_info: &Pubkey, nonce: u64, expiry: i64) -> Vec<u8> {
+    let mut message = ABORT_MESSAGE_PREFIX.to_vec();
+    message.extend_from_slice(cave_info.as_ref());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message.extend_from_slice(&expiry.to_le_bytes());
+    message
+}
+
+/// Checks that the instruction before the current one is an Ed25519 program
+/// instruction verifying a single signature of `signer` over `message`
+pub fn verify_ed25519_signature(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+
+    let current_index = load_current_index_checked(instructions_sysvar)?;
+    let index = current_index
+        .checked_sub(1)
+        .ok_or(TokenCaveError::MissingSignature)?;
+    let instruction = load_instruction_at_checked(index.into(), instructions_sysvar)?;
+    require_keys_eq!(
+        instruction.program_id,
+        ed25519_program::ID,
+        TokenCaveError::MissingSignature,
+    );
+
+    // Exactly one signature, with key, signature and message all in the
+    // Ed25519 instruction itself
+    let data = &instruction.data;
+    require!(
+        data.len() >= HEADER_SIZE + OFFSETS_SIZE && data[0] == 1,
+        TokenCaveError::InvalidSignature,
+    );
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let offsets = HEADER_SIZE;
+    let signature_instruction_index = read_u16(offsets + 2);
+    let public_key_offset = read_u16(offsets + 4) as usize;
+    let public_key_instruction_index = read_u16(offsets + 6);
+    let message_offset = read_u16(offsets + 8) as usize;
+    let message_size = read_u16(offsets + 10) as usize;
+    let message_instruction_index = read_u16(offsets + 12);
+    require!(
+        signature_instruction_index == CURRENT_INSTRUCTION
+        && public_key_instruction_index == CURRENT_INSTRUCTION
+        && message_instruction_index == CURRENT_INSTRUCTION,
+        TokenCaveError::InvalidSignature,
+    );
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(TokenCaveError::InvalidSignature)?;
+    let signed_message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(TokenCaveError::InvalidSignature)?;
+    require!(
+        public_key == signer.as_ref() && signed_message == message,
+        TokenCaveError::InvalidSignature,
+    );
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! Adapter for yield strategies.
+//!
+//! A strategy is an external lending or staking program that holds cave
+//! funds while they are locked. Strategy programs must implement two anchor
+//! style instructions:
+//!
+//! - `deposit(amount: u64)` with accounts
+//!   `[authority (signer), token_account (mut), payer (signer, mut), ..]`
+//! - `redeem()` with accounts
+//!   `[authority (signer), token_account (mut), rent_receiver (mut), ..]`
+//!
+//! where `authority` is the cave info PDA and `token_account` is the cave.
+//! `redeem` must return the entire position, including yield, to the cave.
+//! Any further accounts are strategy specific and are forwarded as is.
+//!
+//! Caves can only deposit into strategies the config authority whitelisted
+//! with `set_strategies`.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+use crate::error::TokenCaveError;
+use crate::instructions::initialize_config::ProgramConfig;
+
+/// Fails unless the config authority whitelisted `program_id`. Only
+/// deposits are checked, so that removing a strategy from the whitelist
+/// never traps the funds of caves that already use it.
+pub fn check_whitelisted(config: &ProgramConfig, program_id: &Pubkey) -> Result<()> {
+    require!(
+        config.strategies.contains(program_id),
+        TokenCaveError::StrategyNotWhitelisted,
+    );
+    Ok(())
+}
+
+/// Deposits `amount` from the cave into the strategy
+pub fn deposit<'info>(
+    strategy_program: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    token_account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    strategy_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let mut data = sighash("deposit").to_vec();
+    data.extend_from_slice(&amount.to_le_bytes());
+    invoke_strategy(
+        strategy_program,
+        [authority, token_account, payer],
+        strategy_accounts,
+        data,
+        signer_seeds,
+    )
+}
+
+/// Redeems the cave's entire position in the strategy back into the cave
+pub fn redeem<'info>(
+    strategy_program: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    token_account: &AccountInfo<'info>,
+    rent_receiver: &AccountInfo<'info>,
+    strategy_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    invoke_strategy(
+        strategy_program,
+        [authority, token_account, rent_receiver],
+        strategy_accounts,
+        sighash("redeem").to_vec(),
+        signer_seeds,
+    )
+}
+
+/// Splits the remaining accounts of `withdraw` and `abort` into the
+/// strategy program and the strategy specific accounts, checking that the
+/// program is the one the cave deposited into.
+pub fn split_remaining_accounts<'a, 'info>(
+    strategy: Pubkey,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<(&'a AccountInfo<'info>, &'a [AccountInfo<'info>])> {
+    let (strategy_program, strategy_accounts) = remaining_accounts
+        .split_first()
+        .ok_or(TokenCaveError::MissingStrategyAccounts)?;
+    require_keys_eq!(
+        strategy_program.key(),
+        strategy,
+        TokenCaveError::MissingStrategyAccounts,
+    );
+    Ok((strategy_program, strategy_accounts))
+}
+
+fn invoke_strategy<'info>(
+    strategy_program: &AccountInfo<'info>,
+    interface_accounts: [&AccountInfo<'info>; 3],
+    strategy_accounts: &[AccountInfo<'info>],
+    data: Vec<u8>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+
+    let [authority, token_account, third] = interface_accounts;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(authority.key(), true),
+        AccountMeta::new(token_account.key(), false),
+        AccountMeta::new(third.key(), third.is_signer),
+    ];
+    accounts.extend(strategy_accounts.iter().map(|account| {
+        if account.is_writable {
+            AccountMeta::new(account.key(), account.is_signer)
+        } else {
+            AccountMeta::new_readonly(account.key(), account.is_signer)
+        }
+    }));
+
+    let mut account_infos = vec![
+        authority.clone(),
+        token_account.clone(),
+        third.clone(),
+    ];
+    account_infos.extend_from_slice(strategy_accounts);
+    account_infos.push(strategy_program.clone());
+
+    invoke_signed(
+        &Instruction {
+            program_id: strategy_program.key(),
+            accounts,
+            data,
+        },
+        &account_infos,
+        signer_seeds,
+    ).map_err(Into::into)
+}
+
+/// Anchor instruction discriminator
+fn sighash(name: &str) -> [u8; 8] {
+    let mut discriminator = [0; 8];
+    discriminator.copy_from_slice(&hash(format!("global:{name}").as_bytes()).to_bytes()[..8]);
+    discriminator
+}
@@ -1,24 +1,50 @@
 use std::rc::Rc;
 use std::time::Duration;
 
+use anchor_client::anchor_lang::solana_program::hash::hash;
 use anchor_client::anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
 use anchor_client::anchor_lang::solana_program::sysvar::SysvarId;
 use anchor_client::anchor_lang::system_program;
-use anchor_client::solana_sdk::instruction::Instruction;
+use anchor_client::anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_client::solana_sdk::instruction::{AccountMeta, CompiledInstruction, Instruction};
+use anchor_client::solana_sdk::message::{v0, MessageHeader, VersionedMessage};
 use anchor_client::solana_sdk::program_pack::Pack;
-use anchor_client::solana_sdk::transaction::Transaction;
+use anchor_client::solana_sdk::transaction::{Transaction, VersionedTransaction};
 use anchor_client::solana_client::rpc_client::RpcClient;
+use anchor_client::solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType};
+use anchor_client::solana_client::rpc_request::RpcRequest;
+use anchor_client::solana_sdk::bpf_loader_upgradeable;
 use anchor_client::solana_sdk::commitment_config::CommitmentConfig;
+use anchor_client::solana_sdk::ed25519_instruction::new_ed25519_instruction;
 use anchor_client::solana_sdk::pubkey::Pubkey;
 use anchor_client::solana_sdk::signature::read_keypair_file;
-use anchor_client::solana_sdk::signature::{Keypair, Signer};
-use anchor_client::solana_sdk::system_transaction;
-use anchor_spl::token::spl_token::state::Mint;
+use anchor_client::solana_sdk::signature::{Keypair, Signature, Signer};
+use anchor_client::solana_sdk::stake;
+use anchor_client::solana_sdk::{system_instruction, system_transaction};
+use anchor_spl::token::spl_token::instruction as spl_token_instruction;
+use anchor_spl::token::spl_token::state::{Account as TokenAccount, Mint};
 use anchor_client::{Client, Cluster, Program};
 use anchor_spl::token::ID as TOKEN_PROGRAM_ID;
 use rand::rngs::OsRng;
+use serde_json::json;
+use solana_address_lookup_table_program as address_lookup_table;
 // Get token_cave
-use token_cave::instructions::initialize::{CaveInfo, CAVE_INFO_SIZE};
+use token_cave::instructions::initialize::{
+    CaveInfo, CaveKind, PriceTrigger, TimelockUnit, CAVE_INFO_DEPOSITOR_OFFSET, CAVE_INFO_SIZE,
+};
+use token_cave::instructions::batch_abort::BATCH_ABORT_GROUP_SIZE;
+use token_cave::instructions::create_voter_weight_record::{VoterWeightRecord, VOTER_WEIGHT_RECORD_SIZE};
+use token_cave::instructions::initialize_config::ProgramConfig;
+use token_cave::instructions::initialize_pnft::{
+    transfer_v1_instruction, TransferV1Keys, TOKEN_METADATA_PROGRAM_ID,
+};
+use token_cave::instructions::register_session_key::SCOPE_WITHDRAW;
+use token_cave::instructions::request_withdraw_ticket::WithdrawTicket;
+use token_cave::instructions::set_duress_key::duress_commitment;
+use token_cave::instructions::set_hashlock::HASHLOCK_CLAIM_MARGIN;
+use token_cave::fee::{FeeSchedule, FeeTiming, MAX_FLAT_FEE};
+use token_cave::registry::CaveRegistry;
+use token_cave::signature::abort_message;
 use anyhow::Result;
 
 const PROGRAM_ID: Pubkey = Pubkey::new_from_array([
@@ -57,6 +83,13 @@ fn test_deposit_unlock_withdraw() {
             .unwrap_or("FAILED TO INITIALIZE MINT ACCOUNT".to_string())
     );
 
+    // Initialize program config
+    println!(
+        "initialize config tx signature: {}",
+        initialize_config_account(&dev_key, &program)
+            .unwrap_or("FAILED TO INITIALIZE CONFIG".to_string())
+    );
+
     // Get funded user and backup
     let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
         .expect("failed to get funded user");
@@ -95,15 +128,22 @@ fn test_deposit_unlock_withdraw() {
             cave,
             mint: mint_key.pubkey(),
             depositor: user.keypair.pubkey(),
+            payer: user.keypair.pubkey(),
             depositor_token_account: user.ata,
             token_program: TOKEN_PROGRAM_ID,
             system_program: system_program::ID,
             rent: anchor_client::solana_sdk::rent::Rent::id(),
+            config: get_config_pda(),
+            fee_override: get_fee_override_pda(&mint_key.pubkey()),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
         })
         .args(token_cave::instruction::Initialize {
             backup_address: Some(backup.keypair.pubkey()),
             deposit_amount: 10 * ONE_DEMO_TOKEN,
             timelock_duration: TEST_TIMELOCK_DURATION,
+            timelock_unit: TimelockUnit::UnixTimestamp,
+            withdraw_window: None,
         })
         .signer(&*user.keypair)
         .payer(user.keypair.clone())
@@ -146,18 +186,20 @@ fn test_deposit_unlock_withdraw() {
         i64::MIN,
     );
     assert!(!cave_info_account.unlocking);
+    assert_eq!(cave_info_account.mint, mint_key.pubkey());
+    assert_eq!(cave_info_account.cave, cave);
+    assert_eq!(cave_info_account.deposited_amount, 10 * ONE_DEMO_TOKEN);
+    assert!(cave_info_account.created_at > 0);
 
     // Construct and send unlock instruction
     match program
         .request()
         .accounts(token_cave::accounts::Unlock {
             cave_info,
-            cave,
-            mint: mint_key.pubkey(),
             depositor: user.keypair.pubkey(),
-            depositor_token_account: user.ata,
+            config: get_config_pda(),
         })
-        .args(token_cave::instruction::Unlock)
+        .args(token_cave::instruction::Unlock { duress_salt: None })
         .signer(&*user.keypair)
         .payer(user.keypair.clone())
         .send() {
@@ -180,8 +222,12 @@ fn test_deposit_unlock_withdraw() {
             cave,
             mint: mint_key.pubkey(),
             depositor: user.keypair.pubkey(),
+            rent_payer: user.keypair.pubkey(),
             depositor_token_account: user.ata,
             token_program: TOKEN_PROGRAM_ID,
+            config: get_config_pda(),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
         })
         .args(token_cave::instruction::Withdraw)
         .signer(&*user.keypair)
@@ -196,8 +242,12 @@ fn test_deposit_unlock_withdraw() {
             cave,
             mint: mint_key.pubkey(),
             depositor: user.keypair.pubkey(),
+            rent_payer: user.keypair.pubkey(),
             depositor_token_account: user.ata,
             token_program: TOKEN_PROGRAM_ID,
+            config: get_config_pda(),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
         })
         .args(token_cave::instruction::Withdraw)
         .payer(user.keypair.clone())
@@ -245,6 +295,13 @@ fn test_deposit_unlock_abort() {
             .unwrap_or("FAILED TO INITIALIZE MINT ACCOUNT".to_string())
     );
 
+    // Initialize program config
+    println!(
+        "initialize config tx signature: {}",
+        initialize_config_account(&dev_key, &program)
+            .unwrap_or("FAILED TO INITIALIZE CONFIG".to_string())
+    );
+
     // Get funded user and backup
     let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
         .expect("failed to get funded user");
@@ -283,15 +340,22 @@ fn test_deposit_unlock_abort() {
             cave,
             mint: mint_key.pubkey(),
             depositor: user.keypair.pubkey(),
+            payer: user.keypair.pubkey(),
             depositor_token_account: user.ata,
             token_program: TOKEN_PROGRAM_ID,
             system_program: system_program::ID,
             rent: anchor_client::solana_sdk::rent::Rent::id(),
+            config: get_config_pda(),
+            fee_override: get_fee_override_pda(&mint_key.pubkey()),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
         })
         .args(token_cave::instruction::Initialize {
             backup_address: Some(backup.keypair.pubkey()),
             deposit_amount: 10 * ONE_DEMO_TOKEN,
             timelock_duration: TEST_TIMELOCK_DURATION,
+            timelock_unit: TimelockUnit::UnixTimestamp,
+            withdraw_window: None,
         })
         .signer(&*user.keypair)
         .payer(user.keypair.clone())
@@ -335,18 +399,20 @@ fn test_deposit_unlock_abort() {
         i64::MIN,
     );
     assert!(!cave_info_account.unlocking);
+    assert_eq!(cave_info_account.mint, mint_key.pubkey());
+    assert_eq!(cave_info_account.cave, cave);
+    assert_eq!(cave_info_account.deposited_amount, 10 * ONE_DEMO_TOKEN);
+    assert!(cave_info_account.created_at > 0);
 
     // Construct and send unlock instruction
     match program
         .request()
         .accounts(token_cave::accounts::Unlock {
             cave_info,
-            cave,
-            mint: mint_key.pubkey(),
             depositor: user.keypair.pubkey(),
-            depositor_token_account: user.ata,
+            config: get_config_pda(),
         })
-        .args(token_cave::instruction::Unlock)
+        .args(token_cave::instruction::Unlock { duress_salt: None })
         .signer(&*user.keypair)
         .payer(user.keypair.clone())
         .send() {
@@ -362,6 +428,10 @@ fn test_deposit_unlock_abort() {
     assert!(cave_info_account_post_unlock.unlocking);
 
     // Construct and send withdraw instruction (too early, and then on time)
+    // The backup gets all of the cave's rent
+    let backup_lamports = solana_client.get_balance(&backup.keypair.pubkey())
+        .expect("failed to get balance");
+
     match program
         .request()
         .accounts(token_cave::accounts::Abort {
@@ -372,7 +442,8 @@ fn test_deposit_unlock_abort() {
             depositor_token_account: user.ata,
             token_program: TOKEN_PROGRAM_ID,
             backup: backup.keypair.pubkey(),
-            backup_spl_account: backup.ata,
+            backup_destination: get_backup_destination(backup.ata, user.keypair.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
         })
         .args(token_cave::instruction::Abort)
         .signer(&*user.keypair)
@@ -399,7 +470,3019 @@ fn test_deposit_unlock_abort() {
             .parse::<u64>()
             .unwrap(),
     );
+    let cave_rent = solana_client.get_minimum_balance_for_rent_exemption(CAVE_INFO_SIZE).unwrap()
+        + solana_client.get_minimum_balance_for_rent_exemption(TokenAccount::LEN).unwrap();
+    assert_eq!(
+        backup_lamports + cave_rent,
+        solana_client.get_balance(&backup.keypair.pubkey()).expect("failed to get balance"),
+    );
+
+}
+
+#[test]
+fn test_verify_lock() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Get funded user and backup
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let backup: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+
+    let (cave, cave_info) = initialize_cave(
+        &program,
+        &mint_key,
+        &user,
+        Some(backup.keypair.pubkey()),
+        10 * ONE_DEMO_TOKEN,
+    );
+
+    // Lock satisfies the requirements
+    let verify_lock = |min_amount: u64, min_lock_duration: u32| program
+        .request()
+        .accounts(token_cave::accounts::VerifyLock {
+            cave,
+            cave_info,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+        })
+        .args(token_cave::instruction::VerifyLock {
+            min_amount,
+            min_lock_duration,
+        })
+        .send();
+    match verify_lock(10 * ONE_DEMO_TOKEN, TEST_TIMELOCK_DURATION) {
+        Ok(sig) => println!("verify lock tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+
+    // Not enough tokens or not locked for long enough
+    verify_lock(10 * ONE_DEMO_TOKEN + 1, TEST_TIMELOCK_DURATION)
+        .expect_err("should have failed");
+    verify_lock(10 * ONE_DEMO_TOKEN, TEST_TIMELOCK_DURATION + 1)
+        .expect_err("should have failed");
+
+    // Wrong depositor
+    program
+        .request()
+        .accounts(token_cave::accounts::VerifyLock {
+            cave,
+            cave_info,
+            mint: mint_key.pubkey(),
+            depositor: backup.keypair.pubkey(),
+        })
+        .args(token_cave::instruction::VerifyLock {
+            min_amount: 0,
+            min_lock_duration: 0,
+        })
+        .send()
+        .expect_err("should have failed");
+
+    // Unlocking caves don't count as locked
+    match program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("cave unlock tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    verify_lock(0, 0)
+        .expect_err("should have failed");
+}
+
+#[test]
+fn test_strategy_deposit_unlock_withdraw() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+    let strategy_program: Program = Client::new_with_options(
+        Cluster::Localnet,
+        Rc::new(Keypair::from_bytes(&dev_key.to_bytes()).unwrap()),
+        CommitmentConfig::processed(),
+    ).program(mock_strategy::ID);
+
+    // Get funded user
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+
+    // Get strategy PDAs, and initialize the pool if this is the first run
+    let (pool, _) = Pubkey::find_program_address(
+        &[b"pool", mint_key.pubkey().as_ref()],
+        &mock_strategy::ID,
+    );
+    let (vault, _) = Pubkey::find_program_address(
+        &[b"vault", mint_key.pubkey().as_ref()],
+        &mock_strategy::ID,
+    );
+    if solana_client.get_account(&pool).is_err() {
+        match strategy_program
+            .request()
+            .accounts(mock_strategy::accounts::InitializePool {
+                pool,
+                vault,
+                mint: mint_key.pubkey(),
+                payer: dev_key.pubkey(),
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: system_program::ID,
+                rent: anchor_client::solana_sdk::rent::Rent::id(),
+            })
+            .args(mock_strategy::instruction::InitializePool)
+            .send() {
+                Ok(sig) => println!("initialize pool tx signature: {sig}"),
+                Err(e) => panic!("{e:#?}"),
+        };
+    }
+
+    // Only the config authority can whitelist the strategy
+    program
+        .request()
+        .accounts(token_cave::accounts::SetStrategies {
+            config: get_config_pda(),
+            authority: user.keypair.pubkey(),
+        })
+        .args(token_cave::instruction::SetStrategies {
+            strategies: vec![mock_strategy::ID],
+        })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send()
+        .expect_err("should have failed");
+    match program
+        .request()
+        .accounts(token_cave::accounts::SetStrategies {
+            config: get_config_pda(),
+            authority: dev_key.pubkey(),
+        })
+        .args(token_cave::instruction::SetStrategies {
+            strategies: vec![mock_strategy::ID],
+        })
+        .send() {
+            Ok(sig) => println!("set strategies tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    let config: ProgramConfig = program
+        .account(get_config_pda())
+        .unwrap();
+    assert_eq!(config.strategies, vec![mock_strategy::ID]);
+
+    let (cave, cave_info) = initialize_cave(
+        &program,
+        &mint_key,
+        &user,
+        None,
+        10 * ONE_DEMO_TOKEN,
+    );
+    let (position, _) = Pubkey::find_program_address(
+        &[b"position", pool.as_ref(), cave_info.as_ref()],
+        &mock_strategy::ID,
+    );
+    let strategy_accounts = vec![
+        AccountMeta::new(pool, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(position, false),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+
+    // Programs that aren't whitelisted are refused
+    program
+        .request()
+        .accounts(token_cave::accounts::DepositToStrategy {
+            cave,
+            cave_info,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            depositor_token_account: user.ata,
+            strategy_program: mock_oracle::ID,
+            config: get_config_pda(),
+        })
+        .accounts(strategy_accounts.clone())
+        .args(token_cave::instruction::DepositToStrategy {
+            amount: 8 * ONE_DEMO_TOKEN,
+        })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send()
+        .expect_err("should have failed");
+
+    // Deploy 8 of the 10 tokens to the strategy
+    match program
+        .request()
+        .accounts(token_cave::accounts::DepositToStrategy {
+            cave,
+            cave_info,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            depositor_token_account: user.ata,
+            strategy_program: mock_strategy::ID,
+            config: get_config_pda(),
+        })
+        .accounts(strategy_accounts.clone())
+        .accounts(AccountMeta::new_readonly(system_program::ID, false))
+        .args(token_cave::instruction::DepositToStrategy {
+            amount: 8 * ONE_DEMO_TOKEN,
+        })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("deposit to strategy tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(
+        2 * ONE_DEMO_TOKEN,
+        solana_client.get_token_account_balance(&cave)
+            .expect("failed to get cave balance")
+            .amount
+            .parse::<u64>()
+            .unwrap(),
+    );
+
+    // Simulate yield by minting straight into the strategy vault. Other
+    // runs of this test share the pool, so only check that yield accrued.
+    let yield_ix: Instruction = anchor_spl::token::spl_token::instruction::mint_to(
+        &TOKEN_PROGRAM_ID,
+        &mint_key.pubkey(),
+        &vault,
+        &dev_key.pubkey(),
+        &[&dev_key.pubkey()],
+        ONE_DEMO_TOKEN,
+    ).expect("unable to create mint transaction");
+    solana_client.send_and_confirm_transaction(&Transaction::new_signed_with_payer(
+        &[yield_ix],
+        Some(&dev_key.pubkey()),
+        &[&dev_key],
+        solana_client.get_latest_blockhash().expect("failed to get lastest blockhash")
+    )).expect("failed to mint yield");
+
+    // Unlock and withdraw, redeeming from the strategy
+    match program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("cave unlock tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    std::thread::sleep(Duration::from_secs(1 + TEST_TIMELOCK_DURATION as u64));
+    match program
+        .request()
+        .accounts(token_cave::accounts::Withdraw {
+            cave_info,
+            cave,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            rent_payer: user.keypair.pubkey(),
+            depositor_token_account: user.ata,
+            token_program: TOKEN_PROGRAM_ID,
+            config: get_config_pda(),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .accounts(AccountMeta::new_readonly(mock_strategy::ID, false))
+        .accounts(strategy_accounts)
+        .args(token_cave::instruction::Withdraw)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("withdraw tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+
+    // Verify the depositor received principal and yield
+    assert!(
+        100 * ONE_DEMO_TOKEN < solana_client.get_token_account_balance(&user.ata)
+            .expect("failed to get ata balance")
+            .amount
+            .parse::<u64>()
+            .unwrap(),
+    );
+}
+
+#[test]
+fn test_stake_deposit_unlock_withdraw() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Get funded user and backup
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let backup: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+
+    // Create a stake account staked and withdrawable by the user,
+    // delegated to the test validator
+    let stake_account = Keypair::generate(&mut OsRng);
+    let vote_account: Pubkey = solana_client.get_vote_accounts()
+        .expect("failed to get vote accounts")
+        .current[0]
+        .vote_pubkey
+        .parse()
+        .unwrap();
+    let create_stake_ixs: Vec<Instruction> = stake::instruction::create_account_and_delegate_stake(
+        &dev_key.pubkey(),
+        &stake_account.pubkey(),
+        &vote_account,
+        &stake::state::Authorized::auto(&user.keypair.pubkey()),
+        &stake::state::Lockup::default(),
+        LAMPORTS_PER_SOL,
+    );
+    println!(
+        "create_stake_tx signature: {}",
+        solana_client.send_and_confirm_transaction(&Transaction::new_signed_with_payer(
+            &create_stake_ixs,
+            Some(&dev_key.pubkey()),
+            &[&dev_key, &stake_account, &*user.keypair],
+            solana_client.get_latest_blockhash().expect("failed to get lastest blockhash")
+        )).expect("failed to create stake account")
+    );
+
+    // Lock the stake account in a cave
+    let (cave_info, _) = Pubkey::find_program_address(
+        &[stake_account.pubkey().as_ref()],
+        &PROGRAM_ID,
+    );
+    match program
+        .request()
+        .accounts(token_cave::accounts::InitializeStake {
+            stake_account: stake_account.pubkey(),
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            payer: user.keypair.pubkey(),
+            clock: anchor_client::solana_sdk::sysvar::clock::ID,
+            stake_program: stake::program::ID,
+            system_program: system_program::ID,
+            config: get_config_pda(),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::InitializeStake {
+            backup_address: Some(backup.keypair.pubkey()),
+            timelock_duration: TEST_TIMELOCK_DURATION,
+            timelock_unit: TimelockUnit::UnixTimestamp,
+            withdraw_window: None,
+        })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("stake deposit tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    let cave_info_account: CaveInfo = program
+        .account(cave_info)
+        .unwrap();
+    assert_eq!(cave_info_account.kind, CaveKind::Stake);
+    assert_eq!(cave_info_account.cave, stake_account.pubkey());
+    assert_eq!(cave_info_account.deposited_amount, LAMPORTS_PER_SOL);
+
+    // The user no longer controls the stake account
+    let authorize_backup_tx = || Transaction::new_signed_with_payer(
+        &[stake::instruction::authorize(
+            &stake_account.pubkey(),
+            &user.keypair.pubkey(),
+            &backup.keypair.pubkey(),
+            stake::state::StakeAuthorize::Withdrawer,
+            None,
+        )],
+        Some(&user.keypair.pubkey()),
+        &[&*user.keypair],
+        solana_client.get_latest_blockhash().expect("failed to get lastest blockhash")
+    );
+    solana_client.send_and_confirm_transaction(&authorize_backup_tx())
+        .expect_err("should have failed");
+
+    // Unlock, wait, and withdraw
+    match program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("stake cave unlock tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    std::thread::sleep(Duration::from_secs(1 + TEST_TIMELOCK_DURATION as u64));
+    match program
+        .request()
+        .accounts(token_cave::accounts::WithdrawStake {
+            stake_account: stake_account.pubkey(),
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            rent_payer: user.keypair.pubkey(),
+            clock: anchor_client::solana_sdk::sysvar::clock::ID,
+            stake_program: stake::program::ID,
+            config: get_config_pda(),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::WithdrawStake)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("stake withdraw tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+
+    // The user controls the stake account again
+    solana_client.send_and_confirm_transaction(&authorize_backup_tx())
+        .expect("user should be the withdraw authority");
+}
+
+#[test]
+fn test_basket_deposit_unlock_abort() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Get funded user and backup
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let backup: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+
+    // Create a second mint and fund the user with it. The backup never
+    // held it, so the abort creates its token account.
+    let second_mint_key = Keypair::generate(&mut OsRng);
+    initialize_mint_account(&dev_key, &second_mint_key, &solana_client)
+        .expect("failed to initialize second mint");
+    let user_second_ata: Pubkey = spl_associated_token_account::get_associated_token_address(
+        &user.keypair.pubkey(),
+        &second_mint_key.pubkey(),
+    );
+    let backup_second_ata: Pubkey = spl_associated_token_account::get_associated_token_address(
+        &backup.keypair.pubkey(),
+        &second_mint_key.pubkey(),
+    );
+    solana_client.send_and_confirm_transaction(&Transaction::new_signed_with_payer(
+        &[
+            spl_associated_token_account::instruction::create_associated_token_account(
+                &dev_key.pubkey(),
+                &user.keypair.pubkey(),
+                &second_mint_key.pubkey(),
+            ),
+            anchor_spl::token::spl_token::instruction::mint_to(
+                &TOKEN_PROGRAM_ID,
+                &second_mint_key.pubkey(),
+                &user_second_ata,
+                &dev_key.pubkey(),
+                &[&dev_key.pubkey()],
+                100 * ONE_DEMO_TOKEN,
+            ).expect("unable to create mint transaction"),
+        ],
+        Some(&dev_key.pubkey()),
+        &[&dev_key],
+        solana_client.get_latest_blockhash().expect("failed to get lastest blockhash")
+    )).expect("failed to fund user with second mint");
 
+    // Create the basket
+    let basket = Keypair::generate(&mut OsRng).pubkey();
+    let (cave_info, _) = Pubkey::find_program_address(
+        &[b"basket", basket.as_ref()],
+        &PROGRAM_ID,
+    );
+    match program
+        .request()
+        .accounts(token_cave::accounts::InitializeBasket {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            payer: user.keypair.pubkey(),
+            system_program: system_program::ID,
+            config: get_config_pda(),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::InitializeBasket {
+            basket,
+            backup_address: Some(backup.keypair.pubkey()),
+            timelock_duration: TEST_TIMELOCK_DURATION,
+            timelock_unit: TimelockUnit::UnixTimestamp,
+            withdraw_window: None,
+        })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("initialize basket tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+
+    // Only the basket's rent payer can fund its token accounts
+    program
+        .request()
+        .accounts(token_cave::accounts::AddToBasket {
+            basket_cave: Pubkey::find_program_address(
+                &[cave_info.as_ref(), mint_key.pubkey().as_ref()],
+                &PROGRAM_ID,
+            ).0,
+            cave_info,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            payer: backup.keypair.pubkey(),
+            depositor_token_account: user.ata,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: anchor_client::solana_sdk::rent::Rent::id(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::AddToBasket {
+            deposit_amount: 10 * ONE_DEMO_TOKEN,
+        })
+        .signer(&*user.keypair)
+        .signer(&*backup.keypair)
+        .payer(user.keypair.clone())
+        .send()
+        .expect_err("should have failed");
+
+    // Add both mints to the basket
+    let mut basket_caves = vec![];
+    for (mint, depositor_token_account) in [
+        (mint_key.pubkey(), user.ata),
+        (second_mint_key.pubkey(), user_second_ata),
+    ] {
+        let (basket_cave, _) = Pubkey::find_program_address(
+            &[cave_info.as_ref(), mint.as_ref()],
+            &PROGRAM_ID,
+        );
+        match program
+            .request()
+            .accounts(token_cave::accounts::AddToBasket {
+                basket_cave,
+                cave_info,
+                mint,
+                depositor: user.keypair.pubkey(),
+                payer: user.keypair.pubkey(),
+                depositor_token_account,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: system_program::ID,
+                rent: anchor_client::solana_sdk::rent::Rent::id(),
+                config: get_config_pda(),
+            })
+            .args(token_cave::instruction::AddToBasket {
+                deposit_amount: 10 * ONE_DEMO_TOKEN,
+            })
+            .signer(&*user.keypair)
+            .payer(user.keypair.clone())
+            .send() {
+                Ok(sig) => println!("add to basket tx signature: {sig}"),
+                Err(e) => panic!("{e:#?}"),
+        };
+        basket_caves.push(basket_cave);
+    }
+    let cave_info_account: CaveInfo = program
+        .account(cave_info)
+        .unwrap();
+    assert_eq!(cave_info_account.kind, CaveKind::Basket);
+    assert_eq!(cave_info_account.basket_size, 2);
+
+    // Unlock the whole basket and abort to the backup
+    match program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("basket unlock tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    let abort = |remaining_accounts: Vec<AccountMeta>| program
+        .request()
+        .accounts(token_cave::accounts::AbortBasket {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            backup: backup.keypair.pubkey(),
+            backup_destination: get_backup_destination(backup.ata, user.keypair.pubkey()),
+            token_program: TOKEN_PROGRAM_ID,
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .accounts(remaining_accounts)
+        .args(token_cave::instruction::AbortBasket)
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send();
+
+    // Every mint must be swept, each to the backup's token account for it
+    abort(vec![
+        AccountMeta::new(basket_caves[0], false),
+        AccountMeta::new(backup.ata, false),
+        AccountMeta::new_readonly(mint_key.pubkey(), false),
+    ]).expect_err("should have failed");
+    abort(vec![
+        AccountMeta::new(basket_caves[0], false),
+        AccountMeta::new(backup.ata, false),
+        AccountMeta::new_readonly(mint_key.pubkey(), false),
+        AccountMeta::new(basket_caves[1], false),
+        AccountMeta::new(backup.ata, false),
+        AccountMeta::new_readonly(second_mint_key.pubkey(), false),
+    ]).expect_err("should have failed");
+    match abort(vec![
+        AccountMeta::new(basket_caves[0], false),
+        AccountMeta::new(backup.ata, false),
+        AccountMeta::new_readonly(mint_key.pubkey(), false),
+        AccountMeta::new(basket_caves[1], false),
+        AccountMeta::new(backup_second_ata, false),
+        AccountMeta::new_readonly(second_mint_key.pubkey(), false),
+    ]) {
+        Ok(sig) => println!("abort basket tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+
+    // Verify the backup received both mints
+    for (backup_token_account, expected) in [
+        (backup.ata, 110 * ONE_DEMO_TOKEN),
+        (backup_second_ata, 10 * ONE_DEMO_TOKEN),
+    ] {
+        assert_eq!(
+            expected,
+            solana_client.get_token_account_balance(&backup_token_account)
+                .expect("failed to get ata balance")
+                .amount
+                .parse::<u64>()
+                .unwrap(),
+        );
+    }
+}
+
+#[test]
+fn test_slot_timelock() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Get funded user
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+
+    // Lock for a number of slots instead of seconds
+    const TEST_TIMELOCK_SLOTS: u32 = 20;
+    let (cave, cave_info) = initialize_cave_with_timelock(
+        &program,
+        &mint_key,
+        &user,
+        None,
+        10 * ONE_DEMO_TOKEN,
+        TEST_TIMELOCK_SLOTS,
+        TimelockUnit::Slot,
+        None,
+    );
+
+    // Unlock, which records the slot of the request
+    match program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("cave unlock tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    let cave_info_account: CaveInfo = program
+        .account(cave_info)
+        .unwrap();
+    assert_eq!(cave_info_account.timelock_unit, TimelockUnit::Slot);
+    let unlock_slot = cave_info_account.unlock_request_time as u64;
+    assert!(unlock_slot <= solana_client.get_slot().unwrap());
+
+    // Withdraw too early, and then once enough slots have passed
+    let withdraw = || program
+        .request()
+        .accounts(token_cave::accounts::Withdraw {
+            cave_info,
+            cave,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            rent_payer: user.keypair.pubkey(),
+            depositor_token_account: user.ata,
+            token_program: TOKEN_PROGRAM_ID,
+            config: get_config_pda(),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::Withdraw)
+        .payer(user.keypair.clone())
+        .send();
+    withdraw().expect_err("should have failed");
+    while solana_client.get_slot().unwrap() <= unlock_slot + TEST_TIMELOCK_SLOTS as u64 {
+        std::thread::sleep(Duration::from_millis(400));
+    }
+    match withdraw() {
+        Ok(sig) => println!("withdraw tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(
+        100 * ONE_DEMO_TOKEN,
+        solana_client.get_token_account_balance(&user.ata)
+            .expect("failed to get ata balance")
+            .amount
+            .parse::<u64>()
+            .unwrap(),
+    );
+}
+
+#[test]
+fn test_withdraw_window_expiry() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Get funded user
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+
+    // The cave can only be withdrawn within a short window after maturity
+    const TEST_WITHDRAW_WINDOW: u32 = 2;
+    let (cave, cave_info) = initialize_cave_with_timelock(
+        &program,
+        &mint_key,
+        &user,
+        None,
+        10 * ONE_DEMO_TOKEN,
+        TEST_TIMELOCK_DURATION,
+        TimelockUnit::UnixTimestamp,
+        Some(TEST_WITHDRAW_WINDOW),
+    );
+    let unlock = || program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send();
+    let withdraw = || program
+        .request()
+        .accounts(token_cave::accounts::Withdraw {
+            cave_info,
+            cave,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            rent_payer: user.keypair.pubkey(),
+            depositor_token_account: user.ata,
+            token_program: TOKEN_PROGRAM_ID,
+            config: get_config_pda(),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::Withdraw)
+        .payer(user.keypair.clone())
+        .send();
+
+    // Unlock, and a second unlock is rejected while the first is active
+    match unlock() {
+        Ok(sig) => println!("cave unlock tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    unlock().expect_err("should have failed");
+
+    // Let the window pass, after which withdraw fails
+    std::thread::sleep(Duration::from_secs(
+        2 + (TEST_TIMELOCK_DURATION + TEST_WITHDRAW_WINDOW) as u64
+    ));
+    withdraw().expect_err("should have failed");
+
+    // A new unlock with a full wait is required
+    match unlock() {
+        Ok(sig) => println!("second cave unlock tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    withdraw().expect_err("should have failed");
+    std::thread::sleep(Duration::from_secs(1 + TEST_TIMELOCK_DURATION as u64));
+    match withdraw() {
+        Ok(sig) => println!("withdraw tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(
+        100 * ONE_DEMO_TOKEN,
+        solana_client.get_token_account_balance(&user.ata)
+            .expect("failed to get ata balance")
+            .amount
+            .parse::<u64>()
+            .unwrap(),
+    );
+}
+
+#[test]
+fn test_withdraw_tickets() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Get funded user, payee and backup
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let payee: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let backup: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+
+    let (cave, cave_info) = initialize_cave(
+        &program,
+        &mint_key,
+        &user,
+        Some(backup.keypair.pubkey()),
+        10 * ONE_DEMO_TOKEN,
+    );
+
+    // Schedule several withdrawals at once
+    let get_ticket = |id: u64| Pubkey::find_program_address(
+        &[b"ticket", cave_info.as_ref(), &id.to_le_bytes()],
+        &PROGRAM_ID,
+    ).0;
+    let request_ticket = |id: u64, amount: u64| program
+        .request()
+        .accounts(token_cave::accounts::RequestWithdrawTicket {
+            ticket: get_ticket(id),
+            cave,
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            destination: payee.ata,
+            system_program: system_program::ID,
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::RequestWithdrawTicket { amount })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send();
+    for (id, amount) in [(0, 3), (1, 2), (2, 4)] {
+        match request_ticket(id, amount * ONE_DEMO_TOKEN) {
+            Ok(sig) => println!("request ticket tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+        };
+    }
+
+    // Only one token is left unreserved
+    request_ticket(3, 2 * ONE_DEMO_TOKEN).expect_err("should have failed");
+    let cave_info_account: CaveInfo = program
+        .account(cave_info)
+        .unwrap();
+    assert_eq!(cave_info_account.next_ticket_id, 3);
+    assert_eq!(cave_info_account.open_tickets, 3);
+    assert_eq!(cave_info_account.ticketed_amount, 9 * ONE_DEMO_TOKEN);
+    assert!(!cave_info_account.unlocking);
+
+    // The cave can't be aborted while tickets are pending
+    match program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("cave unlock tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    let abort = || program
+        .request()
+        .accounts(token_cave::accounts::Abort {
+            cave_info,
+            cave,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            depositor_token_account: user.ata,
+            token_program: TOKEN_PROGRAM_ID,
+            backup: backup.keypair.pubkey(),
+            backup_destination: get_backup_destination(backup.ata, user.keypair.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::Abort)
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send();
+    abort().expect_err("should have failed");
+
+    // Cancel the second ticket and abort the third one to the backup
+    match program
+        .request()
+        .accounts(token_cave::accounts::CancelWithdrawTicket {
+            ticket: get_ticket(1),
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            rent_payer: user.keypair.pubkey(),
+        })
+        .args(token_cave::instruction::CancelWithdrawTicket)
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("cancel ticket tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    match program
+        .request()
+        .accounts(token_cave::accounts::AbortWithdrawTicket {
+            ticket: get_ticket(2),
+            cave,
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            backup: backup.keypair.pubkey(),
+            mint: mint_key.pubkey(),
+            backup_destination: get_backup_destination(backup.ata, user.keypair.pubkey()),
+            token_program: TOKEN_PROGRAM_ID,
+        })
+        .args(token_cave::instruction::AbortWithdrawTicket)
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("abort ticket tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+
+    // The first ticket can be paid out by anyone once it matures
+    let withdraw_ticket = || program
+        .request()
+        .accounts(token_cave::accounts::WithdrawTicketFunds {
+            ticket: get_ticket(0),
+            cave,
+            cave_info,
+            rent_payer: user.keypair.pubkey(),
+            destination: payee.ata,
+            token_program: TOKEN_PROGRAM_ID,
+            config: get_config_pda(),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+        })
+        .args(token_cave::instruction::WithdrawTicket)
+        .send();
+    withdraw_ticket().expect_err("should have failed");
+    std::thread::sleep(Duration::from_secs(1 + TEST_TIMELOCK_DURATION as u64));
+    match withdraw_ticket() {
+        Ok(sig) => println!("withdraw ticket tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+
+    let balance = |ata: &Pubkey| solana_client.get_token_account_balance(ata)
+        .expect("failed to get ata balance")
+        .amount
+        .parse::<u64>()
+        .unwrap();
+    assert_eq!(balance(&payee.ata), 103 * ONE_DEMO_TOKEN);
+    assert_eq!(balance(&backup.ata), 104 * ONE_DEMO_TOKEN);
+    assert_eq!(balance(&cave), 3 * ONE_DEMO_TOKEN);
+    let cave_info_account: CaveInfo = program
+        .account(cave_info)
+        .unwrap();
+    assert_eq!(cave_info_account.open_tickets, 0);
+    assert_eq!(cave_info_account.ticketed_amount, 0);
+    assert_eq!(cave_info_account.deposited_amount, 3 * ONE_DEMO_TOKEN);
+
+    // With no tickets left, the rest can be aborted
+    match abort() {
+        Ok(sig) => println!("abort unlock tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(balance(&backup.ata), 107 * ONE_DEMO_TOKEN);
+}
+
+#[test]
+fn test_duress_unlock_withdraw() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Get funded user and backup
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let backup: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+
+    let (cave, cave_info) = initialize_cave(
+        &program,
+        &mint_key,
+        &user,
+        Some(backup.keypair.pubkey()),
+        10 * ONE_DEMO_TOKEN,
+    );
+
+    // Register a duress key. The cave only stores its salted hash.
+    let duress_key = Keypair::generate(&mut OsRng);
+    let salt: [u8; 32] = rand::random();
+    let commitment = duress_commitment(&duress_key.pubkey(), &salt);
+    match program
+        .request()
+        .accounts(token_cave::accounts::SetDuressKey {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+        })
+        .args(token_cave::instruction::SetDuressKey {
+            duress_commitment: Some(commitment),
+        })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("set duress key tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    let cave_info_account: CaveInfo = program
+        .account(cave_info)
+        .unwrap();
+    assert_eq!(cave_info_account.duress_commitment, Some(commitment));
+
+    // The duress key can't unlock without the right salt
+    let unlock = |duress_salt: Option<[u8; 32]>| program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: duress_key.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt })
+        .signer(&duress_key)
+        .payer(user.keypair.clone())
+        .send();
+    unlock(None).expect_err("should have failed");
+    unlock(Some([0; 32])).expect_err("should have failed");
+
+    // Unlock under duress
+    match unlock(Some(salt)) {
+        Ok(sig) => println!("cave duress unlock tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    let cave_info_account: CaveInfo = program
+        .account(cave_info)
+        .unwrap();
+    assert!(cave_info_account.unlocking);
+    assert!(cave_info_account.is_duress_unlock());
+
+    // Once mature, withdraw needs the backup's token account and pays it
+    std::thread::sleep(Duration::from_secs(1 + TEST_TIMELOCK_DURATION as u64));
+    let withdraw = |remaining_accounts: Vec<AccountMeta>| program
+        .request()
+        .accounts(token_cave::accounts::Withdraw {
+            cave_info,
+            cave,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            rent_payer: user.keypair.pubkey(),
+            depositor_token_account: user.ata,
+            token_program: TOKEN_PROGRAM_ID,
+            config: get_config_pda(),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .accounts(remaining_accounts)
+        .args(token_cave::instruction::Withdraw)
+        .payer(user.keypair.clone())
+        .send();
+    withdraw(vec![]).expect_err("should have failed");
+    withdraw(vec![AccountMeta::new(user.ata, false)]).expect_err("should have failed");
+    let backup_ata_lamports = solana_client.get_balance(&backup.ata)
+        .expect("failed to get balance");
+    match withdraw(vec![AccountMeta::new(backup.ata, false)]) {
+        Ok(sig) => println!("withdraw tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+
+    let balance = |ata: &Pubkey| solana_client.get_token_account_balance(ata)
+        .expect("failed to get ata balance")
+        .amount
+        .parse::<u64>()
+        .unwrap();
+    assert_eq!(balance(&user.ata), 90 * ONE_DEMO_TOKEN);
+    assert_eq!(balance(&backup.ata), 110 * ONE_DEMO_TOKEN);
+
+    // The cave's rent went back to the rent payer, not to the backup's
+    // token account
+    assert_eq!(
+        solana_client.get_balance(&backup.ata).expect("failed to get balance"),
+        backup_ata_lamports,
+    );
+}
+
+#[test]
+fn test_config_authorities() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // The config was created by the upgrade authority
+    let config: ProgramConfig = program
+        .account(get_config_pda())
+        .unwrap();
+    assert_eq!(config.authority, dev_key.pubkey());
+    assert_eq!(config.pause_authority, dev_key.pubkey());
+
+    // Nobody else can create, update or pause
+    let (program_data, _) = Pubkey::find_program_address(
+        &[PROGRAM_ID.as_ref()],
+        &bpf_loader_upgradeable::ID,
+    );
+    let stranger = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    program
+        .request()
+        .accounts(token_cave::accounts::InitializeConfig {
+            config: get_config_pda(),
+            authority: stranger.keypair.pubkey(),
+            program: PROGRAM_ID,
+            program_data,
+            system_program: system_program::ID,
+        })
+        .args(token_cave::instruction::InitializeConfig {
+            pause_authority: stranger.keypair.pubkey(),
+            withdraw_allowed_while_paused: false,
+        })
+        .signer(&*stranger.keypair)
+        .payer(stranger.keypair.clone())
+        .send()
+        .expect_err("should have failed");
+    program
+        .request()
+        .accounts(token_cave::accounts::UpdateConfig {
+            config: get_config_pda(),
+            authority: stranger.keypair.pubkey(),
+        })
+        .args(token_cave::instruction::UpdateConfig {
+            new_authority: stranger.keypair.pubkey(),
+            pause_authority: stranger.keypair.pubkey(),
+            withdraw_allowed_while_paused: false,
+        })
+        .signer(&*stranger.keypair)
+        .payer(stranger.keypair.clone())
+        .send()
+        .expect_err("should have failed");
+    program
+        .request()
+        .accounts(token_cave::accounts::SetPause {
+            config: get_config_pda(),
+            pause_authority: stranger.keypair.pubkey(),
+        })
+        .args(token_cave::instruction::SetPause { paused: true })
+        .signer(&*stranger.keypair)
+        .payer(stranger.keypair.clone())
+        .send()
+        .expect_err("should have failed");
+    let config: ProgramConfig = program
+        .account(get_config_pda())
+        .unwrap();
+    assert!(!config.paused);
+}
+
+#[test]
+fn test_mint_fee_override() {
+
+    let (dev_key, _, program, solana_client) = get_test_env();
+
+    // Use a fresh mint, so that its fee doesn't affect other tests
+    let fee_mint_key = Keypair::generate(&mut OsRng);
+    initialize_mint_account(&dev_key, &fee_mint_key, &solana_client)
+        .expect("failed to initialize fee mint");
+    let user: User = get_funded_user(&dev_key, &fee_mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let treasury_ata = get_treasury_token_account(&fee_mint_key.pubkey());
+    let create_treasury_ata_tx = Transaction::new_signed_with_payer(
+        &[spl_associated_token_account::instruction::create_associated_token_account(
+            &dev_key.pubkey(),
+            &dev_key.pubkey(),
+            &fee_mint_key.pubkey(),
+        )],
+        Some(&dev_key.pubkey()),
+        &[&dev_key],
+        solana_client.get_latest_blockhash().expect("failed to get lastest blockhash"),
+    );
+    solana_client.send_and_confirm_transaction(&create_treasury_ata_tx)
+        .expect("failed to create treasury ata");
+
+    let set_mint_fee = |fee: FeeSchedule| program
+        .request()
+        .accounts(token_cave::accounts::SetMintFee {
+            fee_override: get_fee_override_pda(&fee_mint_key.pubkey()),
+            config: get_config_pda(),
+            mint: fee_mint_key.pubkey(),
+            authority: dev_key.pubkey(),
+            system_program: system_program::ID,
+        })
+        .args(token_cave::instruction::SetMintFee { fee })
+        .send();
+
+    // Fees above the protocol maximum are rejected
+    set_mint_fee(FeeSchedule {
+        bps: 2_000,
+        flat: 0,
+        max: u64::MAX,
+        timing: FeeTiming::Deposit,
+    }).expect_err("should have failed");
+
+    // A flat fee above the protocol maximum is rejected too
+    set_mint_fee(FeeSchedule {
+        bps: 0,
+        flat: MAX_FLAT_FEE + 1,
+        max: u64::MAX,
+        timing: FeeTiming::Deposit,
+    }).expect_err("should have failed");
+
+    // 5% plus one token, capped at two tokens, on deposit. The total is
+    // capped at `MAX_FEE_BPS` of the deposit, so it's one token.
+    match set_mint_fee(FeeSchedule {
+        bps: 500,
+        flat: ONE_DEMO_TOKEN,
+        max: 2 * ONE_DEMO_TOKEN,
+        timing: FeeTiming::Deposit,
+    }) {
+        Ok(sig) => println!("set mint fee tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    let (cave, cave_info) = initialize_cave(
+        &program,
+        &fee_mint_key,
+        &user,
+        None,
+        10 * ONE_DEMO_TOKEN,
+    );
+    let balance = |ata: &Pubkey| solana_client.get_token_account_balance(ata)
+        .expect("failed to get ata balance")
+        .amount
+        .parse::<u64>()
+        .unwrap();
+    assert_eq!(balance(&treasury_ata), ONE_DEMO_TOKEN);
+    assert_eq!(balance(&cave), 9 * ONE_DEMO_TOKEN);
+
+    // 10%, capped at half a token, on withdraw
+    match set_mint_fee(FeeSchedule {
+        bps: 1_000,
+        flat: 0,
+        max: ONE_DEMO_TOKEN / 2,
+        timing: FeeTiming::Withdraw,
+    }) {
+        Ok(sig) => println!("set mint fee tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    let unlock_and_withdraw = |cave: Pubkey, cave_info: Pubkey| {
+        match program
+            .request()
+            .accounts(token_cave::accounts::Unlock {
+                cave_info,
+                depositor: user.keypair.pubkey(),
+                config: get_config_pda(),
+            })
+            .args(token_cave::instruction::Unlock { duress_salt: None })
+            .signer(&*user.keypair)
+            .payer(user.keypair.clone())
+            .send() {
+                Ok(sig) => println!("cave unlock tx signature: {sig}"),
+                Err(e) => panic!("{e:#?}"),
+        };
+        std::thread::sleep(Duration::from_secs(1 + TEST_TIMELOCK_DURATION as u64));
+        match program
+            .request()
+            .accounts(token_cave::accounts::Withdraw {
+                cave_info,
+                cave,
+                mint: fee_mint_key.pubkey(),
+                depositor: user.keypair.pubkey(),
+                rent_payer: user.keypair.pubkey(),
+                depositor_token_account: user.ata,
+                token_program: TOKEN_PROGRAM_ID,
+                config: get_config_pda(),
+                treasury_token_account: treasury_ata,
+                registry: get_registry_pda(&user.keypair.pubkey()),
+            })
+            .args(token_cave::instruction::Withdraw)
+            .payer(user.keypair.clone())
+            .send() {
+                Ok(sig) => println!("withdraw tx signature: {sig}"),
+                Err(e) => panic!("{e:#?}"),
+        };
+    };
+
+    // The existing cave keeps the schedule it was deposited under
+    unlock_and_withdraw(cave, cave_info);
+    assert_eq!(balance(&treasury_ata), ONE_DEMO_TOKEN);
+    assert_eq!(balance(&user.ata), 99 * ONE_DEMO_TOKEN);
+
+    // A new cave pays the new schedule on withdraw
+    let (cave, cave_info) = initialize_cave(
+        &program,
+        &fee_mint_key,
+        &user,
+        None,
+        10 * ONE_DEMO_TOKEN,
+    );
+    unlock_and_withdraw(cave, cave_info);
+    assert_eq!(balance(&treasury_ata), 3 * ONE_DEMO_TOKEN / 2);
+    assert_eq!(balance(&user.ata), 197 * ONE_DEMO_TOKEN / 2);
+
+    // Clearing the override closes it
+    match program
+        .request()
+        .accounts(token_cave::accounts::ClearMintFee {
+            fee_override: get_fee_override_pda(&fee_mint_key.pubkey()),
+            config: get_config_pda(),
+            authority: dev_key.pubkey(),
+        })
+        .args(token_cave::instruction::ClearMintFee)
+        .send() {
+            Ok(sig) => println!("clear mint fee tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    assert!(solana_client.get_account(&get_fee_override_pda(&fee_mint_key.pubkey())).is_err());
+}
+
+#[test]
+fn test_abort_with_backup_signature() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // The backup has neither SOL nor a token account for the mint, which
+    // the abort creates
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let backup = Keypair::generate(&mut OsRng);
+    let backup_ata = spl_associated_token_account::get_associated_token_address(
+        &backup.pubkey(),
+        &mint_key.pubkey(),
+    );
+
+    // Deposit and unlock, as a thief with the depositor key would
+    let (cave, cave_info) = initialize_cave(
+        &program,
+        &mint_key,
+        &user,
+        Some(backup.pubkey()),
+        10 * ONE_DEMO_TOKEN,
+    );
+    match program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("cave unlock tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+
+    // The backup signs off-chain, and the dev key relays
+    let cave_info_account: CaveInfo = program
+        .account(cave_info)
+        .unwrap();
+    let expiry = unix_now() + 60;
+    let backup_dalek = ed25519_dalek::Keypair::from_bytes(&backup.to_bytes())
+        .expect("failed to convert backup key");
+    let abort_with_signature = |nonce: u64| program
+        .request()
+        .instruction(new_ed25519_instruction(
+            &backup_dalek,
+            &abort_message(&cave_info, nonce, expiry),
+        ))
+        .accounts(token_cave::accounts::AbortWithSignature {
+            cave,
+            cave_info,
+            mint: mint_key.pubkey(),
+            depositor_token_account: user.ata,
+            backup: backup.pubkey(),
+            backup_destination: get_backup_destination(backup_ata, dev_key.pubkey()),
+            instructions_sysvar: anchor_client::solana_sdk::sysvar::instructions::ID,
+            token_program: TOKEN_PROGRAM_ID,
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::AbortWithSignature { nonce, expiry })
+        .send();
+
+    // A signature for another nonce is rejected
+    abort_with_signature(cave_info_account.abort_nonce + 1).expect_err("should have failed");
+    match abort_with_signature(cave_info_account.abort_nonce) {
+        Ok(sig) => println!("abort with signature tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(
+        10 * ONE_DEMO_TOKEN,
+        solana_client.get_token_account_balance(&backup_ata)
+            .expect("failed to get ata balance")
+            .amount
+            .parse::<u64>()
+            .unwrap(),
+    );
+    assert_eq!(solana_client.get_balance(&backup.pubkey()).unwrap(), 0);
+}
+
+/// Current unix timestamp according to the local clock
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+#[test]
+fn test_abort_to_registered_destination() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Get funded user, backup, and the cold wallet the backup prefers
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let backup: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let cold_wallet: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+
+    let (cave, cave_info) = initialize_cave(
+        &program,
+        &mint_key,
+        &user,
+        Some(backup.keypair.pubkey()),
+        10 * ONE_DEMO_TOKEN,
+    );
+
+    // Only the backup can register a destination
+    let set_backup_destination = |signer: &User| program
+        .request()
+        .accounts(token_cave::accounts::SetBackupDestination {
+            cave_info,
+            backup: signer.keypair.pubkey(),
+        })
+        .args(token_cave::instruction::SetBackupDestination {
+            destination: Some(cold_wallet.ata),
+        })
+        .signer(&*signer.keypair)
+        .payer(signer.keypair.clone())
+        .send();
+    set_backup_destination(&user).expect_err("should have failed");
+    match set_backup_destination(&backup) {
+        Ok(sig) => println!("set backup destination tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+
+    match program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("cave unlock tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+
+    // The backup's own token account is no longer accepted
+    let abort = |backup_spl_account: Pubkey| program
+        .request()
+        .accounts(token_cave::accounts::Abort {
+            cave_info,
+            cave,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            depositor_token_account: user.ata,
+            token_program: TOKEN_PROGRAM_ID,
+            backup: backup.keypair.pubkey(),
+            backup_destination: get_backup_destination(backup_spl_account, user.keypair.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::Abort)
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send();
+    abort(backup.ata).expect_err("should have failed");
+    match abort(cold_wallet.ata) {
+        Ok(sig) => println!("abort unlock tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(
+        110 * ONE_DEMO_TOKEN,
+        solana_client.get_token_account_balance(&cold_wallet.ata)
+            .expect("failed to get ata balance")
+            .amount
+            .parse::<u64>()
+            .unwrap(),
+    );
+}
+
+#[test]
+fn test_sponsored_deposit() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Get funded user
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let user_lamports = solana_client.get_balance(&user.keypair.pubkey())
+        .expect("failed to get balance");
+
+    // The dev key sponsors the rent and the transaction fee
+    let (cave, cave_info) = get_cave_pdas(&user.ata);
+    match program
+        .request()
+        .accounts(token_cave::accounts::Initialize {
+            cave_info,
+            cave,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            payer: dev_key.pubkey(),
+            depositor_token_account: user.ata,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: anchor_client::solana_sdk::rent::Rent::id(),
+            config: get_config_pda(),
+            fee_override: get_fee_override_pda(&mint_key.pubkey()),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::Initialize {
+            backup_address: None,
+            deposit_amount: 10 * ONE_DEMO_TOKEN,
+            timelock_duration: TEST_TIMELOCK_DURATION,
+            timelock_unit: TimelockUnit::UnixTimestamp,
+            withdraw_window: None,
+        })
+        .signer(&*user.keypair)
+        .send() {
+            Ok(sig) => println!("sponsored deposit tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+
+    let cave_info_account: CaveInfo = program
+        .account(cave_info)
+        .unwrap();
+    assert_eq!(cave_info_account.depositor, user.keypair.pubkey());
+    assert_eq!(cave_info_account.rent_payer, dev_key.pubkey());
+    assert_eq!(
+        user_lamports,
+        solana_client.get_balance(&user.keypair.pubkey()).expect("failed to get balance"),
+    );
+}
+
+#[test]
+fn test_gift_clawback_and_withdraw() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // The funder gives to a beneficiary who has SOL but no token account
+    let funder: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let beneficiary = Keypair::generate(&mut OsRng);
+    let fund_with_sol_tx: Transaction = system_transaction::transfer(
+        &dev_key,
+        &beneficiary.pubkey(),
+        LAMPORTS_PER_SOL,
+        solana_client.get_latest_blockhash().expect("failed to get lastest blockhash")
+    );
+    solana_client.send_and_confirm_transaction(&fund_with_sol_tx)
+        .expect("failed to fund beneficiary with sol");
+    let beneficiary_ata = spl_associated_token_account::get_associated_token_address(
+        &beneficiary.pubkey(),
+        &mint_key.pubkey(),
+    );
+
+    let initialize_gift = |clawback_deadline: Option<i64>| {
+        let (cave, _) = Pubkey::find_program_address(
+            &[b"gift", funder.ata.as_ref(), beneficiary.pubkey().as_ref()],
+            &PROGRAM_ID,
+        );
+        let (cave_info, _) = Pubkey::find_program_address(&[cave.as_ref()], &PROGRAM_ID);
+        match program
+            .request()
+            .accounts(token_cave::accounts::InitializeGift {
+                cave_info,
+                cave,
+                mint: mint_key.pubkey(),
+                depositor: funder.keypair.pubkey(),
+                payer: funder.keypair.pubkey(),
+                depositor_token_account: funder.ata,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: system_program::ID,
+                rent: anchor_client::solana_sdk::rent::Rent::id(),
+                config: get_config_pda(),
+                fee_override: get_fee_override_pda(&mint_key.pubkey()),
+                treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+                registry: get_registry_pda(&funder.keypair.pubkey()),
+            })
+            .args(token_cave::instruction::InitializeGift {
+                deposit_amount: 10 * ONE_DEMO_TOKEN,
+                beneficiary: beneficiary.pubkey(),
+                timelock_duration: TEST_TIMELOCK_DURATION,
+                timelock_unit: TimelockUnit::UnixTimestamp,
+                withdraw_window: None,
+                clawback_deadline,
+            })
+            .signer(&*funder.keypair)
+            .payer(funder.keypair.clone())
+            .send() {
+                Ok(sig) => println!("gift deposit tx signature: {sig}"),
+                Err(e) => panic!("{e:#?}"),
+        };
+        (cave, cave_info)
+    };
+    let unlock = |cave_info: Pubkey, signer: &Rc<Keypair>| program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: signer.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&**signer)
+        .payer(signer.clone())
+        .send();
+    let clawback = |cave: Pubkey, cave_info: Pubkey| program
+        .request()
+        .accounts(token_cave::accounts::Clawback {
+            cave,
+            cave_info,
+            mint: mint_key.pubkey(),
+            depositor: funder.keypair.pubkey(),
+            depositor_token_account: funder.ata,
+            rent_payer: funder.keypair.pubkey(),
+            token_program: TOKEN_PROGRAM_ID,
+            registry: get_registry_pda(&funder.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::Clawback)
+        .signer(&*funder.keypair)
+        .payer(funder.keypair.clone())
+        .send();
+
+    // The funder can't unlock a gift, the beneficiary can. Before the
+    // deadline the funder can still claw it back.
+    let beneficiary = Rc::new(beneficiary);
+    let (cave, cave_info) = initialize_gift(Some(unix_now() + 600));
+    unlock(cave_info, &funder.keypair).expect_err("should have failed");
+    match unlock(cave_info, &beneficiary) {
+        Ok(sig) => println!("gift unlock tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    match clawback(cave, cave_info) {
+        Ok(sig) => println!("clawback tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    assert!(solana_client.get_account(&cave_info).is_err());
+
+    // Without a clawback right the gift goes to the beneficiary, whose
+    // token account the withdraw creates
+    let (cave, cave_info) = initialize_gift(None);
+    clawback(cave, cave_info).expect_err("should have failed");
+    match unlock(cave_info, &beneficiary) {
+        Ok(sig) => println!("gift unlock tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    std::thread::sleep(Duration::from_secs(1 + TEST_TIMELOCK_DURATION as u64));
+    match program
+        .request()
+        .accounts(token_cave::accounts::WithdrawGift {
+            cave,
+            cave_info,
+            mint: mint_key.pubkey(),
+            beneficiary: beneficiary.pubkey(),
+            beneficiary_token_account: beneficiary_ata,
+            rent_payer: funder.keypair.pubkey(),
+            token_program: TOKEN_PROGRAM_ID,
+            associated_token_program: spl_associated_token_account::ID,
+            system_program: system_program::ID,
+            rent: anchor_client::solana_sdk::rent::Rent::id(),
+            config: get_config_pda(),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&funder.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::WithdrawGift)
+        .signer(&*beneficiary)
+        .payer(beneficiary.clone())
+        .send() {
+            Ok(sig) => println!("gift withdraw tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(
+        10 * ONE_DEMO_TOKEN,
+        solana_client.get_token_account_balance(&beneficiary_ata)
+            .expect("failed to get ata balance")
+            .amount
+            .parse::<u64>()
+            .unwrap(),
+    );
+    assert_eq!(
+        90 * ONE_DEMO_TOKEN,
+        solana_client.get_token_account_balance(&funder.ata)
+            .expect("failed to get ata balance")
+            .amount
+            .parse::<u64>()
+            .unwrap(),
+    );
+}
+
+#[test]
+fn test_htlc_claim() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // The depositor locks funds for a swap counterparty
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let counterparty: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let (cave, cave_info) = initialize_cave(
+        &program,
+        &mint_key,
+        &user,
+        None,
+        10 * ONE_DEMO_TOKEN,
+    );
+
+    let preimage = b"atomic swap secret".to_vec();
+    let set_hashlock = |deadline: i64| program
+        .request()
+        .accounts(token_cave::accounts::SetHashlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+        })
+        .args(token_cave::instruction::SetHashlock {
+            hash: hash(&preimage).to_bytes(),
+            recipient: counterparty.keypair.pubkey(),
+            deadline,
+        })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send();
+
+    // The deadline must leave time to claim once the hashlock has armed
+    set_hashlock(unix_now() + TEST_TIMELOCK_DURATION as i64)
+        .expect_err("should have failed");
+    set_hashlock(unix_now() + TEST_TIMELOCK_DURATION as i64 + HASHLOCK_CLAIM_MARGIN - 60)
+        .expect_err("should have failed");
+    match set_hashlock(unix_now() + 600) {
+        Ok(sig) => println!("set hashlock tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+
+    // The depositor can't unlock before the deadline
+    program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send()
+        .expect_err("should have failed");
+
+    // Only the right preimage releases the funds to the counterparty
+    let claim = |preimage: Vec<u8>| program
+        .request()
+        .accounts(token_cave::accounts::ClaimHtlc {
+            cave,
+            cave_info,
+            mint: mint_key.pubkey(),
+            recipient_token_account: counterparty.ata,
+            rent_payer: user.keypair.pubkey(),
+            token_program: TOKEN_PROGRAM_ID,
+            config: get_config_pda(),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::ClaimHtlc { preimage })
+        .send();
+
+    // Claims only open once the hashlock has served the timelock
+    claim(preimage.clone()).expect_err("should have failed");
+    std::thread::sleep(Duration::from_secs(1 + TEST_TIMELOCK_DURATION as u64));
+    claim(b"wrong secret".to_vec()).expect_err("should have failed");
+    match claim(preimage) {
+        Ok(sig) => println!("claim htlc tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(
+        110 * ONE_DEMO_TOKEN,
+        solana_client.get_token_account_balance(&counterparty.ata)
+            .expect("failed to get ata balance")
+            .amount
+            .parse::<u64>()
+            .unwrap(),
+    );
+    assert!(solana_client.get_account(&cave_info).is_err());
+}
+
+#[test]
+fn test_release_on_price() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+    let oracle_program: Program = Client::new_with_options(
+        Cluster::Localnet,
+        Rc::new(Keypair::from_bytes(&dev_key.to_bytes()).unwrap()),
+        CommitmentConfig::processed(),
+    ).program(mock_oracle::ID);
+
+    // The dev key runs a feed quoting 1.00
+    let feed = Keypair::generate(&mut OsRng);
+    match oracle_program
+        .request()
+        .accounts(mock_oracle::accounts::InitializeFeed {
+            feed: feed.pubkey(),
+            authority: dev_key.pubkey(),
+            system_program: system_program::ID,
+        })
+        .args(mock_oracle::instruction::InitializeFeed { price: 100, expo: -2 })
+        .signer(&feed)
+        .send() {
+            Ok(sig) => println!("initialize feed tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+
+    // Lock until the price reaches 1.50
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let (cave, cave_info) = initialize_cave(
+        &program,
+        &mint_key,
+        &user,
+        None,
+        10 * ONE_DEMO_TOKEN,
+    );
+    match program
+        .request()
+        .accounts(token_cave::accounts::SetPriceCondition {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            feed: feed.pubkey(),
+        })
+        .args(token_cave::instruction::SetPriceCondition {
+            trigger: PriceTrigger::AtOrAbove,
+            threshold: 150,
+        })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("set price condition tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+
+    let release = || program
+        .request()
+        .accounts(token_cave::accounts::ReleaseOnPrice {
+            cave,
+            cave_info,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            rent_payer: user.keypair.pubkey(),
+            depositor_token_account: user.ata,
+            feed: feed.pubkey(),
+            token_program: TOKEN_PROGRAM_ID,
+            config: get_config_pda(),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::ReleaseOnPrice)
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send();
+    let set_price = |price: i64| oracle_program
+        .request()
+        .accounts(mock_oracle::accounts::SetPrice {
+            feed: feed.pubkey(),
+            authority: dev_key.pubkey(),
+        })
+        .args(mock_oracle::instruction::SetPrice { price })
+        .send()
+        .expect("failed to set price");
+
+    // The condition is met, but only counts after a full timelock
+    set_price(200);
+    release().expect_err("should have failed");
+    std::thread::sleep(Duration::from_secs(1 + TEST_TIMELOCK_DURATION as u64));
+
+    // Once armed, the price decides
+    set_price(120);
+    release().expect_err("should have failed");
+    set_price(150);
+    match release() {
+        Ok(sig) => println!("release on price tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(
+        100 * ONE_DEMO_TOKEN,
+        solana_client.get_token_account_balance(&user.ata)
+            .expect("failed to get ata balance")
+            .amount
+            .parse::<u64>()
+            .unwrap(),
+    );
+}
+
+#[test]
+fn test_session_key() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // The mobile app holds the session key, the main key stays offline
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let app: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let (cave, cave_info) = initialize_cave(
+        &program,
+        &mint_key,
+        &user,
+        None,
+        10 * ONE_DEMO_TOKEN,
+    );
+    let session = Pubkey::find_program_address(
+        &[b"session", cave_info.as_ref(), app.keypair.pubkey().as_ref()],
+        &PROGRAM_ID,
+    ).0;
+    match program
+        .request()
+        .accounts(token_cave::accounts::RegisterSessionKey {
+            session,
+            cave_info,
+            owner: user.keypair.pubkey(),
+            system_program: system_program::ID,
+        })
+        .args(token_cave::instruction::RegisterSessionKey {
+            key: app.keypair.pubkey(),
+            scopes: SCOPE_WITHDRAW,
+            max_amount: 5 * ONE_DEMO_TOKEN,
+            expires_at: unix_now() + 600,
+        })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("register session key tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+
+    // The session can request tickets to the depositor, within its limit
+    let request_ticket = |id: u64, amount: u64, destination: Pubkey| program
+        .request()
+        .accounts(token_cave::accounts::RequestWithdrawTicket {
+            ticket: Pubkey::find_program_address(
+                &[b"ticket", cave_info.as_ref(), &id.to_le_bytes()],
+                &PROGRAM_ID,
+            ).0,
+            cave,
+            cave_info,
+            depositor: app.keypair.pubkey(),
+            destination,
+            system_program: system_program::ID,
+            config: get_config_pda(),
+        })
+        .accounts(AccountMeta::new(session, false))
+        .args(token_cave::instruction::RequestWithdrawTicket { amount })
+        .signer(&*app.keypair)
+        .payer(app.keypair.clone())
+        .send();
+    request_ticket(0, ONE_DEMO_TOKEN, app.ata).expect_err("should have failed");
+    match request_ticket(0, 3 * ONE_DEMO_TOKEN, user.ata) {
+        Ok(sig) => println!("session request ticket tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    request_ticket(1, 3 * ONE_DEMO_TOKEN, user.ata).expect_err("should have failed");
+
+    // It lacks the unlock scope
+    let unlock = || program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: app.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .accounts(AccountMeta::new_readonly(session, false))
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*app.keypair)
+        .payer(app.keypair.clone())
+        .send();
+    unlock().expect_err("should have failed");
+
+    // Once revoked, the session can't do anything
+    match program
+        .request()
+        .accounts(token_cave::accounts::RevokeSessionKey {
+            session,
+            owner: user.keypair.pubkey(),
+        })
+        .args(token_cave::instruction::RevokeSessionKey)
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("revoke session key tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    request_ticket(1, ONE_DEMO_TOKEN, user.ata).expect_err("should have failed");
+    let cave_info_account: CaveInfo = program
+        .account(cave_info)
+        .unwrap();
+    assert_eq!(cave_info_account.ticketed_amount, 3 * ONE_DEMO_TOKEN);
+
+    // The session key paid the ticket's rent, so cancelling refunds it
+    let ticket = Pubkey::find_program_address(
+        &[b"ticket", cave_info.as_ref(), &0_u64.to_le_bytes()],
+        &PROGRAM_ID,
+    ).0;
+    let ticket_account: WithdrawTicket = program
+        .account(ticket)
+        .unwrap();
+    assert_eq!(ticket_account.rent_payer, app.keypair.pubkey());
+    let cancel_ticket = |rent_payer: Pubkey| program
+        .request()
+        .accounts(token_cave::accounts::CancelWithdrawTicket {
+            ticket,
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            rent_payer,
+        })
+        .args(token_cave::instruction::CancelWithdrawTicket)
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send();
+    cancel_ticket(user.keypair.pubkey()).expect_err("should have failed");
+    let app_lamports = solana_client.get_balance(&app.keypair.pubkey()).unwrap();
+    match cancel_ticket(app.keypair.pubkey()) {
+        Ok(sig) => println!("cancel ticket tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    assert!(solana_client.get_balance(&app.keypair.pubkey()).unwrap() > app_lamports);
+}
+
+#[test]
+fn test_multisig_depositor() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+    let multisig_program: Program = Client::new_with_options(
+        Cluster::Localnet,
+        Rc::new(Keypair::from_bytes(&dev_key.to_bytes()).unwrap()),
+        CommitmentConfig::processed(),
+    ).program(mock_multisig::ID);
+
+    // A 2 of 3 multisig whose vault PDA holds SOL and tokens
+    let members: Vec<Keypair> = (0..3).map(|_| Keypair::generate(&mut OsRng)).collect();
+    let multisig = Keypair::generate(&mut OsRng);
+    let vault = Pubkey::find_program_address(
+        &[b"vault", multisig.pubkey().as_ref()],
+        &mock_multisig::ID,
+    ).0;
+    match multisig_program
+        .request()
+        .accounts(mock_multisig::accounts::CreateMultisig {
+            multisig: multisig.pubkey(),
+            vault,
+            payer: dev_key.pubkey(),
+            system_program: system_program::ID,
+        })
+        .args(mock_multisig::instruction::CreateMultisig {
+            members: members.iter().map(|member| member.pubkey()).collect(),
+            threshold: 2,
+        })
+        .signer(&multisig)
+        .send() {
+            Ok(sig) => println!("create multisig tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    let vault_ata = spl_associated_token_account::get_associated_token_address(
+        &vault,
+        &mint_key.pubkey(),
+    );
+    let fund_vault_tx: Transaction = Transaction::new_signed_with_payer(
+        &[
+            anchor_client::solana_sdk::system_instruction::transfer(
+                &dev_key.pubkey(),
+                &vault,
+                LAMPORTS_PER_SOL,
+            ),
+            spl_associated_token_account::instruction::create_associated_token_account(
+                &dev_key.pubkey(),
+                &vault,
+                &mint_key.pubkey(),
+            ),
+            anchor_spl::token::spl_token::instruction::mint_to(
+                &TOKEN_PROGRAM_ID,
+                &mint_key.pubkey(),
+                &vault_ata,
+                &dev_key.pubkey(),
+                &[&dev_key.pubkey()],
+                100 * ONE_DEMO_TOKEN,
+            ).unwrap(),
+        ],
+        Some(&dev_key.pubkey()),
+        &[&dev_key],
+        solana_client.get_latest_blockhash().expect("failed to get lastest blockhash")
+    );
+    solana_client.send_and_confirm_transaction(&fund_vault_tx)
+        .expect("failed to fund vault");
+
+    // Runs a token cave instruction through the multisig, with the vault
+    // signing through CPI
+    let execute = |approvers: &[&Keypair], accounts: Vec<AccountMeta>, data: Vec<u8>| {
+        let mut request = multisig_program
+            .request()
+            .accounts(mock_multisig::accounts::Execute {
+                multisig: multisig.pubkey(),
+                vault,
+                target_program: PROGRAM_ID,
+            });
+        for approver in approvers {
+            request = request.accounts(AccountMeta::new_readonly(approver.pubkey(), true));
+        }
+        for meta in accounts {
+            request = request.accounts(AccountMeta {
+                is_signer: meta.is_signer && meta.pubkey != vault,
+                ..meta
+            });
+        }
+        for approver in approvers {
+            request = request.signer(*approver);
+        }
+        request
+            .args(mock_multisig::instruction::Execute { data })
+            .send()
+    };
+
+    // The vault deposits, paying the rent itself
+    let (cave, cave_info) = get_cave_pdas(&vault_ata);
+    let initialize_accounts = token_cave::accounts::Initialize {
+        cave_info,
+        cave,
+        mint: mint_key.pubkey(),
+        depositor: vault,
+        payer: vault,
+        depositor_token_account: vault_ata,
+        token_program: TOKEN_PROGRAM_ID,
+        system_program: system_program::ID,
+        rent: anchor_client::solana_sdk::rent::Rent::id(),
+        config: get_config_pda(),
+        fee_override: get_fee_override_pda(&mint_key.pubkey()),
+        treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+        registry: get_registry_pda(&vault),
+    }.to_account_metas(None);
+    let initialize_data = token_cave::instruction::Initialize {
+        backup_address: None,
+        deposit_amount: 10 * ONE_DEMO_TOKEN,
+        timelock_duration: TEST_TIMELOCK_DURATION,
+        timelock_unit: TimelockUnit::UnixTimestamp,
+        withdraw_window: None,
+    }.data();
+    execute(&[&members[0]], initialize_accounts.clone(), initialize_data.clone())
+        .expect_err("should have failed");
+    match execute(&[&members[0], &members[2]], initialize_accounts, initialize_data) {
+        Ok(sig) => println!("multisig deposit tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    let cave_info_account: CaveInfo = program
+        .account(cave_info)
+        .unwrap();
+    assert_eq!(cave_info_account.depositor, vault);
+    assert_eq!(cave_info_account.rent_payer, vault);
+
+    // The vault unlocks, and once the timelock is up anyone can withdraw
+    // to the vault
+    match execute(
+        &[&members[1], &members[2]],
+        token_cave::accounts::Unlock {
+            cave_info,
+            depositor: vault,
+            config: get_config_pda(),
+        }.to_account_metas(None),
+        token_cave::instruction::Unlock { duress_salt: None }.data(),
+    ) {
+        Ok(sig) => println!("multisig unlock tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    std::thread::sleep(Duration::from_secs(1 + TEST_TIMELOCK_DURATION as u64));
+    match program
+        .request()
+        .accounts(token_cave::accounts::Withdraw {
+            cave_info,
+            cave,
+            mint: mint_key.pubkey(),
+            depositor: vault,
+            rent_payer: vault,
+            depositor_token_account: vault_ata,
+            token_program: TOKEN_PROGRAM_ID,
+            config: get_config_pda(),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&vault),
+        })
+        .args(token_cave::instruction::Withdraw)
+        .send() {
+            Ok(sig) => println!("withdraw tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(
+        100 * ONE_DEMO_TOKEN,
+        solana_client.get_token_account_balance(&vault_ata)
+            .expect("failed to get ata balance")
+            .amount
+            .parse::<u64>()
+            .unwrap(),
+    );
+}
+
+#[test]
+fn test_batch_withdraw_and_abort() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Three caves, two of which get unlocked
+    let backup: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let users: Vec<User> = (0..3)
+        .map(|_| get_funded_user(&dev_key, &mint_key, &solana_client)
+            .expect("failed to get funded user"))
+        .collect();
+    let caves: Vec<(Pubkey, Pubkey)> = users
+        .iter()
+        .map(|user| initialize_cave(
+            &program,
+            &mint_key,
+            user,
+            Some(backup.keypair.pubkey()),
+            10 * ONE_DEMO_TOKEN,
+        ))
+        .collect();
+    let unlock = |user: &User, cave_info: Pubkey| match program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("cave unlock tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    unlock(&users[0], caves[0].1);
+    unlock(&users[1], caves[1].1);
+    std::thread::sleep(Duration::from_secs(1 + TEST_TIMELOCK_DURATION as u64));
+
+    // One batch withdraws the matured caves and skips the locked one
+    let mut request = program
+        .request()
+        .accounts(token_cave::accounts::BatchWithdraw {
+            token_program: TOKEN_PROGRAM_ID,
+            config: get_config_pda(),
+        });
+    for (user, (cave, cave_info)) in users.iter().zip(&caves) {
+        request = request
+            .accounts(AccountMeta::new(*cave, false))
+            .accounts(AccountMeta::new(*cave_info, false))
+            .accounts(AccountMeta::new(user.ata, false))
+            .accounts(AccountMeta::new(user.keypair.pubkey(), false))
+            .accounts(AccountMeta::new(get_treasury_token_account(&mint_key.pubkey()), false))
+            .accounts(AccountMeta::new(get_registry_pda(&user.keypair.pubkey()), false));
+    }
+    match request.args(token_cave::instruction::BatchWithdraw).send() {
+        Ok(sig) => println!("batch withdraw tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    let balance = |ata: &Pubkey| solana_client.get_token_account_balance(ata)
+        .expect("failed to get ata balance")
+        .amount
+        .parse::<u64>()
+        .unwrap();
+    assert_eq!(balance(&users[0].ata), 100 * ONE_DEMO_TOKEN);
+    assert_eq!(balance(&users[1].ata), 100 * ONE_DEMO_TOKEN);
+    assert_eq!(balance(&users[2].ata), 90 * ONE_DEMO_TOKEN);
+
+    // The remaining cave can only be batch aborted once it is unlocking
+    let batch_abort = || program
+        .request()
+        .accounts(token_cave::accounts::BatchAbort {
+            depositor: users[2].keypair.pubkey(),
+            backup: backup.keypair.pubkey(),
+            backup_destination: get_backup_destination(backup.ata, users[2].keypair.pubkey()),
+            token_program: TOKEN_PROGRAM_ID,
+            registry: get_registry_pda(&users[2].keypair.pubkey()),
+        })
+        .accounts(AccountMeta::new(caves[2].0, false))
+        .accounts(AccountMeta::new(caves[2].1, false))
+        .accounts(AccountMeta::new(backup.ata, false))
+        .accounts(AccountMeta::new_readonly(mint_key.pubkey(), false))
+        .args(token_cave::instruction::BatchAbort)
+        .signer(&*users[2].keypair)
+        .payer(users[2].keypair.clone())
+        .send();
+    match batch_abort() {
+        Ok(sig) => println!("skipping batch abort tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(balance(&backup.ata), 100 * ONE_DEMO_TOKEN);
+    unlock(&users[2], caves[2].1);
+    match batch_abort() {
+        Ok(sig) => println!("batch abort tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(balance(&backup.ata), 110 * ONE_DEMO_TOKEN);
+    assert!(solana_client.get_account(&caves[2].1).is_err());
+}
+
+#[test]
+fn test_batch_abort_lookup_table() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Two caves of one depositor, aborting to a backup that never held
+    // the mint
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    // Caves are keyed by the depositor token account, so the second cave
+    // needs a second one
+    let second_token_account = Keypair::generate(&mut OsRng);
+    match program
+        .request()
+        .instruction(system_instruction::create_account(
+            &user.keypair.pubkey(),
+            &second_token_account.pubkey(),
+            solana_client.get_minimum_balance_for_rent_exemption(TokenAccount::LEN).unwrap(),
+            TokenAccount::LEN as u64,
+            &TOKEN_PROGRAM_ID,
+        ))
+        .instruction(spl_token_instruction::initialize_account3(
+            &TOKEN_PROGRAM_ID,
+            &second_token_account.pubkey(),
+            &mint_key.pubkey(),
+            &user.keypair.pubkey(),
+        ).unwrap())
+        .instruction(spl_token_instruction::transfer(
+            &TOKEN_PROGRAM_ID,
+            &user.ata,
+            &second_token_account.pubkey(),
+            &user.keypair.pubkey(),
+            &[],
+            10 * ONE_DEMO_TOKEN,
+        ).unwrap())
+        .signer(&second_token_account)
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("create token account tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    let backup = Keypair::generate(&mut OsRng).pubkey();
+    let backup_ata = spl_associated_token_account::get_associated_token_address(
+        &backup,
+        &mint_key.pubkey(),
+    );
+    let caves: Vec<(Pubkey, Pubkey)> = [user.ata, second_token_account.pubkey()]
+        .iter()
+        .map(|token_account| initialize_cave(
+            &program,
+            &mint_key,
+            &User { keypair: user.keypair.clone(), ata: *token_account },
+            Some(backup),
+            10 * ONE_DEMO_TOKEN,
+        ))
+        .collect();
+    for (_, cave_info) in &caves {
+        match program
+            .request()
+            .accounts(token_cave::accounts::Unlock {
+                cave_info: *cave_info,
+                depositor: user.keypair.pubkey(),
+                config: get_config_pda(),
+            })
+            .args(token_cave::instruction::Unlock { duress_salt: None })
+            .signer(&*user.keypair)
+            .payer(user.keypair.clone())
+            .send() {
+                Ok(sig) => println!("cave unlock tx signature: {sig}"),
+                Err(e) => panic!("{e:#?}"),
+        };
+    }
+
+    // Put the groups in an address lookup table
+    let groups: Vec<Pubkey> = caves
+        .iter()
+        .flat_map(|(cave, cave_info)| [*cave, *cave_info, backup_ata, mint_key.pubkey()])
+        .collect();
+    let recent_slot = solana_client
+        .get_slot_with_commitment(CommitmentConfig::finalized())
+        .unwrap();
+    let (create_table, lookup_table) = address_lookup_table::instruction::create_lookup_table(
+        user.keypair.pubkey(),
+        user.keypair.pubkey(),
+        recent_slot,
+    );
+    let extend_table = address_lookup_table::instruction::extend_lookup_table(
+        lookup_table,
+        user.keypair.pubkey(),
+        Some(user.keypair.pubkey()),
+        groups.clone(),
+    );
+    match program
+        .request()
+        .instruction(create_table)
+        .instruction(extend_table)
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("create lookup table tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+
+    // Addresses can only be loaded from the slot after they were added
+    std::thread::sleep(Duration::from_secs(1));
+
+    // One v0 transaction aborts both caves and creates the backup's account
+    let mut accounts = token_cave::accounts::BatchAbort {
+        depositor: user.keypair.pubkey(),
+        backup,
+        backup_destination: get_backup_destination(backup_ata, user.keypair.pubkey()),
+        token_program: TOKEN_PROGRAM_ID,
+        registry: get_registry_pda(&user.keypair.pubkey()),
+    }.to_account_metas(None);
+    accounts.extend(groups.chunks(BATCH_ABORT_GROUP_SIZE).flat_map(|group| [
+        AccountMeta::new(group[0], false),
+        AccountMeta::new(group[1], false),
+        AccountMeta::new(group[2], false),
+        AccountMeta::new_readonly(group[3], false),
+    ]));
+    let batch_abort = Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data: token_cave::instruction::BatchAbort.data(),
+    };
+    match send_v0_transaction(
+        &solana_client,
+        &user.keypair,
+        batch_abort,
+        lookup_table,
+        &groups,
+    ) {
+        Ok(sig) => println!("lookup table batch abort tx signature: {sig}"),
+        Err(e) => panic!("{e:#?}"),
+    };
+    assert_eq!(
+        solana_client.get_token_account_balance(&backup_ata)
+            .expect("failed to get ata balance")
+            .amount
+            .parse::<u64>()
+            .unwrap(),
+        20 * ONE_DEMO_TOKEN,
+    );
+    for (cave, cave_info) in &caves {
+        assert!(solana_client.get_account(cave).is_err());
+        assert!(solana_client.get_account(cave_info).is_err());
+    }
+}
+
+#[test]
+fn test_cave_registry() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Get funded user with a cave
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let (cave, cave_info) = initialize_cave(&program, &mint_key, &user, None, 10 * ONE_DEMO_TOKEN);
+
+    // The registry lists the cave
+    let registry = get_registry_pda(&user.keypair.pubkey());
+    let registry_account: CaveRegistry = program
+        .account(registry)
+        .unwrap();
+    assert_eq!(registry_account.depositor, user.keypair.pubkey());
+    assert_eq!(registry_account.caves, vec![cave_info]);
+
+    // So does a scan filtered on the depositor
+    let caves: Vec<(Pubkey, CaveInfo)> = program
+        .accounts(vec![RpcFilterType::Memcmp(Memcmp {
+            offset: CAVE_INFO_DEPOSITOR_OFFSET,
+            bytes: MemcmpEncodedBytes::Bytes(user.keypair.pubkey().to_bytes().to_vec()),
+            encoding: None,
+        })])
+        .unwrap();
+    assert_eq!(caves.len(), 1);
+    assert_eq!(caves[0].0, cave_info);
+
+    // Withdrawing removes the cave again
+    match program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("cave unlock tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    std::thread::sleep(Duration::from_secs(1 + TEST_TIMELOCK_DURATION as u64));
+    match program
+        .request()
+        .accounts(token_cave::accounts::Withdraw {
+            cave_info,
+            cave,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            rent_payer: user.keypair.pubkey(),
+            depositor_token_account: user.ata,
+            token_program: TOKEN_PROGRAM_ID,
+            config: get_config_pda(),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry,
+        })
+        .args(token_cave::instruction::Withdraw)
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("withdraw tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    let registry_account: CaveRegistry = program
+        .account(registry)
+        .unwrap();
+    assert!(registry_account.caves.is_empty());
+}
+
+#[test]
+fn test_voter_weight_record() {
+
+    let (dev_key, mint_key, program, solana_client) = get_test_env();
+
+    // Get funded user with a cave locked for the longest duration, which
+    // gives a weight equal to the deposited amount
+    let user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let (_, cave_info) = initialize_cave_with_timelock(
+        &program,
+        &mint_key,
+        &user,
+        None,
+        10 * ONE_DEMO_TOKEN,
+        TimelockUnit::UnixTimestamp.max_duration(),
+        TimelockUnit::UnixTimestamp,
+        None,
+    );
+
+    // Create the record for some realm
+    let realm = Keypair::generate(&mut OsRng).pubkey();
+    let create_record = |owner: &User| {
+        let (voter_weight_record, _) = Pubkey::find_program_address(
+            &[
+                b"voter-weight-record",
+                realm.as_ref(),
+                mint_key.pubkey().as_ref(),
+                owner.keypair.pubkey().as_ref(),
+            ],
+            &PROGRAM_ID,
+        );
+        match program
+            .request()
+            .accounts(token_cave::accounts::CreateVoterWeightRecord {
+                voter_weight_record,
+                governing_token_mint: mint_key.pubkey(),
+                governing_token_owner: owner.keypair.pubkey(),
+                system_program: system_program::ID,
+            })
+            .args(token_cave::instruction::CreateVoterWeightRecord { realm })
+            .signer(&*owner.keypair)
+            .payer(owner.keypair.clone())
+            .send() {
+                Ok(sig) => println!("create voter weight record tx signature: {sig}"),
+                Err(e) => panic!("{e:#?}"),
+        };
+        voter_weight_record
+    };
+    let voter_weight_record = create_record(&user);
+
+    // The record has the layout spl-governance reads
+    let data = solana_client.get_account_data(&voter_weight_record)
+        .expect("failed to get voter weight record");
+    assert_eq!(data.len(), VOTER_WEIGHT_RECORD_SIZE);
+    assert_eq!(data[..8], hash(b"account:VoterWeightRecord").to_bytes()[..8]);
+    assert_eq!(data[8..40], realm.to_bytes());
+    assert_eq!(data[40..72], mint_key.pubkey().to_bytes());
+    assert_eq!(data[72..104], user.keypair.pubkey().to_bytes());
+    assert_eq!(data[104..112], 0_u64.to_le_bytes());
+
+    // Updates set the weight for the current slot only
+    let update = |voter_weight_record: Pubkey, cave_infos: Vec<Pubkey>| {
+        let mut request = program
+            .request()
+            .accounts(token_cave::accounts::UpdateVoterWeightRecord {
+                voter_weight_record,
+            });
+        for cave_info in cave_infos {
+            request = request.accounts(AccountMeta::new_readonly(cave_info, false));
+        }
+        request
+            .args(token_cave::instruction::UpdateVoterWeightRecord)
+            .send()
+    };
+    let check_weight = |sig, expected_weight: u64| {
+        let record: VoterWeightRecord = program
+            .account(voter_weight_record)
+            .unwrap();
+        let slot = solana_client.get_signature_statuses(&[sig])
+            .expect("failed to get signature status")
+            .value[0]
+            .as_ref()
+            .expect("missing signature status")
+            .slot;
+        assert_eq!(record.voter_weight, expected_weight);
+        assert_eq!(record.voter_weight_expiry, Some(slot));
+    };
+    let cave_info_account: CaveInfo = program
+        .account(cave_info)
+        .unwrap();
+    match update(voter_weight_record, vec![cave_info]) {
+        Ok(sig) => check_weight(sig, cave_info_account.deposited_amount),
+        Err(e) => panic!("{e:#?}"),
+    };
+
+    // A cave counts once
+    update(voter_weight_record, vec![cave_info, cave_info]).expect_err("should have failed");
+
+    // Caves of another depositor don't count
+    let other_user: User = get_funded_user(&dev_key, &mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let (_, other_cave_info) = initialize_cave(&program, &mint_key, &other_user, None, 10 * ONE_DEMO_TOKEN);
+    update(voter_weight_record, vec![other_cave_info]).expect_err("should have failed");
+
+    // Neither do caves of another mint
+    let other_mint_key = Keypair::generate(&mut OsRng);
+    initialize_mint_account(&dev_key, &other_mint_key, &solana_client)
+        .expect("failed to initialize other mint");
+    let other_mint_user: User = get_funded_user(&dev_key, &other_mint_key, &solana_client)
+        .expect("failed to get funded user");
+    let (_, other_mint_cave_info) = initialize_cave(&program, &other_mint_key, &other_mint_user, None, 10 * ONE_DEMO_TOKEN);
+    let other_mint_user_record = create_record(&other_mint_user);
+    update(other_mint_user_record, vec![other_mint_cave_info]).expect_err("should have failed");
+
+    // An unlock takes the weight to zero
+    match program
+        .request()
+        .accounts(token_cave::accounts::Unlock {
+            cave_info,
+            depositor: user.keypair.pubkey(),
+            config: get_config_pda(),
+        })
+        .args(token_cave::instruction::Unlock { duress_salt: None })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("cave unlock tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+    match update(voter_weight_record, vec![cave_info]) {
+        Ok(sig) => check_weight(sig, 0),
+        Err(e) => panic!("{e:#?}"),
+    };
+}
+
+#[test]
+fn test_pnft_transfer_instruction() {
+
+    // The localnet validator doesn't load the token metadata program, so
+    // check the TransferV1 instruction the pNFT instructions invoke
+    // against the token metadata program's interface instead
+    let keys = TransferV1Keys {
+        token: Pubkey::new_unique(),
+        token_owner: Pubkey::new_unique(),
+        destination: Pubkey::new_unique(),
+        destination_owner: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        metadata: Pubkey::new_unique(),
+        edition: Pubkey::new_unique(),
+        owner_token_record: Pubkey::new_unique(),
+        destination_token_record: Pubkey::new_unique(),
+        authority: Pubkey::new_unique(),
+        payer: Pubkey::new_unique(),
+        system_program: system_program::ID,
+        sysvar_instructions: anchor_client::solana_sdk::sysvar::instructions::ID,
+        token_program: TOKEN_PROGRAM_ID,
+        associated_token_program: spl_associated_token_account::ID,
+        authorization_rules_program: TOKEN_METADATA_PROGRAM_ID,
+        authorization_rules: TOKEN_METADATA_PROGRAM_ID,
+    };
+    let instruction = transfer_v1_instruction(&keys);
+    assert_eq!(
+        instruction.program_id,
+        "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s".parse::<Pubkey>().unwrap(),
+    );
+
+    // Transfer discriminator, then TransferArgs::V1 with an amount of one
+    // and no authorization data
+    assert_eq!(instruction.data, vec![49, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+    // Accounts in the program's order, with their signer and writable flags
+    let expected = vec![
+        AccountMeta::new(keys.token, false),
+        AccountMeta::new_readonly(keys.token_owner, false),
+        AccountMeta::new(keys.destination, false),
+        AccountMeta::new_readonly(keys.destination_owner, false),
+        AccountMeta::new_readonly(keys.mint, false),
+        AccountMeta::new(keys.metadata, false),
+        AccountMeta::new_readonly(keys.edition, false),
+        AccountMeta::new(keys.owner_token_record, false),
+        AccountMeta::new(keys.destination_token_record, false),
+        AccountMeta::new_readonly(keys.authority, true),
+        AccountMeta::new(keys.payer, true),
+        AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(anchor_client::solana_sdk::sysvar::instructions::ID, false),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+        AccountMeta::new_readonly(TOKEN_METADATA_PROGRAM_ID, false),
+        AccountMeta::new_readonly(TOKEN_METADATA_PROGRAM_ID, false),
+    ];
+    assert_eq!(instruction.accounts, expected);
+}
+
+/// Gets the dev and mint keys, the program client and an rpc client,
+/// and makes sure the demo mint exists
+fn get_test_env() -> (Keypair, Keypair, Program, RpcClient) {
+
+    // Get dev and mint key.
+    let dev_key: Keypair = read_keypair_file(&*shellexpand::tilde("../../dev_key.json"))
+        .expect("Example requires a keypair file");
+    let dev_key_for_client: Keypair = read_keypair_file(&*shellexpand::tilde("../../dev_key.json"))
+        .expect("Example requires a keypair file");
+    let mint_key: Keypair = read_keypair_file(&*shellexpand::tilde("../../mint_key.json"))
+            .expect("Example requires a keypair file");
+
+    // Get client, program, and rpc client
+    let url: Cluster = Cluster::Localnet;
+    let client: Client = Client::new_with_options(url, Rc::new(dev_key_for_client), CommitmentConfig::processed());
+    let program: Program = client.program(PROGRAM_ID);
+    let solana_client: RpcClient = program.rpc();
+
+    // Initialize mint account
+    println!(
+        "initialize token mint tx signature: {}",
+        initialize_mint_account(&dev_key, &mint_key, &solana_client)
+            .unwrap_or("FAILED TO INITIALIZE MINT ACCOUNT".to_string())
+    );
+
+    // Initialize program config
+    println!(
+        "initialize config tx signature: {}",
+        initialize_config_account(&dev_key, &program)
+            .unwrap_or("FAILED TO INITIALIZE CONFIG".to_string())
+    );
+
+    (dev_key, mint_key, program, solana_client)
+}
+
+/// Returns the cave and cave_info PDAs for a depositor token account
+fn get_cave_pdas(depositor_token_account: &Pubkey) -> (Pubkey, Pubkey) {
+    let (cave, _) = Pubkey::find_program_address(
+        &[depositor_token_account.as_ref()],
+        &PROGRAM_ID,
+    );
+    let (cave_info, _) = Pubkey::find_program_address(
+        &[cave.as_ref()],
+        &PROGRAM_ID,
+    );
+    (cave, cave_info)
+}
+
+/// Deposits `deposit_amount` from the user's ata into a new cave
+fn initialize_cave(
+    program: &Program,
+    mint_key: &Keypair,
+    user: &User,
+    backup_address: Option<Pubkey>,
+    deposit_amount: u64,
+) -> (Pubkey, Pubkey) {
+    initialize_cave_with_timelock(
+        program,
+        mint_key,
+        user,
+        backup_address,
+        deposit_amount,
+        TEST_TIMELOCK_DURATION,
+        TimelockUnit::UnixTimestamp,
+        None,
+    )
+}
+
+/// Deposits `deposit_amount` from the user's ata into a new cave with the
+/// given timelock and withdraw window
+fn initialize_cave_with_timelock(
+    program: &Program,
+    mint_key: &Keypair,
+    user: &User,
+    backup_address: Option<Pubkey>,
+    deposit_amount: u64,
+    timelock_duration: u32,
+    timelock_unit: TimelockUnit,
+    withdraw_window: Option<u32>,
+) -> (Pubkey, Pubkey) {
+
+    let (cave, cave_info) = get_cave_pdas(&user.ata);
+    match program
+        .request()
+        .accounts(token_cave::accounts::Initialize {
+            cave_info,
+            cave,
+            mint: mint_key.pubkey(),
+            depositor: user.keypair.pubkey(),
+            payer: user.keypair.pubkey(),
+            depositor_token_account: user.ata,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: anchor_client::solana_sdk::rent::Rent::id(),
+            config: get_config_pda(),
+            fee_override: get_fee_override_pda(&mint_key.pubkey()),
+            treasury_token_account: get_treasury_token_account(&mint_key.pubkey()),
+            registry: get_registry_pda(&user.keypair.pubkey()),
+        })
+        .args(token_cave::instruction::Initialize {
+            backup_address,
+            deposit_amount,
+            timelock_duration,
+            timelock_unit,
+            withdraw_window,
+        })
+        .signer(&*user.keypair)
+        .payer(user.keypair.clone())
+        .send() {
+            Ok(sig) => println!("deposit tx signature: {sig}"),
+            Err(e) => panic!("{e:#?}"),
+    };
+
+    (cave, cave_info)
+}
+
+/// Returns the program config PDA
+fn get_config_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"config"], &PROGRAM_ID).0
+}
+
+/// Returns the cave registry PDA of a depositor
+fn get_registry_pda(depositor: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"registry", depositor.as_ref()], &PROGRAM_ID).0
+}
+
+/// Creates the program config with the dev key, which is the upgrade
+/// authority of the test deployment, as config and pause authority
+fn initialize_config_account(
+    dev_key: &Keypair,
+    program: &Program,
+) -> Result<String, anchor_client::ClientError> {
+    let (program_data, _) = Pubkey::find_program_address(
+        &[PROGRAM_ID.as_ref()],
+        &bpf_loader_upgradeable::ID,
+    );
+    program
+        .request()
+        .accounts(token_cave::accounts::InitializeConfig {
+            config: get_config_pda(),
+            authority: dev_key.pubkey(),
+            program: PROGRAM_ID,
+            program_data,
+            system_program: system_program::ID,
+        })
+        .args(token_cave::instruction::InitializeConfig {
+            pause_authority: dev_key.pubkey(),
+            withdraw_allowed_while_paused: true,
+        })
+        .send()
+        .map(|s| s.to_string())
+}
+
+/// Returns the fee override PDA of a mint
+fn get_fee_override_pda(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"fee", mint.as_ref()], &PROGRAM_ID).0
+}
+
+/// Returns the treasury's token account for a mint. The config is created
+/// with the dev key as treasury.
+fn get_treasury_token_account(mint: &Pubkey) -> Pubkey {
+    let dev_key: Keypair = read_keypair_file(&*shellexpand::tilde("../../dev_key.json"))
+        .expect("Example requires a keypair file");
+    spl_associated_token_account::get_associated_token_address(&dev_key.pubkey(), mint)
+}
+
+/// Abort destination accounts for `backup_spl_account`, paid by `payer`
+fn get_backup_destination(
+    backup_spl_account: Pubkey,
+    payer: Pubkey,
+) -> token_cave::accounts::BackupDestination {
+    token_cave::accounts::BackupDestination {
+        backup_spl_account,
+        payer,
+        system_program: system_program::ID,
+        associated_token_program: spl_associated_token_account::ID,
+        rent: anchor_client::solana_sdk::rent::Rent::id(),
+    }
+}
+
+/// Sends `instruction` signed by `payer` in a v0 transaction that loads
+/// the accounts in `table_addresses` from `lookup_table`. Program ids and
+/// signers stay in the message itself.
+fn send_v0_transaction(
+    solana_client: &RpcClient,
+    payer: &Keypair,
+    instruction: Instruction,
+    lookup_table: Pubkey,
+    table_addresses: &[Pubkey],
+) -> Result<Signature> {
+
+    // Merge the accounts, keeping the loaded ones apart
+    let mut static_keys = vec![AccountMeta::new(payer.pubkey(), true)];
+    let mut loaded_keys: Vec<AccountMeta> = Vec::new();
+    for meta in instruction.accounts.iter().cloned().chain([
+        AccountMeta::new_readonly(instruction.program_id, false),
+    ]) {
+        let is_static = meta.is_signer
+            || meta.pubkey == instruction.program_id
+            || !table_addresses.contains(&meta.pubkey);
+        let keys = if is_static { &mut static_keys } else { &mut loaded_keys };
+        match keys.iter_mut().find(|key| key.pubkey == meta.pubkey) {
+            Some(key) => {
+                key.is_signer |= meta.is_signer;
+                key.is_writable |= meta.is_writable;
+            },
+            None => keys.push(meta),
+        }
+    }
+
+    // Order them the way the runtime indexes them: signers first, writable
+    // before readonly, then the writable and readonly loaded accounts
+    static_keys.sort_by_key(|key| (!key.is_signer, !key.is_writable));
+    loaded_keys.sort_by_key(|key| !key.is_writable);
+    let index = |pubkey: &Pubkey| static_keys
+        .iter()
+        .chain(&loaded_keys)
+        .position(|key| key.pubkey == *pubkey)
+        .unwrap() as u8;
+    let table_index = |key: &AccountMeta| table_addresses
+        .iter()
+        .position(|address| *address == key.pubkey)
+        .unwrap() as u8;
+
+    let message = VersionedMessage::V0(v0::Message {
+        header: MessageHeader {
+            num_required_signatures: static_keys.iter().filter(|key| key.is_signer).count() as u8,
+            num_readonly_signed_accounts: static_keys
+                .iter()
+                .filter(|key| key.is_signer && !key.is_writable)
+                .count() as u8,
+            num_readonly_unsigned_accounts: static_keys
+                .iter()
+                .filter(|key| !key.is_signer && !key.is_writable)
+                .count() as u8,
+        },
+        account_keys: static_keys.iter().map(|key| key.pubkey).collect(),
+        recent_blockhash: solana_client.get_latest_blockhash()?,
+        instructions: vec![CompiledInstruction {
+            program_id_index: index(&instruction.program_id),
+            accounts: instruction.accounts.iter().map(|key| index(&key.pubkey)).collect(),
+            data: instruction.data,
+        }],
+        address_table_lookups: vec![v0::MessageAddressTableLookup {
+            account_key: lookup_table,
+            writable_indexes: loaded_keys
+                .iter()
+                .filter(|key| key.is_writable)
+                .map(table_index)
+                .collect(),
+            readonly_indexes: loaded_keys
+                .iter()
+                .filter(|key| !key.is_writable)
+                .map(table_index)
+                .collect(),
+        }],
+    });
+
+    // The payer is the only signer, since lookup tables can't hold signers
+    let transaction = VersionedTransaction {
+        signatures: vec![payer.sign_message(&message.serialize())],
+        message,
+    };
+    let signature: String = solana_client.send(
+        RpcRequest::SendTransaction,
+        json!([
+            base64::encode(bincode::serialize(&transaction)?),
+            { "encoding": "base64" },
+        ]),
+    )?;
+    let signature: Signature = signature.parse()?;
+    loop {
+        match solana_client.get_signature_status(&signature)? {
+            Some(result) => return result.map(|_| signature).map_err(Into::into),
+            None => std::thread::sleep(Duration::from_millis(200)),
+        }
+    }
 }
 
 fn get_funded_user(